@@ -0,0 +1,394 @@
+use chrono::{Datelike, NaiveDate};
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+use crate::repo::TipsRepo;
+
+mod fairness;
+
+pub use fairness::{fairness, FairnessParams};
+
+const WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+/// Date range the analytics are computed over; either end may be left open.
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+#[serde(default)]
+pub struct AnalyticsRange {
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+}
+
+#[derive(Debug, Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Breakdown {
+    #[default]
+    Role,
+    Weekday,
+    Month,
+}
+
+/// One night of service: the pool, the hours it was split over and the
+/// sales it came from, when the night's inputs were stored.
+#[derive(Debug, Serialize)]
+pub struct NightTotals {
+    pub date: String,
+    pub weekday: String,
+    pub staff: u32,
+    pub tip_pool: f64,
+    pub tipped_hours: f64,
+    pub tips_per_tipped_hour: Option<f64>,
+    pub total_sales: Option<f64>,
+    pub tips_percent_of_sales: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupTotals {
+    pub group: String,
+    pub nights: u32,
+    pub hours: f64,
+    pub tips: f64,
+    pub average_tips_per_night: f64,
+    pub tips_per_hour: Option<f64>,
+    /// Only for weekday and month breakdowns; tips are not attributed to
+    /// sales by role.
+    pub tips_percent_of_sales: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MonthComparison {
+    pub month: u32,
+    pub tip_pool: f64,
+    pub previous_tip_pool: f64,
+    pub tips_per_tipped_hour: Option<f64>,
+    pub previous_tips_per_tipped_hour: Option<f64>,
+    /// Change in the month's tip pool from the previous year, as a percent.
+    pub change_percent: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct YearOverYear {
+    pub year: i32,
+    pub previous_year: i32,
+    pub months: Vec<MonthComparison>,
+}
+
+pub async fn nights(
+    repo: &dyn TipsRepo,
+    range: &AnalyticsRange,
+) -> Result<Vec<NightTotals>, Box<dyn Error>> {
+    let tips = load_tips(repo, range).await?;
+    let nights = load_nights(repo, range).await?;
+    let df = nightly(tips, nights)?
+        .sort(["date"], Default::default())
+        .collect()?;
+
+    let dates = strings(&df, "date")?;
+    let weekdays = strings(&df, "weekday")?;
+    let staff = counts(&df, "staff")?;
+    let tip_pool = floats(&df, "tip_pool")?;
+    let tipped_hours = floats(&df, "tipped_hours")?;
+    let tips_per_tipped_hour = floats(&df, "tips_per_tipped_hour")?;
+    let total_sales = floats(&df, "total_sales")?;
+    let tips_percent_of_sales = floats(&df, "tips_percent_of_sales")?;
+
+    Ok((0..df.height())
+        .map(|i| NightTotals {
+            date: dates[i].clone(),
+            weekday: weekdays[i].clone(),
+            staff: staff[i],
+            tip_pool: tip_pool[i].unwrap_or_default(),
+            tipped_hours: tipped_hours[i].unwrap_or_default(),
+            tips_per_tipped_hour: tips_per_tipped_hour[i],
+            total_sales: total_sales[i],
+            tips_percent_of_sales: tips_percent_of_sales[i],
+        })
+        .collect())
+}
+
+pub async fn breakdown(
+    repo: &dyn TipsRepo,
+    range: &AnalyticsRange,
+    by: Breakdown,
+) -> Result<Vec<GroupTotals>, Box<dyn Error>> {
+    let tips = load_tips(repo, range).await?;
+
+    let df = match by {
+        Breakdown::Role => tips
+            .group_by([col("role").alias("group")])
+            .agg([
+                col("date").n_unique().alias("nights"),
+                col("duration").sum().alias("hours"),
+                col("net_tips").sum().alias("tips"),
+                lit(NULL)
+                    .cast(DataType::Float64)
+                    .alias("tips_percent_of_sales"),
+            ])
+            .sort(["group"], Default::default()),
+        Breakdown::Weekday | Breakdown::Month => {
+            let (keys, label) = match by {
+                Breakdown::Weekday => (["weekday_number", "weekday"].as_slice(), "weekday"),
+                _ => (["month"].as_slice(), "month"),
+            };
+            nightly(tips, load_nights(repo, range).await?)?
+                .group_by(keys.iter().map(|key| col(key)).collect::<Vec<_>>())
+                .agg([
+                    col("date").count().alias("nights"),
+                    col("tipped_hours").sum().alias("hours"),
+                    col("tip_pool").sum().alias("tips"),
+                    percent(
+                        col("tip_pool")
+                            .filter(col("total_sales").is_not_null())
+                            .sum(),
+                        col("total_sales").sum(),
+                    )
+                    .alias("tips_percent_of_sales"),
+                ])
+                .sort([keys[0]], Default::default())
+                .with_column(col(label).alias("group"))
+        }
+    }
+    .with_columns([
+        (col("tips") / col("nights").cast(DataType::Float64)).alias("average_tips_per_night"),
+        ratio(col("tips"), col("hours")).alias("tips_per_hour"),
+    ])
+    .collect()?;
+
+    let groups = strings(&df, "group")?;
+    let nights = counts(&df, "nights")?;
+    let hours = floats(&df, "hours")?;
+    let tips = floats(&df, "tips")?;
+    let average_tips_per_night = floats(&df, "average_tips_per_night")?;
+    let tips_per_hour = floats(&df, "tips_per_hour")?;
+    let tips_percent_of_sales = floats(&df, "tips_percent_of_sales")?;
+
+    Ok((0..df.height())
+        .map(|i| GroupTotals {
+            group: groups[i].clone(),
+            nights: nights[i],
+            hours: hours[i].unwrap_or_default(),
+            tips: tips[i].unwrap_or_default(),
+            average_tips_per_night: average_tips_per_night[i].unwrap_or_default(),
+            tips_per_hour: tips_per_hour[i],
+            tips_percent_of_sales: tips_percent_of_sales[i],
+        })
+        .collect())
+}
+
+/// The previous and given calendar years, or `None` when either falls
+/// outside the dates chrono can represent.
+pub fn year_over_year_range(year: i32) -> Option<AnalyticsRange> {
+    let start = NaiveDate::from_ymd_opt(year.checked_sub(1)?, 1, 1)?;
+    let end = NaiveDate::from_ymd_opt(year, 12, 31)?;
+    Some(AnalyticsRange {
+        start: Some(start),
+        end: Some(end),
+    })
+}
+
+pub async fn year_over_year(
+    repo: &dyn TipsRepo,
+    year: i32,
+) -> Result<YearOverYear, Box<dyn Error>> {
+    let range = year_over_year_range(year).ok_or_else(|| format!("Invalid year: {}", year))?;
+
+    let tips = load_tips(repo, &range).await?;
+    let nights = load_nights(repo, &range).await?;
+    let monthly = nightly(tips, nights)?
+        .group_by([col("year"), col("month_number")])
+        .agg([
+            col("tip_pool").sum().alias("tip_pool"),
+            col("tipped_hours").sum().alias("tipped_hours"),
+        ])
+        .with_column(ratio(col("tip_pool"), col("tipped_hours")).alias("tips_per_tipped_hour"));
+
+    let for_year = |year: i32, prefix: &str| {
+        monthly.clone().filter(col("year").eq(lit(year))).select([
+            col("month_number"),
+            col("tip_pool").alias(&format!("{}tip_pool", prefix)),
+            col("tips_per_tipped_hour").alias(&format!("{}tips_per_tipped_hour", prefix)),
+        ])
+    };
+
+    let months = df!("month_number" => (1..=12u32).collect::<Vec<_>>())?;
+    let df = months
+        .lazy()
+        .left_join(for_year(year, ""), col("month_number"), col("month_number"))
+        .left_join(
+            for_year(year - 1, "previous_"),
+            col("month_number"),
+            col("month_number"),
+        )
+        .with_columns([
+            col("tip_pool").fill_null(lit(0.0)),
+            col("previous_tip_pool").fill_null(lit(0.0)),
+        ])
+        .with_column(
+            percent(
+                col("tip_pool") - col("previous_tip_pool"),
+                col("previous_tip_pool"),
+            )
+            .alias("change_percent"),
+        )
+        .sort(["month_number"], Default::default())
+        .collect()?;
+
+    let month_numbers = counts(&df, "month_number")?;
+    let tip_pool = floats(&df, "tip_pool")?;
+    let previous_tip_pool = floats(&df, "previous_tip_pool")?;
+    let tips_per_tipped_hour = floats(&df, "tips_per_tipped_hour")?;
+    let previous_tips_per_tipped_hour = floats(&df, "previous_tips_per_tipped_hour")?;
+    let change_percent = floats(&df, "change_percent")?;
+
+    Ok(YearOverYear {
+        year,
+        previous_year: year - 1,
+        months: (0..df.height())
+            .map(|i| MonthComparison {
+                month: month_numbers[i],
+                tip_pool: tip_pool[i].unwrap_or_default(),
+                previous_tip_pool: previous_tip_pool[i].unwrap_or_default(),
+                tips_per_tipped_hour: tips_per_tipped_hour[i],
+                previous_tips_per_tipped_hour: previous_tips_per_tipped_hour[i],
+                change_percent: change_percent[i],
+            })
+            .collect(),
+    })
+}
+
+/// One row per night: the pool is the stored tip total for the night when
+/// its inputs were saved, otherwise the sum of everyone's net tips.
+fn nightly(tips: LazyFrame, nights: LazyFrame) -> Result<LazyFrame, PolarsError> {
+    Ok(tips
+        .group_by([col("date")])
+        .agg([
+            col("weekday").first(),
+            col("weekday_number").first(),
+            col("month").first(),
+            col("month_number").first(),
+            col("year").first(),
+            col("eid").count().alias("staff"),
+            col("net_tips").sum().alias("tips_paid"),
+            col("duration")
+                .filter(in_pool())
+                .sum()
+                .alias("tipped_hours"),
+        ])
+        .left_join(nights, col("date"), col("date"))
+        .with_column(
+            col("total_tips")
+                .fill_null(col("tips_paid"))
+                .alias("tip_pool"),
+        )
+        .with_columns([
+            ratio(col("tip_pool"), col("tipped_hours")).alias("tips_per_tipped_hour"),
+            percent(col("tip_pool"), col("total_sales")).alias("tips_percent_of_sales"),
+        ]))
+}
+
+/// Rows worked in a role that shares the pool. Stewards are paid a tip-out
+/// from it instead.
+fn in_pool() -> Expr {
+    col("role").neq(lit("Steward"))
+}
+
+/// `numerator / denominator`, or null when there is nothing to divide by.
+fn ratio(numerator: Expr, denominator: Expr) -> Expr {
+    when(denominator.clone().gt(lit(0.0)))
+        .then(numerator.cast(DataType::Float64) / denominator.cast(DataType::Float64))
+        .otherwise(lit(NULL).cast(DataType::Float64))
+}
+
+fn percent(part: Expr, whole: Expr) -> Expr {
+    ratio(part, whole) * lit(100.0)
+}
+
+async fn load_tips(
+    repo: &dyn TipsRepo,
+    range: &AnalyticsRange,
+) -> Result<LazyFrame, Box<dyn Error>> {
+    let rows = repo.analytics_tips(range.clone()).await?;
+
+    let df = df!(
+        "date" => rows.iter().map(|row| row.date.to_string()).collect::<Vec<_>>(),
+        "weekday" => rows.iter().map(|row| weekday(row.date)).collect::<Vec<_>>(),
+        "weekday_number" => rows.iter().map(|row| row.date.weekday().number_from_monday()).collect::<Vec<_>>(),
+        "month" => rows.iter().map(|row| row.date.format("%Y-%m").to_string()).collect::<Vec<_>>(),
+        "month_number" => rows.iter().map(|row| row.date.month()).collect::<Vec<_>>(),
+        "year" => rows.iter().map(|row| row.date.year()).collect::<Vec<_>>(),
+        "eid" => rows.iter().map(|row| row.eid).collect::<Vec<_>>(),
+        "name" => rows.iter().map(|row| row.name.clone()).collect::<Vec<_>>(),
+        "role" => rows.iter().map(|row| row.role.clone()).collect::<Vec<_>>(),
+        "duration" => rows.iter().map(|row| row.duration as f64).collect::<Vec<_>>(),
+        "net_tips" => rows.iter().map(|row| row.net_tips as f64).collect::<Vec<_>>(),
+    )?;
+
+    Ok(df.lazy())
+}
+
+async fn load_nights(
+    repo: &dyn TipsRepo,
+    range: &AnalyticsRange,
+) -> Result<LazyFrame, Box<dyn Error>> {
+    let rows = repo.night_inputs(range.clone()).await?;
+
+    let df = df!(
+        "date" => rows.iter().map(|row| row.date.to_string()).collect::<Vec<_>>(),
+        "total_sales" => rows.iter().map(|row| row.total_sales as f64).collect::<Vec<_>>(),
+        "total_tips" => rows.iter().map(|row| row.total_tips as f64).collect::<Vec<_>>(),
+    )?;
+
+    Ok(df.lazy())
+}
+
+fn weekday(date: NaiveDate) -> String {
+    WEEKDAYS[date.weekday().num_days_from_monday() as usize].to_string()
+}
+
+fn strings(df: &DataFrame, name: &str) -> Result<Vec<String>, PolarsError> {
+    Ok(df
+        .column(name)?
+        .cast(&DataType::String)?
+        .str()?
+        .into_iter()
+        .map(|value| value.unwrap_or_default().to_string())
+        .collect())
+}
+
+fn floats(df: &DataFrame, name: &str) -> Result<Vec<Option<f64>>, PolarsError> {
+    Ok(df
+        .column(name)?
+        .cast(&DataType::Float64)?
+        .f64()?
+        .into_iter()
+        .map(|value| value.filter(|value| value.is_finite()))
+        .collect())
+}
+
+fn counts(df: &DataFrame, name: &str) -> Result<Vec<u32>, PolarsError> {
+    Ok(df
+        .column(name)?
+        .cast(&DataType::UInt32)?
+        .u32()?
+        .into_iter()
+        .map(|value| value.unwrap_or_default())
+        .collect())
+}
+
+fn integers(df: &DataFrame, name: &str) -> Result<Vec<i32>, PolarsError> {
+    Ok(df
+        .column(name)?
+        .cast(&DataType::Int32)?
+        .i32()?
+        .into_iter()
+        .map(|value| value.unwrap_or_default())
+        .collect())
+}
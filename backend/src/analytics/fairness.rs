@@ -0,0 +1,243 @@
+use chrono::NaiveDate;
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use super::{floats, in_pool, integers, load_tips, ratio, strings, AnalyticsRange};
+use crate::repo::TipsRepo;
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct FairnessParams {
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+    /// Fraction an employee's tipped hourly may differ from their role's
+    /// median before they are listed as an outlier.
+    pub threshold: f64,
+}
+
+impl Default for FairnessParams {
+    fn default() -> Self {
+        Self {
+            start: None,
+            end: None,
+            threshold: 0.25,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FairnessReport {
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+    /// Everyone in the range, highest tipped hourly first.
+    pub employees: Vec<EmployeeShare>,
+    pub roles: Vec<RoleDistribution>,
+    pub outliers: Vec<Outlier>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EmployeeShare {
+    pub rank: u32,
+    pub eid: i32,
+    pub name: String,
+    pub nights: u32,
+    pub hours: f64,
+    pub tips: f64,
+    /// Fraction of the pool's hours in the range worked by this employee.
+    /// Steward hours are not in the pool.
+    pub share_of_hours: f64,
+    /// Fraction of all tips in the range paid to this employee.
+    pub share_of_tips: f64,
+    pub tipped_hourly: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoleDistribution {
+    pub role: String,
+    pub employees: u32,
+    pub median_tipped_hourly: Option<f64>,
+    /// Gini coefficient of the employees' tipped hourly rates in the role:
+    /// 0 when everyone earns the same rate, approaching 1 as it
+    /// concentrates on a few people.
+    pub gini: Option<f64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Outlier {
+    pub eid: i32,
+    pub name: String,
+    pub role: String,
+    pub tipped_hourly: f64,
+    pub role_median: f64,
+    /// Signed fraction the employee's rate differs from the role median.
+    pub deviation: f64,
+}
+
+pub async fn fairness(
+    repo: &dyn TipsRepo,
+    params: &FairnessParams,
+) -> Result<FairnessReport, Box<dyn Error>> {
+    let range = AnalyticsRange {
+        start: params.start,
+        end: params.end,
+    };
+    let tips = load_tips(repo, &range).await?;
+
+    let employees = tips
+        .clone()
+        .group_by([col("eid")])
+        .agg([
+            col("name").first(),
+            col("date").n_unique().alias("nights"),
+            col("duration").sum().alias("hours"),
+            col("duration").filter(in_pool()).sum().alias("pool_hours"),
+            col("net_tips").sum().alias("tips"),
+        ])
+        .with_columns([
+            ratio(col("pool_hours"), col("pool_hours").sum())
+                .fill_null(lit(0.0))
+                .alias("share_of_hours"),
+            ratio(col("tips"), col("tips").sum())
+                .fill_null(lit(0.0))
+                .alias("share_of_tips"),
+            ratio(col("tips"), col("hours")).alias("tipped_hourly"),
+        ])
+        .sort(
+            ["tipped_hourly", "name"],
+            SortMultipleOptions::default()
+                .with_order_descendings([true, false])
+                .with_nulls_last(true),
+        )
+        .collect()?;
+
+    let by_role = tips
+        .group_by([col("eid"), col("role")])
+        .agg([
+            col("name").first(),
+            col("duration").sum().alias("hours"),
+            col("net_tips").sum().alias("tips"),
+        ])
+        .with_column(ratio(col("tips"), col("hours")).alias("tipped_hourly"))
+        .filter(col("tipped_hourly").is_not_null())
+        .with_column(
+            col("tipped_hourly")
+                .median()
+                .over([col("role")])
+                .alias("role_median"),
+        )
+        .with_column(
+            ratio(
+                col("tipped_hourly") - col("role_median"),
+                col("role_median"),
+            )
+            .alias("deviation"),
+        )
+        .sort(["role", "name"], Default::default())
+        .collect()?;
+
+    Ok(FairnessReport {
+        start: params.start,
+        end: params.end,
+        employees: employee_shares(&employees)?,
+        roles: role_distributions(&by_role)?,
+        outliers: outliers(&by_role, params.threshold)?,
+    })
+}
+
+fn employee_shares(df: &DataFrame) -> Result<Vec<EmployeeShare>, PolarsError> {
+    let eids = integers(df, "eid")?;
+    let names = strings(df, "name")?;
+    let nights = floats(df, "nights")?;
+    let hours = floats(df, "hours")?;
+    let tips = floats(df, "tips")?;
+    let share_of_hours = floats(df, "share_of_hours")?;
+    let share_of_tips = floats(df, "share_of_tips")?;
+    let tipped_hourly = floats(df, "tipped_hourly")?;
+
+    Ok((0..df.height())
+        .map(|i| EmployeeShare {
+            rank: i as u32 + 1,
+            eid: eids[i],
+            name: names[i].clone(),
+            nights: nights[i].unwrap_or_default() as u32,
+            hours: hours[i].unwrap_or_default(),
+            tips: tips[i].unwrap_or_default(),
+            share_of_hours: share_of_hours[i].unwrap_or_default(),
+            share_of_tips: share_of_tips[i].unwrap_or_default(),
+            tipped_hourly: tipped_hourly[i],
+        })
+        .collect())
+}
+
+fn role_distributions(df: &DataFrame) -> Result<Vec<RoleDistribution>, PolarsError> {
+    let roles = strings(df, "role")?;
+    let tipped_hourly = floats(df, "tipped_hourly")?;
+    let role_median = floats(df, "role_median")?;
+
+    let mut rates: BTreeMap<String, (Vec<f64>, Option<f64>)> = BTreeMap::new();
+    for i in 0..df.height() {
+        let entry = rates.entry(roles[i].clone()).or_default();
+        entry.0.extend(tipped_hourly[i]);
+        entry.1 = role_median[i];
+    }
+
+    Ok(rates
+        .into_iter()
+        .map(|(role, (rates, median))| RoleDistribution {
+            role,
+            employees: rates.len() as u32,
+            median_tipped_hourly: median,
+            gini: gini(rates),
+        })
+        .collect())
+}
+
+fn outliers(df: &DataFrame, threshold: f64) -> Result<Vec<Outlier>, PolarsError> {
+    let eids = integers(df, "eid")?;
+    let names = strings(df, "name")?;
+    let roles = strings(df, "role")?;
+    let tipped_hourly = floats(df, "tipped_hourly")?;
+    let role_median = floats(df, "role_median")?;
+    let deviation = floats(df, "deviation")?;
+
+    Ok((0..df.height())
+        .filter_map(|i| {
+            let deviation = deviation[i]?;
+            if deviation.abs() <= threshold {
+                return None;
+            }
+            Some(Outlier {
+                eid: eids[i],
+                name: names[i].clone(),
+                role: roles[i].clone(),
+                tipped_hourly: tipped_hourly[i]?,
+                role_median: role_median[i]?,
+                deviation,
+            })
+        })
+        .collect())
+}
+
+/// Gini coefficient of `values`, or `None` when there is nothing to
+/// compare.
+fn gini(mut values: Vec<f64>) -> Option<f64> {
+    let total: f64 = values.iter().sum();
+    if values.is_empty() || total <= 0.0 {
+        return None;
+    }
+
+    values.sort_by(f64::total_cmp);
+    let n = values.len() as f64;
+    let weighted: f64 = values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| (2.0 * (i as f64 + 1.0) - n - 1.0) * value)
+        .sum();
+
+    Some(weighted / (n * total))
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,76 @@
+use super::*;
+use crate::repo::AppState;
+use crate::testing::{block_on, database};
+
+#[test]
+fn gini_of_equal_rates_is_zero() {
+    assert_eq!(gini(vec![22.5, 22.5, 22.5, 22.5]), Some(0.0));
+}
+
+#[test]
+fn gini_of_a_single_earner_is_n_minus_one_over_n() {
+    for n in 2..=5 {
+        let mut rates = vec![0.0; n - 1];
+        rates.push(30.0);
+        let expected = (n as f64 - 1.0) / n as f64;
+        let gini = gini(rates).expect("No coefficient");
+        assert!(
+            (gini - expected).abs() < 1e-12,
+            "{} for {} earners",
+            gini,
+            n
+        );
+    }
+}
+
+#[test]
+fn gini_needs_something_to_compare() {
+    assert_eq!(gini(Vec::new()), None);
+    assert_eq!(gini(vec![0.0, 0.0]), None);
+}
+
+#[test]
+fn steward_hours_are_left_out_of_the_pool_share() {
+    block_on(async {
+        let db = database().await;
+        db.query(
+            "
+            LET $now = '2024-05-02T09:00:00Z';
+            FOR $tip IN [
+                { eid: 101, name: 'Ana Rivera', role: 'Server', duration: 6, net_tips: 60 },
+                { eid: 102, name: 'Ben Chen', role: 'Server', duration: 2, net_tips: 20 },
+                { eid: 103, name: 'Cam Okafor', role: 'Steward', duration: 4, net_tips: 10 }
+            ] {
+                CREATE tips CONTENT {
+                    employee: type::thing('staff', $tip.eid), eid: $tip.eid, name: $tip.name,
+                    role: $tip.role, date: '2024-05-01', duration: $tip.duration,
+                    net_tips: $tip.net_tips, total_pay_for_night: $tip.net_tips,
+                    hourly_pay_for_night: 0, tipped_hour_for_night: 0,
+                    created: $now, modified: $now
+                };
+            };
+            ",
+        )
+        .await
+        .unwrap()
+        .check()
+        .unwrap();
+        let state = AppState::surreal(db);
+
+        let report = fairness(&*state.tips, &FairnessParams::default())
+            .await
+            .map_err(|err| err.to_string())
+            .unwrap();
+
+        let share = |eid| {
+            report
+                .employees
+                .iter()
+                .find(|employee| employee.eid == eid)
+                .map(|employee| employee.share_of_hours)
+        };
+        assert_eq!(share(101), Some(0.75));
+        assert_eq!(share(102), Some(0.25));
+        assert_eq!(share(103), Some(0.0));
+    });
+}
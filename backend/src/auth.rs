@@ -0,0 +1,401 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, rand_core::RngCore, SaltString},
+    Argon2, PasswordHash, PasswordHasher, PasswordVerifier,
+};
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query, Request},
+    http::{header, request::Parts, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use surrealdb::sql::Thing;
+
+use crate::repo;
+use crate::DB;
+
+#[cfg(test)]
+mod tests;
+
+const SESSION_LENGTH_HOURS: i64 = 12;
+/// How long a stream token can be used to open an event stream.
+const STREAM_TOKEN_SECONDS: i64 = 60;
+
+/// Checked against when the username is unknown, so a failed login takes
+/// as long whether or not the account exists.
+static DUMMY_PASSWORD_HASH: Lazy<String> =
+    Lazy::new(|| hash_password("not a real password").expect("Could not hash password"));
+
+/// Key stream tokens are signed with. They only last a minute, so a new
+/// key on every start is enough.
+static STREAM_TOKEN_KEY: Lazy<[u8; 32]> = Lazy::new(|| {
+    let mut key = [0u8; 32];
+    OsRng.fill_bytes(&mut key);
+    key
+});
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum UserRole {
+    Staff,
+    Manager,
+    Owner,
+}
+
+/// The signed-in user, attached to the request by [`authenticate`].
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CurrentUser {
+    pub username: String,
+    pub role: UserRole,
+    /// Staff record the account belongs to, required for staff accounts.
+    pub eid: Option<i32>,
+}
+
+impl CurrentUser {
+    /// Owners and managers see everyone; staff only see themselves.
+    pub fn can_view(&self, eid: i32) -> bool {
+        self.role >= UserRole::Manager || self.eid == Some(eid)
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for CurrentUser {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<CurrentUser>()
+            .cloned()
+            .ok_or_else(unauthorized)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct User {
+    pub username: String,
+    pub password_hash: String,
+    pub role: UserRole,
+    pub eid: Option<i32>,
+    pub created: DateTime<Utc>,
+    pub modified: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Session {
+    user: Thing,
+    expires: DateTime<Utc>,
+    created: DateTime<Utc>,
+}
+
+pub fn hash_password(password: &str) -> Result<String, Box<dyn Error>> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|err| err.to_string())?
+        .to_string())
+}
+
+fn verify_password(password: &str, password_hash: &str) -> bool {
+    PasswordHash::new(password_hash)
+        .map(|hash| {
+            Argon2::default()
+                .verify_password(password.as_bytes(), &hash)
+                .is_ok()
+        })
+        .unwrap_or(false)
+}
+
+/// Creates the first owner account, returning `None` once there is any
+/// user. The `setup:owner` record is created in the same transaction, so of
+/// two setups at once only one can commit.
+pub async fn set_up_owner(
+    username: &str,
+    password: &str,
+) -> Result<Option<CurrentUser>, Box<dyn Error>> {
+    let password_hash = hash_password(password)?;
+
+    let mut response = DB
+        .query(
+            "
+            BEGIN TRANSACTION;
+            IF count(SELECT id FROM users) > 0 {
+                THROW 'Setup has already been completed';
+            };
+            CREATE setup:owner CONTENT { username: $username, created: $now };
+            CREATE type::thing('users', $username) CONTENT $user RETURN username, role, eid;
+            COMMIT TRANSACTION;
+            ",
+        )
+        .bind(("username", username))
+        .bind(("now", Utc::now()))
+        .bind((
+            "user",
+            User {
+                username: username.to_string(),
+                password_hash,
+                role: UserRole::Owner,
+                eid: None,
+                created: Utc::now(),
+                modified: Utc::now(),
+            },
+        ))
+        .await?;
+
+    let errors = response.take_errors();
+    if errors.values().any(|err| {
+        repo::is_duplicate(err)
+            || matches!(err, surrealdb::Error::Db(surrealdb::error::Db::Thrown(_)))
+    }) {
+        return Ok(None);
+    }
+    if let Some(err) = errors.into_values().next() {
+        return Err(err.into());
+    }
+
+    let user: Option<CurrentUser> = response.take(2)?;
+    Ok(user)
+}
+
+/// Sessions are stored under a hash of their token so a leaked database
+/// does not hand out working tokens.
+fn session_id(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+/// Checks the credentials and opens a session, returning its bearer token.
+pub async fn login(
+    username: &str,
+    password: &str,
+) -> Result<Option<(String, DateTime<Utc>, CurrentUser)>, Box<dyn Error>> {
+    let user: Option<User> = DB.select(("users", username)).await?;
+
+    let password_hash = user
+        .as_ref()
+        .map_or(DUMMY_PASSWORD_HASH.as_str(), |user| &user.password_hash);
+    let verified = verify_password(password, password_hash);
+    let Some(user) = user.filter(|_| verified) else {
+        return Ok(None);
+    };
+
+    let mut token = [0u8; 32];
+    OsRng.fill_bytes(&mut token);
+    let token = hex::encode(token);
+    let expires = Utc::now() + Duration::hours(SESSION_LENGTH_HOURS);
+
+    let _: Option<Session> = DB
+        .create(("sessions", session_id(&token)))
+        .content(Session {
+            user: Thing {
+                tb: "users".to_string(),
+                id: user.username.clone().into(),
+            },
+            expires,
+            created: Utc::now(),
+        })
+        .await?;
+
+    Ok(Some((
+        token,
+        expires,
+        CurrentUser {
+            username: user.username,
+            role: user.role,
+            eid: user.eid,
+        },
+    )))
+}
+
+pub async fn logout(token: &str) -> Result<(), Box<dyn Error>> {
+    let _: Option<Session> = DB.delete(("sessions", session_id(token))).await?;
+    Ok(())
+}
+
+async fn session_user(token: &str) -> Result<Option<CurrentUser>, Box<dyn Error>> {
+    let user: Option<CurrentUser> = DB
+        .query(
+            "
+            SELECT user.username AS username, user.role AS role, user.eid AS eid
+            FROM type::thing('sessions', $session_id) WHERE expires > $now AND user.username != NONE;
+            ",
+        )
+        .bind(("session_id", session_id(token)))
+        .bind(("now", Utc::now()))
+        .await?
+        .take(0)?;
+
+    Ok(user)
+}
+
+/// A token that opens an event stream as `user` for the next minute.
+/// Browsers' `EventSource` cannot send an `Authorization` header, so the
+/// event streams accept this in the query string instead.
+pub fn stream_token(user: &CurrentUser) -> (String, DateTime<Utc>) {
+    let username = hex::encode(&user.username);
+    let expires = Utc::now() + Duration::seconds(STREAM_TOKEN_SECONDS);
+    let signature = stream_token_mac(&username, expires.timestamp())
+        .finalize()
+        .into_bytes();
+    (
+        format!(
+            "{}.{}.{}",
+            username,
+            expires.timestamp(),
+            hex::encode(signature)
+        ),
+        expires,
+    )
+}
+
+fn stream_token_mac(username: &str, expires: i64) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(STREAM_TOKEN_KEY.as_slice())
+        .expect("HMAC takes any key size");
+    mac.update(format!("{}:{}", username, expires).as_bytes());
+    mac
+}
+
+/// The user a genuine, unexpired stream token was made for, as they are
+/// now, so a removed or demoted account loses access.
+async fn stream_token_user(token: &str) -> Result<Option<CurrentUser>, Box<dyn Error>> {
+    let mut parts = token.split('.');
+    let (Some(username), Some(expires), Some(signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Ok(None);
+    };
+    let (Ok(expires), Ok(signature)) = (expires.parse::<i64>(), hex::decode(signature)) else {
+        return Ok(None);
+    };
+    if expires < Utc::now().timestamp()
+        || stream_token_mac(username, expires)
+            .verify_slice(&signature)
+            .is_err()
+    {
+        return Ok(None);
+    }
+    let Some(username) = hex::decode(username)
+        .ok()
+        .and_then(|username| String::from_utf8(username).ok())
+    else {
+        return Ok(None);
+    };
+
+    let user: Option<CurrentUser> = DB
+        .query(
+            "
+            SELECT username, role, eid FROM type::thing('users', $username);
+            ",
+        )
+        .bind(("username", username))
+        .await?
+        .take(0)?;
+    Ok(user)
+}
+
+pub fn bearer_token(parts: &axum::http::HeaderMap) -> Option<&str> {
+    parts
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")
+        .map(str::trim)
+}
+
+/// Resolves the bearer token to a [`CurrentUser`] and rejects the request
+/// when there is no valid session.
+pub async fn authenticate(request: Request, next: Next) -> Response {
+    let Some(token) = bearer_token(request.headers()).map(str::to_string) else {
+        return unauthorized();
+    };
+
+    let user = session_user(&token).await.map_err(|err| err.to_string());
+    with_user(request, next, user).await
+}
+
+#[derive(Deserialize)]
+struct StreamParams {
+    token: Option<String>,
+}
+
+/// Like [`authenticate`], but without an `Authorization` header it accepts
+/// a [`stream_token`] as `?token=`. Only for event streams, so the token
+/// opens nothing else.
+pub async fn authenticate_stream(request: Request, next: Next) -> Response {
+    let user = match bearer_token(request.headers()).map(str::to_string) {
+        Some(token) => session_user(&token).await.map_err(|err| err.to_string()),
+        None => {
+            let Ok(Query(StreamParams { token: Some(token) })) =
+                Query::<StreamParams>::try_from_uri(request.uri())
+            else {
+                return unauthorized();
+            };
+            stream_token_user(&token)
+                .await
+                .map_err(|err| err.to_string())
+        }
+    };
+    with_user(request, next, user).await
+}
+
+async fn with_user(
+    mut request: Request,
+    next: Next,
+    user: Result<Option<CurrentUser>, String>,
+) -> Response {
+    match user {
+        Ok(Some(user)) => {
+            request.extensions_mut().insert(user);
+            next.run(request).await
+        }
+        Ok(None) => unauthorized(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err
+            })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn require_manager(user: CurrentUser, request: Request, next: Next) -> Response {
+    match user.role >= UserRole::Manager {
+        true => next.run(request).await,
+        false => forbidden(),
+    }
+}
+
+pub async fn require_owner(user: CurrentUser, request: Request, next: Next) -> Response {
+    match user.role == UserRole::Owner {
+        true => next.run(request).await,
+        false => forbidden(),
+    }
+}
+
+pub fn unauthorized() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        Json(json!({
+        "error": "Not signed in"
+        })),
+    )
+        .into_response()
+}
+
+pub fn forbidden() -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        Json(json!({
+        "error": "Not allowed"
+        })),
+    )
+        .into_response()
+}
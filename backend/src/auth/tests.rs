@@ -0,0 +1,125 @@
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+use crate::repo::AppState;
+use crate::testing::block_on;
+use crate::{routes, DB};
+
+async fn send(method: Method, uri: &str, token: Option<&str>, body: Value) -> (StatusCode, Value) {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json");
+    if let Some(token) = token {
+        request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+    }
+    let request = request
+        .body(Body::from(body.to_string()))
+        .expect("Could not build request");
+
+    let response = routes::routes(AppState::surreal(DB.clone()))
+        .oneshot(request)
+        .await
+        .expect("Could not send request");
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("Could not read response");
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+/// Opens `uri` without reading the body, which an event stream never
+/// finishes.
+async fn open(uri: &str) -> (StatusCode, Option<String>) {
+    let request = Request::builder()
+        .uri(uri)
+        .body(Body::empty())
+        .expect("Could not build request");
+    let response = routes::routes(AppState::surreal(DB.clone()))
+        .oneshot(request)
+        .await
+        .expect("Could not send request");
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    (response.status(), content_type)
+}
+
+/// Setup only succeeds once, so this runs inside the setup test with its
+/// owner's session.
+async fn stream_token_opens_only_event_streams(session: &str) {
+    let (status, _) = send(Method::POST, "/auth/stream-token", None, Value::Null).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, issued) = send(
+        Method::POST,
+        "/auth/stream-token",
+        Some(session),
+        Value::Null,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let token = issued["token"].as_str().expect("No stream token");
+
+    let (status, content_type) = open(&format!("/events?token={}", token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(content_type.as_deref(), Some("text/event-stream"));
+
+    let (status, _) = open("/events").await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let tampered = format!("{}0", token);
+    let (status, _) = open(&format!("/events?token={}", tampered)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = open(&format!("/auth/me?token={}", token)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = send(Method::GET, "/auth/me", Some(token), Value::Null).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn setup_login_and_authenticated_request() {
+    block_on(async {
+        let owner = json!({ "username": "owner", "password": "correct horse" });
+
+        let (status, _) = send(Method::POST, "/auth/setup", None, owner.clone()).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let second = json!({ "username": "other", "password": "correct horse" });
+        let (status, _) = send(Method::POST, "/auth/setup", None, second).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let wrong = json!({ "username": "owner", "password": "wrong password" });
+        let (status, _) = send(Method::POST, "/auth/login", None, wrong).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let unknown = json!({ "username": "nobody", "password": "correct horse" });
+        let (status, _) = send(Method::POST, "/auth/login", None, unknown).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, login) = send(Method::POST, "/auth/login", None, owner).await;
+        assert_eq!(status, StatusCode::OK);
+        let token = login["token"].as_str().expect("No token");
+
+        let (status, me) = send(Method::GET, "/auth/me", Some(token), Value::Null).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(me["username"], "owner");
+        assert_eq!(me["role"], "owner");
+
+        let (status, _) = send(Method::GET, "/auth/me", None, Value::Null).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        stream_token_opens_only_event_streams(token).await;
+
+        let (status, _) = send(Method::POST, "/auth/logout", Some(token), Value::Null).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = send(Method::GET, "/auth/me", Some(token), Value::Null).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    });
+}
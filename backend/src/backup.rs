@@ -0,0 +1,413 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use surrealdb::{engine::any::Any, sql, Surreal};
+
+use crate::downloads::Export;
+use crate::migrations;
+use crate::DB;
+
+const ARCHIVE_FORMAT: &str = "wine-list-backup";
+const ARCHIVE_VERSION: u32 = 1;
+const BACKUP_FILE_PREFIX: &str = "wine-list-backup_";
+const BACKUP_FILE_SUFFIX: &str = ".ndjson";
+
+/// Business data tables an archive holds. Logins, sessions and pending
+/// downloads are left out so a backup never carries password hashes.
+const TABLES: &[Table] = &[
+    Table {
+        name: "staff",
+        links: &[],
+    },
+    Table {
+        name: "tips",
+        links: &["employee"],
+    },
+    Table {
+        name: "nights",
+        links: &[],
+    },
+    Table {
+        name: "wines",
+        links: &[],
+    },
+    Table {
+        name: "commissions",
+        links: &["name", "wine"],
+    },
+    Table {
+        name: "pricing_policy",
+        links: &[],
+    },
+    Table {
+        name: "recalculations",
+        links: &[],
+    },
+    Table {
+        name: "tips_archive",
+        links: &["recalculation"],
+    },
+];
+
+struct Table {
+    name: &'static str,
+    /// Fields holding record links, written to the archive as `table:id`
+    /// strings and turned back into links on restore.
+    links: &'static [&'static str],
+}
+
+/// First line of an archive. Every line after it is one [`ArchiveRecord`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveHeader {
+    pub format: String,
+    pub version: u32,
+    /// Newest migration applied to the database the archive was taken from.
+    pub schema_version: u32,
+    pub created: DateTime<Utc>,
+    /// Number of records per table.
+    pub tables: BTreeMap<String, usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ArchiveRecord {
+    table: String,
+    /// Record ID as `table:id`.
+    id: String,
+    record: Map<String, Value>,
+}
+
+/// A parsed archive, ready to be compared with the database or restored.
+pub struct Archive {
+    pub header: ArchiveHeader,
+    records: Vec<ArchiveRecord>,
+}
+
+/// What restoring an archive changes in one table.
+#[derive(Debug, Serialize, Default)]
+pub struct TableDiff {
+    pub table: String,
+    /// Records only in the archive.
+    pub added: Vec<String>,
+    /// Records in both whose contents differ.
+    pub changed: Vec<String>,
+    /// Records only in the database, which a restore deletes.
+    pub removed: Vec<String>,
+    pub unchanged: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RestoreDiff {
+    pub dry_run: bool,
+    pub archive: ArchiveHeader,
+    pub tables: Vec<TableDiff>,
+}
+
+/// Writes every business data table in `db` to an NDJSON archive.
+pub async fn export(db: &Surreal<Any>) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut tables = BTreeMap::new();
+    let mut records = Vec::new();
+    for table in TABLES {
+        let rows = current_records(db, table.name).await?;
+        tables.insert(table.name.to_string(), rows.len());
+        records.extend(rows.into_iter().map(|(id, record)| ArchiveRecord {
+            table: table.name.to_string(),
+            id,
+            record,
+        }));
+    }
+
+    let header = ArchiveHeader {
+        format: ARCHIVE_FORMAT.to_string(),
+        version: ARCHIVE_VERSION,
+        schema_version: migrations::latest_version(),
+        created: Utc::now(),
+        tables,
+    };
+
+    let mut bytes = serde_json::to_vec(&header)?;
+    bytes.push(b'\n');
+    for record in records {
+        serde_json::to_writer(&mut bytes, &record)?;
+        bytes.push(b'\n');
+    }
+    Ok(bytes)
+}
+
+/// An archive as a file to download.
+pub async fn export_file() -> Result<Export, Box<dyn Error>> {
+    Ok(Export {
+        filename: backup_filename(Utc::now()),
+        content_type: "application/x-ndjson",
+        bytes: export(&DB).await?,
+    })
+}
+
+/// Reads an archive, rejecting one from another program or from a newer
+/// schema than this build knows about.
+pub fn parse(bytes: &[u8]) -> Result<Archive, Box<dyn Error>> {
+    let text = std::str::from_utf8(bytes)?;
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+
+    let header: ArchiveHeader = serde_json::from_str(lines.next().ok_or("The archive is empty")?)
+        .map_err(|err| format!("Invalid archive header: {}", err))?;
+    if header.format != ARCHIVE_FORMAT {
+        return Err(format!("Not a {} archive", ARCHIVE_FORMAT).into());
+    }
+    if header.version > ARCHIVE_VERSION {
+        return Err(format!(
+            "Archive format version {} is newer than this build supports ({})",
+            header.version, ARCHIVE_VERSION
+        )
+        .into());
+    }
+    if header.schema_version > migrations::latest_version() {
+        return Err(format!(
+            "The archive is from schema version {}, newer than this build's {}",
+            header.schema_version,
+            migrations::latest_version()
+        )
+        .into());
+    }
+
+    let mut records = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let record: ArchiveRecord = serde_json::from_str(line)
+            .map_err(|err| format!("Invalid archive record on line {}: {}", i + 2, err))?;
+        if table(&record.table).is_none() {
+            return Err(format!("Unknown table {} on line {}", record.table, i + 2).into());
+        }
+        records.push(record);
+    }
+
+    for (name, count) in &header.tables {
+        let found = records
+            .iter()
+            .filter(|record| &record.table == name)
+            .count();
+        if found != *count {
+            return Err(format!(
+                "The archive should have {} {} records but has {}",
+                count, name, found
+            )
+            .into());
+        }
+    }
+
+    Ok(Archive { header, records })
+}
+
+/// Compares the archive with `db` and, unless `dry_run`, replaces the
+/// contents of every table in the archive with its records in a single
+/// transaction.
+pub async fn restore(
+    db: &Surreal<Any>,
+    archive: Archive,
+    dry_run: bool,
+) -> Result<RestoreDiff, Box<dyn Error>> {
+    let mut tables = Vec::new();
+    for name in archive.header.tables.keys() {
+        tables.push(diff(db, name, &archive).await?);
+    }
+
+    if !dry_run {
+        let mut records = Vec::with_capacity(archive.records.len());
+        for record in &archive.records {
+            records.push(restorable(record)?);
+        }
+
+        let deletes: String = archive
+            .header
+            .tables
+            .keys()
+            .filter_map(|name| table(name))
+            .map(|table| format!("DELETE {};\n", table.name))
+            .collect();
+
+        db.query(format!(
+            "
+            BEGIN TRANSACTION;
+            {}
+            FOR $record IN $records {{
+                UPDATE $record.id CONTENT $record.content;
+            }};
+            COMMIT TRANSACTION;
+            ",
+            deletes
+        ))
+        .bind(("records", sql::Value::from(records)))
+        .await?
+        .check()?;
+    }
+
+    Ok(RestoreDiff {
+        dry_run,
+        archive: archive.header,
+        tables,
+    })
+}
+
+/// Saves an archive to `BACKUP_DIR` every `BACKUP_INTERVAL_HOURS`, keeping
+/// the newest `BACKUP_KEEP`, for the life of the server.
+pub async fn back_up_periodically() {
+    let dir: PathBuf = std::env::var("BACKUP_DIR")
+        .unwrap_or_else(|_| "backups".to_string())
+        .into();
+    let hours = env_number("BACKUP_INTERVAL_HOURS", 24);
+    let keep = env_number("BACKUP_KEEP", 14) as usize;
+
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(hours * 60 * 60));
+    // The first tick is immediate; skip it so restarts do not each add a
+    // backup and prune the older ones out of `BACKUP_KEEP`.
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        match write_backup(&DB, &dir).await {
+            Ok(path) => tracing::info!("Backed up to {}", path.display()),
+            Err(err) => {
+                tracing::warn!("Could not back up: {}", err);
+                continue;
+            }
+        }
+        if let Err(err) = prune_backups(&dir, keep).await {
+            tracing::warn!("Could not remove old backups: {}", err);
+        }
+    }
+}
+
+/// Writes an archive of `db` into `dir` and returns its path.
+pub async fn write_backup(db: &Surreal<Any>, dir: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    let bytes = export(db).await?;
+    tokio::fs::create_dir_all(dir).await?;
+    let path = dir.join(backup_filename(Utc::now()));
+    tokio::fs::write(&path, bytes).await?;
+    Ok(path)
+}
+
+/// Deletes all but the newest `keep` backups in `dir`.
+async fn prune_backups(dir: &Path, keep: usize) -> Result<(), Box<dyn Error>> {
+    let mut backups = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with(BACKUP_FILE_PREFIX) && name.ends_with(BACKUP_FILE_SUFFIX) {
+            backups.push(entry.path());
+        }
+    }
+
+    // The timestamp in the name sorts oldest first
+    backups.sort();
+    let excess = backups.len().saturating_sub(keep);
+    for path in &backups[..excess] {
+        tokio::fs::remove_file(path).await?;
+    }
+    Ok(())
+}
+
+fn backup_filename(created: DateTime<Utc>) -> String {
+    format!(
+        "{}{}{}",
+        BACKUP_FILE_PREFIX,
+        created.format("%Y-%m-%dT%H-%M-%SZ"),
+        BACKUP_FILE_SUFFIX
+    )
+}
+
+fn env_number(name: &str, default: u64) -> u64 {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|value| *value > 0)
+        .unwrap_or(default)
+}
+
+fn table(name: &str) -> Option<&'static Table> {
+    TABLES.iter().find(|table| table.name == name)
+}
+
+/// Every record in `table` as JSON, keyed by `table:id`, without its `id`.
+async fn current_records(
+    db: &Surreal<Any>,
+    table: &str,
+) -> Result<BTreeMap<String, Map<String, Value>>, Box<dyn Error>> {
+    let rows: sql::Value = db
+        .query("SELECT * FROM type::table($table);")
+        .bind(("table", table))
+        .await?
+        .take(0)?;
+
+    let mut records = BTreeMap::new();
+    if let Value::Array(rows) = rows.into_json() {
+        for row in rows {
+            let Value::Object(mut record) = row else {
+                continue;
+            };
+            let Some(Value::String(id)) = record.remove("id") else {
+                continue;
+            };
+            records.insert(id, record);
+        }
+    }
+    Ok(records)
+}
+
+async fn diff(
+    db: &Surreal<Any>,
+    name: &str,
+    archive: &Archive,
+) -> Result<TableDiff, Box<dyn Error>> {
+    let mut current = current_records(db, name).await?;
+    let mut diff = TableDiff {
+        table: name.to_string(),
+        ..Default::default()
+    };
+
+    for record in archive.records.iter().filter(|record| record.table == name) {
+        match current.remove(&record.id) {
+            None => diff.added.push(record.id.clone()),
+            Some(existing) if existing != record.record => diff.changed.push(record.id.clone()),
+            Some(_) => diff.unchanged += 1,
+        }
+    }
+    diff.removed = current.into_keys().collect();
+
+    Ok(diff)
+}
+
+/// `{ id, content }` for the restore query, with the record's links
+/// turned back from strings into records.
+fn restorable(record: &ArchiveRecord) -> Result<sql::Value, Box<dyn Error>> {
+    let invalid = |what: &str| format!("Invalid {} in archive record {}", what, record.id);
+
+    let id = sql::thing(&record.id).map_err(|_| invalid("id"))?;
+    if id.tb != record.table {
+        return Err(invalid("id").into());
+    }
+
+    let sql::Value::Object(mut content) =
+        sql::json(&Value::Object(record.record.clone()).to_string())
+            .map_err(|_| invalid("record"))?
+    else {
+        return Err(invalid("record").into());
+    };
+
+    for field in table(&record.table)
+        .map(|table| table.links)
+        .unwrap_or_default()
+    {
+        if let Some(sql::Value::Strand(link)) = content.get(*field) {
+            let link = sql::thing(link.as_str()).map_err(|_| invalid(field))?;
+            content.insert(field.to_string(), sql::Value::Thing(link));
+        }
+    }
+
+    let mut restorable = sql::Object::default();
+    restorable.insert("id".to_string(), sql::Value::Thing(id));
+    restorable.insert("content".to_string(), sql::Value::Object(content));
+    Ok(sql::Value::Object(restorable))
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,119 @@
+use super::*;
+// Each test restores into a database of its own, leaving the other tests'
+// data alone
+use crate::testing::{block_on, database, STAFF};
+
+async fn seed(db: &Surreal<Any>) {
+    db.query(
+        "
+        LET $now = '2024-05-02T09:00:00Z';
+        CREATE staff:401 CONTENT { name: 'Fay Ito', card_id: '9000401', eid: 401, created: $now, modified: $now };
+        CREATE staff:402 CONTENT { name: 'Gus Park', card_id: '9000402', eid: 402, created: $now, modified: $now };
+        CREATE tips:401_2024_05_01 CONTENT {
+            employee: staff:401, eid: 401, name: 'Fay Ito', role: 'Server', date: '2024-05-01',
+            net_tips: 80.5, total_pay_for_night: 113.5, hourly_pay_for_night: 18.9,
+            tipped_hour_for_night: 13.4, duration: 6, created: $now, modified: $now
+        };
+        ",
+    )
+    .await
+    .expect("Could not seed")
+    .check()
+    .expect("Could not seed");
+}
+
+fn table<'a>(diff: &'a RestoreDiff, name: &str) -> &'a TableDiff {
+    diff.tables
+        .iter()
+        .find(|table| table.table == name)
+        .expect("Table missing from the diff")
+}
+
+#[test]
+fn export_restores_into_another_database() {
+    block_on(async {
+        let source = database().await;
+        seed(&source).await;
+        let bytes = export(&source)
+            .await
+            .map_err(|err| err.to_string())
+            .unwrap();
+
+        let target = database().await;
+        let archive = parse(&bytes).map_err(|err| err.to_string()).unwrap();
+        assert_eq!(archive.header.tables["staff"], STAFF.len() + 2);
+        let diff = restore(&target, archive, false)
+            .await
+            .map_err(|err| err.to_string())
+            .unwrap();
+        assert_eq!(table(&diff, "staff").added, vec!["staff:401", "staff:402"]);
+
+        for name in ["staff", "tips"] {
+            let restored = current_records(&target, name).await.unwrap();
+            assert_eq!(restored, current_records(&source, name).await.unwrap());
+        }
+        let tip: Option<String> = target
+            .query("SELECT VALUE employee.name FROM ONLY tips:401_2024_05_01")
+            .await
+            .unwrap()
+            .take(0)
+            .unwrap();
+        assert_eq!(tip.as_deref(), Some("Fay Ito"), "The link was not restored");
+    });
+}
+
+#[test]
+fn dry_run_reports_changes_without_making_them() {
+    block_on(async {
+        let db = database().await;
+        seed(&db).await;
+        let bytes = export(&db).await.map_err(|err| err.to_string()).unwrap();
+
+        db.query(
+            "
+            UPDATE staff:401 SET name = 'Fay Ito-Lane';
+            DELETE staff:402;
+            CREATE staff:403 CONTENT {
+                name: 'Hal Moss', card_id: '9000403', eid: 403,
+                created: '2024-05-03T09:00:00Z', modified: '2024-05-03T09:00:00Z'
+            };
+            ",
+        )
+        .await
+        .unwrap()
+        .check()
+        .unwrap();
+
+        let archive = parse(&bytes).map_err(|err| err.to_string()).unwrap();
+        let diff = restore(&db, archive, true)
+            .await
+            .map_err(|err| err.to_string())
+            .unwrap();
+
+        let staff = table(&diff, "staff");
+        assert_eq!(staff.added, vec!["staff:402"]);
+        assert_eq!(staff.changed, vec!["staff:401"]);
+        assert_eq!(staff.removed, vec!["staff:403"]);
+        assert_eq!(table(&diff, "tips").unchanged, 1);
+
+        let staff = current_records(&db, "staff").await.unwrap();
+        assert!(staff.contains_key("staff:401") && staff.contains_key("staff:403"));
+        assert!(!staff.contains_key("staff:402"));
+    });
+}
+
+#[test]
+fn parse_rejects_a_foreign_or_short_archive() {
+    let header = |format: &str, staff: usize| {
+        format!(
+            r#"{{"format":"{}","version":1,"schema_version":1,"created":"2024-05-01T00:00:00Z","tables":{{"staff":{}}}}}"#,
+            format, staff
+        )
+    };
+    let record = r#"{"table":"staff","id":"staff:1","record":{"name":"Fay Ito"}}"#;
+
+    assert!(parse(format!("{}\n{}\n", header(ARCHIVE_FORMAT, 1), record).as_bytes()).is_ok());
+    assert!(parse(format!("{}\n{}\n", header("other", 1), record).as_bytes()).is_err());
+    assert!(parse(format!("{}\n{}\n", header(ARCHIVE_FORMAT, 2), record).as_bytes()).is_err());
+    assert!(parse(b"").is_err());
+}
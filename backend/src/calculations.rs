@@ -1,26 +1,65 @@
 use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::error::Error;
 
+use crate::downloads::Export;
+
+mod batch;
 mod compute;
 mod generate;
+mod matching;
+pub mod policy;
+mod recalculate;
+#[cfg(test)]
+mod tests;
 mod transform;
 
+pub use batch::{recalculate_range, BatchRecalculation, BatchReport};
+pub use matching::StaffMatchError;
+pub use recalculate::{load_night, recalculate, NightAmendment, Recalculation};
+
 pub async fn read_csv(
     labor_report_data: LaborReportUpload,
     bytes: &[u8],
-) -> Result<(String, String, Summary, Vec<TippedDayCalculation>), Box<dyn Error>> {
-    let date = labor_report_data.date.clone().to_string();
+) -> Result<(Export, Export, Summary, Vec<TippedDayCalculation>), Box<dyn Error>> {
+    let night = labor_report_data.clone();
 
     let df = transform::transform(bytes)?;
-    let df = compute::compute(labor_report_data, df)?;
-    generate::generate(df, date).await
+    let df = matching::match_staff(df, &night.confirmed_matches).await?;
+    let policy = policy::current();
+    let (df, warnings) = compute::compute(labor_report_data, df, policy)?;
+    let (data_csv, template_csv, mut summary, tips) =
+        generate::generate(df, &night, policy).await?;
+    summary.warnings = warnings;
+    Ok((data_csv, template_csv, summary, tips))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Role {
+    Server,
+    Bartender,
+    Steward,
+}
+
+impl Role {
+    /// The role as the labor report names it.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Server => "Server",
+            Role::Bartender => "Bartender",
+            Role::Steward => "Steward",
+        }
+    }
 }
 
 #[derive(Debug, Default, Deserialize, Serialize)]
 pub struct Summary {
     pub total_tips: f32,
     pub average_net_hourly_pay: f32,
+    /// How the calculation handled an unusual night, for the manager.
+    #[serde(default)]
+    pub warnings: Vec<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
@@ -29,14 +68,109 @@ pub struct LaborReportUpload {
     pub total_sales: f32,
     pub go_tab_tips: f32,
     pub cash_tips: f32,
-    pub data_csv_link: String,
-    pub template_csv_link: String,
+    /// Staff `eid`s a manager confirmed for labor report rows that did not
+    /// match by payroll ID, keyed by payroll ID (or name when it is blank).
+    pub confirmed_matches: HashMap<String, i32>,
+    /// Manager adjustments to individual employees' shares of the pool.
+    #[serde(default)]
+    pub overrides: Vec<TipOverride>,
+}
+
+/// A manager's adjustment to one employee's part in a night's pool. Any
+/// combination may be set except that an employee on a fixed amount or
+/// excluded from the pool has no share to multiply.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct TipOverride {
+    pub eid: i32,
+    /// Hours to use in place of the labor report's.
+    pub hours: Option<f32>,
+    /// Net tips to pay instead of a share; the rest of the pool is split
+    /// among everyone else.
+    pub fixed_amount: Option<f32>,
+    /// Keeps the employee's wages but gives them no tips.
+    pub exclude: bool,
+    /// Weight on the employee's hours when the pool is split, e.g. `0.5`
+    /// for someone in training.
+    pub share_multiplier: Option<f32>,
+    pub reason: String,
+}
+
+impl TipOverride {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.reason.trim().is_empty() {
+            return Err(format!(
+                "A reason is required for the override of eid {}",
+                self.eid
+            ));
+        }
+        if self.hours.is_some_and(|hours| hours < 0.0)
+            || self.fixed_amount.is_some_and(|amount| amount < 0.0)
+            || self
+                .share_multiplier
+                .is_some_and(|multiplier| multiplier < 0.0)
+        {
+            return Err(format!("Overrides for eid {} cannot be negative", self.eid));
+        }
+        if [
+            self.fixed_amount.is_some(),
+            self.exclude,
+            self.share_multiplier.is_some(),
+        ]
+        .iter()
+        .filter(|set| **set)
+        .count()
+            > 1
+        {
+            return Err(format!(
+                "Use only one of fixed_amount, exclude or share_multiplier for eid {}",
+                self.eid
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// A labor report row a night was calculated from, with the staff member
+/// it was matched to.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LaborRow {
+    pub employee: String,
+    pub payroll_id: String,
+    pub role: String,
+    /// Wages for the shift, before tips.
+    pub total_pay: f32,
+    pub duration: f32,
+    pub eid: i32,
+}
+
+/// What a night was calculated from, as stored in `nights`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NightInputs {
+    pub date: NaiveDate,
+    pub total_sales: f32,
+    pub go_tab_tips: f32,
+    pub cash_tips: f32,
+    #[serde(default)]
+    pub labor_rows: Vec<LaborRow>,
+    #[serde(default)]
+    pub overrides: Vec<TipOverride>,
+    /// Tip policy the night was last calculated under; `None` for nights
+    /// calculated before policies were versioned.
+    #[serde(default)]
+    pub policy_version: Option<u32>,
 }
 
 #[derive(Debug, Serialize, Clone)]
 pub struct TippedDayCalculation {
     pub employee: String,
     pub role: String,
+    /// Share of the night's tip pool before tip-outs.
+    pub pool_share: f32,
+    /// Tip-out paid to stewards out of the pool share.
+    pub tip_out_paid: f32,
+    /// Tip-out received from the pool, for stewards.
+    pub tip_out_received: f32,
     pub net_tips: f32,
     pub total_pay_for_night: f32,
     pub hourly_pay_for_night: f32,
@@ -44,4 +178,6 @@ pub struct TippedDayCalculation {
     pub duration: f32,
     pub eid: i32,
     pub date: String,
+    /// Override the manager applied to this employee, kept for audit.
+    pub adjustment: Option<TipOverride>,
 }
@@ -0,0 +1,361 @@
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use surrealdb::sql::{Id, Thing};
+
+use crate::downloads::Export;
+use crate::DB;
+
+use super::{
+    compute,
+    generate::{self, TippedDayForCreate},
+    matching,
+    policy::{self, TipPolicy},
+    recalculate, transform, LaborReportUpload, NightInputs, TippedDayCalculation,
+};
+
+/// Stored nights to calculate again under a tip policy.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BatchRecalculation {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    /// Tip policy to use; the current one when left out.
+    #[serde(default)]
+    pub policy_version: Option<u32>,
+    /// Replaces the stored tips with the new ones, archiving the old rows.
+    /// Without it the batch only reports what would change.
+    #[serde(default)]
+    pub commit: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchReport {
+    /// `recalculations` record the batch was saved as, when committed.
+    pub id: Option<String>,
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+    pub policy_version: u32,
+    pub committed: bool,
+    pub net_tips_before: f32,
+    pub net_tips_after: f32,
+    pub nights: Vec<NightComparison>,
+    /// Nights in the range that could not be calculated again; a commit
+    /// leaves their tips as they were.
+    pub skipped: Vec<SkippedNight>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NightComparison {
+    pub date: NaiveDate,
+    /// Policy the stored tips were calculated under.
+    pub policy_version_before: Option<u32>,
+    pub net_tips_before: f32,
+    pub net_tips_after: f32,
+    pub warnings: Vec<String>,
+    pub employees: Vec<EmployeeComparison>,
+    #[serde(skip)]
+    tips: Vec<TippedDayCalculation>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EmployeeComparison {
+    pub eid: i32,
+    pub name: String,
+    pub net_tips_before: f32,
+    pub net_tips_after: f32,
+    /// Change in the employee's net tips; negative when they were paid
+    /// more under the old calculation.
+    pub difference: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SkippedNight {
+    pub date: NaiveDate,
+    pub reason: String,
+}
+
+/// Calculates every stored night between `batch.start` and `batch.end`
+/// again under the chosen policy and compares each employee's net tips
+/// with what is stored. With `commit`, every recalculated night's tips are
+/// replaced in one transaction, the old rows are kept in `tips_archive`
+/// and the report is saved in `recalculations`. `progress` is told how many
+/// of the nights are done as it goes.
+pub async fn recalculate_range(
+    batch: BatchRecalculation,
+    username: &str,
+    progress: &(dyn Fn(usize, usize) + Sync),
+) -> Result<BatchReport, Box<dyn Error>> {
+    let policy = batch.policy()?;
+
+    let mut report = BatchReport {
+        id: None,
+        start: batch.start,
+        end: batch.end,
+        policy_version: policy.version,
+        committed: false,
+        net_tips_before: 0.0,
+        net_tips_after: 0.0,
+        nights: Vec::new(),
+        skipped: Vec::new(),
+    };
+
+    let nights = load_nights(batch.start, batch.end).await?;
+    let total = nights.len();
+    for (done, night) in nights.into_iter().enumerate() {
+        progress(done, total);
+        let date = night.date;
+        match compare_night(night, policy)
+            .await
+            .map_err(|err| err.to_string())
+        {
+            Ok(comparison) => {
+                report.net_tips_before += comparison.net_tips_before;
+                report.net_tips_after += comparison.net_tips_after;
+                report.nights.push(comparison);
+            }
+            Err(reason) => report.skipped.push(SkippedNight { date, reason }),
+        }
+    }
+
+    if batch.commit && !report.nights.is_empty() {
+        let id = Thing::from(("recalculations".to_string(), Id::rand()));
+        commit(&id, &report, username).await?;
+        report.id = Some(id.to_string());
+        report.committed = true;
+    }
+
+    Ok(report)
+}
+
+impl BatchRecalculation {
+    /// The policy to recalculate under, once the range is known to be valid.
+    pub fn policy(&self) -> Result<&'static TipPolicy, String> {
+        if self.end < self.start {
+            return Err("The end of the range is before its start".to_string());
+        }
+        match self.policy_version {
+            Some(version) => policy::find(version)
+                .ok_or_else(|| format!("There is no tip policy version {}", version)),
+            None => Ok(policy::current()),
+        }
+    }
+}
+
+impl BatchReport {
+    /// One row per employee per night, for the comparison download.
+    pub fn csv(&self) -> Result<Export, Box<dyn Error>> {
+        #[derive(Serialize)]
+        struct Row<'a> {
+            date: NaiveDate,
+            eid: i32,
+            name: &'a str,
+            net_tips_before: f32,
+            net_tips_after: f32,
+            difference: f32,
+        }
+
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        for night in &self.nights {
+            for employee in &night.employees {
+                writer.serialize(Row {
+                    date: night.date,
+                    eid: employee.eid,
+                    name: &employee.name,
+                    net_tips_before: employee.net_tips_before,
+                    net_tips_after: employee.net_tips_after,
+                    difference: employee.difference,
+                })?;
+            }
+        }
+
+        Ok(Export::csv(
+            format!(
+                "{}_{}_policy_{}_recalculation.csv",
+                self.start, self.end, self.policy_version
+            ),
+            writer.into_inner().map_err(|err| err.to_string())?,
+        ))
+    }
+}
+
+async fn load_nights(start: NaiveDate, end: NaiveDate) -> Result<Vec<NightInputs>, Box<dyn Error>> {
+    let nights: Vec<NightInputs> = DB
+        .query(
+            "
+            SELECT date, total_sales, go_tab_tips, cash_tips, labor_rows ?? [] AS labor_rows,
+                overrides ?? [] AS overrides, policy_version
+            FROM nights WHERE date >= $start AND date <= $end ORDER BY date ASC;
+            ",
+        )
+        .bind(("start", start))
+        .bind(("end", end))
+        .await?
+        .take(0)?;
+    Ok(nights)
+}
+
+async fn compare_night(
+    night: NightInputs,
+    policy: &TipPolicy,
+) -> Result<NightComparison, Box<dyn Error>> {
+    if night.labor_rows.is_empty() {
+        return Err("The labor rows for the night were not kept".into());
+    }
+
+    let upload = LaborReportUpload {
+        date: night.date,
+        total_sales: night.total_sales,
+        go_tab_tips: night.go_tab_tips,
+        cash_tips: night.cash_tips,
+        confirmed_matches: recalculate::confirmed_matches(&night.labor_rows),
+        overrides: night.overrides,
+    };
+    let df = transform::from_labor_rows(&night.labor_rows)?;
+    let df = matching::match_staff(df, &upload.confirmed_matches).await?;
+    let (df, warnings) = compute::compute(upload.clone(), df, policy)?;
+    let tips = generate::calculated_tips(df, &upload)?;
+
+    let mut employees: BTreeMap<i32, EmployeeComparison> = BTreeMap::new();
+    for tip in stored_tips(night.date).await? {
+        employees.insert(
+            tip.eid,
+            EmployeeComparison {
+                eid: tip.eid,
+                name: tip.name,
+                net_tips_before: tip.net_tips,
+                net_tips_after: 0.0,
+                difference: 0.0,
+            },
+        );
+    }
+    for tip in &tips {
+        let employee = employees
+            .entry(tip.eid)
+            .or_insert_with(|| EmployeeComparison {
+                eid: tip.eid,
+                name: tip.employee.clone(),
+                net_tips_before: 0.0,
+                net_tips_after: 0.0,
+                difference: 0.0,
+            });
+        employee.net_tips_after = tip.net_tips;
+    }
+    for employee in employees.values_mut() {
+        employee.difference = employee.net_tips_after - employee.net_tips_before;
+    }
+    let employees: Vec<EmployeeComparison> = employees.into_values().collect();
+
+    Ok(NightComparison {
+        date: night.date,
+        policy_version_before: night.policy_version,
+        net_tips_before: employees.iter().map(|e| e.net_tips_before).sum(),
+        net_tips_after: employees.iter().map(|e| e.net_tips_after).sum(),
+        warnings,
+        employees,
+        tips,
+    })
+}
+
+#[derive(Deserialize)]
+struct StoredTip {
+    eid: i32,
+    name: String,
+    net_tips: f32,
+}
+
+async fn stored_tips(date: NaiveDate) -> Result<Vec<StoredTip>, Box<dyn Error>> {
+    let tips: Vec<StoredTip> = DB
+        .query(
+            "
+            SELECT eid, name, net_tips FROM tips WHERE date = $date;
+            ",
+        )
+        .bind(("date", date))
+        .await?
+        .take(0)?;
+    Ok(tips)
+}
+
+async fn commit(id: &Thing, report: &BatchReport, username: &str) -> Result<(), Box<dyn Error>> {
+    #[derive(Serialize)]
+    struct TipRecord {
+        id: Thing,
+        content: TippedDayForCreate,
+    }
+
+    #[derive(Serialize)]
+    struct NightCommit {
+        date: NaiveDate,
+        tips: Vec<TipRecord>,
+    }
+
+    let nights = report
+        .nights
+        .iter()
+        .map(|night| {
+            Ok(NightCommit {
+                date: night.date,
+                tips: night
+                    .tips
+                    .iter()
+                    .cloned()
+                    .map(|tip| {
+                        Ok(TipRecord {
+                            id: Thing::from((
+                                "tips".to_string(),
+                                format!("{}_{}", tip.eid, tip.date),
+                            )),
+                            content: TippedDayForCreate::new(tip, report.policy_version)?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, chrono::ParseError>>()?,
+            })
+        })
+        .collect::<Result<Vec<_>, chrono::ParseError>>()?;
+
+    DB.query(
+        "
+        BEGIN TRANSACTION;
+        CREATE $recalculation CONTENT {
+            start: $start,
+            end: $end,
+            policy_version: $policy_version,
+            report: $report,
+            created_by: $username,
+            created: $now
+        };
+        FOR $night IN $nights {
+            FOR $tip IN (SELECT * OMIT id, employee FROM tips WHERE date = $night.date) {
+                CREATE tips_archive CONTENT {
+                    tip: $tip,
+                    eid: $tip.eid,
+                    date: $tip.date,
+                    recalculation: $recalculation,
+                    archived: $now
+                };
+            };
+            DELETE tips WHERE date = $night.date;
+            FOR $tip IN $night.tips {
+                CREATE $tip.id CONTENT $tip.content;
+            };
+            UPDATE type::thing('nights', $night.date) SET
+                policy_version = $policy_version,
+                modified = $now;
+        };
+        COMMIT TRANSACTION;
+        ",
+    )
+    .bind(("recalculation", id.clone()))
+    .bind(("start", report.start))
+    .bind(("end", report.end))
+    .bind(("policy_version", report.policy_version))
+    .bind(("report", report))
+    .bind(("username", username))
+    .bind(("now", Utc::now()))
+    .bind(("nights", nights))
+    .await?
+    .check()?;
+
+    Ok(())
+}
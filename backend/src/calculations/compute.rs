@@ -1,48 +1,228 @@
 use polars::prelude::*;
 
-use super::LaborReportUpload;
+use super::{policy::TipPolicy, LaborReportUpload, TipOverride};
 
+/// Splits the night's tips under `policy`, returning the calculated frame
+/// and warnings about anything the manager should know was handled
+/// unusually.
+///
+/// Under a policy with short-staffed rules, with no stewards on the labor
+/// report the steward tip-out is waived and servers and bartenders keep
+/// it. With no servers or bartenders the tip pool goes to the stewards
+/// instead. A night with tips and nobody to pay them to is an error.
 pub fn compute(
     labor_report_upload: LaborReportUpload,
     df: DataFrame,
-) -> Result<DataFrame, PolarsError> {
-    dbg!(&df);
+    policy: &TipPolicy,
+) -> Result<(DataFrame, Vec<String>), PolarsError> {
+    let mut warnings = role_warnings(&df)?;
+    let df = apply_overrides(df, &labor_report_upload.overrides)?;
+    let df = wages(df)?;
     let base_hours = compute_base_hours(df.clone())?;
+    let tipped_hours = role_hours(&base_hours, col("role").neq(lit("Steward")))?;
+    let steward_hours = role_hours(&base_hours, col("role").eq(lit("Steward")))?;
     let total_tips = labor_report_upload.cash_tips + labor_report_upload.go_tab_tips;
-    let df = proportion_of_total_tipped_hours(df, base_hours.clone())?;
-    let df = proportion_of_total_tips(total_tips, df)?;
+    let df = proportion_of_total_tipped_hours(df, tipped_hours)?;
+    let shared_tips = total_tips - fixed_tips(&df, col("role").neq(lit("Steward")))?;
+    if shared_tips < 0.0 {
+        return Err(PolarsError::ComputeError(
+            "Fixed amounts add up to more than the tip pool".into(),
+        ));
+    }
+
+    let redirected_tips = match tipped_hours > 0.0 || !policy.short_staffed_rules {
+        true => 0.0,
+        false => shared_tips,
+    };
+    if redirected_tips > 0.0 {
+        if steward_hours <= 0.0 {
+            return Err(PolarsError::ComputeError(
+                "No one on the labor report can receive the tip pool".into(),
+            ));
+        }
+        warnings.push(format!(
+            "No servers or bartenders shared the pool, so all {:.2} in tips went to the stewards",
+            redirected_tips
+        ));
+    }
+
+    let tip_out_rate = match steward_hours > 0.0 || !policy.short_staffed_rules {
+        true => policy.steward_tip_out,
+        false => 0.0,
+    };
+    if tip_out_rate == 0.0 && tipped_hours > 0.0 && labor_report_upload.total_sales > 0.0 {
+        warnings.push("No stewards worked, so the steward tip-out was waived".to_string());
+    }
+
+    let df = proportion_of_total_tips(shared_tips, df)?;
     let df = proportion_of_total_sales(labor_report_upload.total_sales, df)?;
-    let df = steward_tip_out(df)?;
-    let df = proportion_of_total_steward_hours(df, base_hours)?;
-    let df = proportion_of_total_steward_tips(df)?;
+    let df = steward_tip_out(df, tip_out_rate)?;
+    let df = proportion_of_total_steward_hours(df, steward_hours)?;
+    let df = proportion_of_total_steward_tips(df, redirected_tips)?;
     let df = net_tips(df)?;
     let df = total_pay_for_night(df)?;
     let df = tipped_hourly_for_night(df)?;
-    hourly_pay_for_night(df)
+    let df = hourly_pay_for_night(df)?;
+    Ok((ensure_finite(df)?, warnings))
+}
+
+/// Tells the manager about each shift paid in a different role from the
+/// one on the labor report because of the staff record.
+fn role_warnings(df: &DataFrame) -> Result<Vec<String>, PolarsError> {
+    let employees = df.column("employee")?.str()?;
+    let roles = df.column("role")?.str()?;
+    let reported_roles = df.column("reported_role")?.str()?;
+
+    Ok(employees
+        .into_iter()
+        .zip(roles)
+        .zip(reported_roles)
+        .filter(|((_, role), reported_role)| role != reported_role)
+        .map(|((employee, role), reported_role)| {
+            format!(
+                "{} was a {} on the labor report but was paid as a {}, their role on record",
+                employee.unwrap_or_default(),
+                reported_role.unwrap_or_default(),
+                role.unwrap_or_default()
+            )
+        })
+        .collect())
+}
+
+/// Swaps in corrected hours, keeping the labor report's as
+/// `reported_duration`, and adds each employee's `share_weight`, the
+/// multiplier on their hours when the pool is split, and `fixed_tips`,
+/// the net tips they get instead of a share, from the manager's overrides.
+/// The share weight starts from the staff record's `point_weight`.
+fn apply_overrides(df: DataFrame, overrides: &[TipOverride]) -> Result<DataFrame, PolarsError> {
+    let eids = df.column("eid")?.i32()?.clone();
+
+    for (i, adjustment) in overrides.iter().enumerate() {
+        adjustment
+            .validate()
+            .map_err(|err| PolarsError::ComputeError(err.into()))?;
+        if !eids.into_iter().any(|eid| eid == Some(adjustment.eid)) {
+            return Err(PolarsError::ComputeError(
+                format!("eid {} is not on the labor report", adjustment.eid).into(),
+            ));
+        }
+        if overrides[..i]
+            .iter()
+            .any(|other| other.eid == adjustment.eid)
+        {
+            return Err(PolarsError::ComputeError(
+                format!("eid {} has more than one override", adjustment.eid).into(),
+            ));
+        }
+    }
+
+    let durations = df.column("duration")?.f32()?.clone();
+    let point_weights = df.column("point_weight")?.f32()?.clone();
+    let mut duration = Vec::with_capacity(df.height());
+    let mut share_weight = Vec::with_capacity(df.height());
+    let mut fixed_tips = Vec::with_capacity(df.height());
+
+    for ((eid, hours), point_weight) in eids.into_iter().zip(&durations).zip(&point_weights) {
+        let point_weight = point_weight.unwrap_or(1.0);
+        let adjustment = overrides
+            .iter()
+            .find(|adjustment| Some(adjustment.eid) == eid);
+
+        duration.push(
+            adjustment
+                .and_then(|adjustment| adjustment.hours)
+                .or(hours)
+                .unwrap_or_default(),
+        );
+        share_weight.push(match adjustment {
+            Some(adjustment) if adjustment.exclude || adjustment.fixed_amount.is_some() => 0.0,
+            Some(adjustment) => point_weight * adjustment.share_multiplier.unwrap_or(1.0),
+            None => point_weight,
+        });
+        fixed_tips.push(match adjustment {
+            Some(adjustment) if adjustment.exclude => Some(0.0),
+            Some(adjustment) => adjustment.fixed_amount,
+            None => None,
+        });
+    }
+
+    let mut df = df;
+    let reported_duration = df
+        .column("duration")?
+        .clone()
+        .with_name("reported_duration");
+    df.with_column(Series::new("duration", duration))?;
+    df.hstack_mut(&[
+        reported_duration,
+        Series::new("share_weight", share_weight),
+        Series::new("fixed_tips", fixed_tips),
+    ])?;
+    Ok(df)
+}
+
+/// Adds `wages`, the shift's pay before tips: the staff record's hourly
+/// wage for the hours worked, or the labor report's `total_pay` without
+/// one.
+fn wages(df: DataFrame) -> Result<DataFrame, PolarsError> {
+    df.lazy()
+        .with_column(
+            when(col("hourly_wage").is_not_null())
+                .then(col("hourly_wage").cast(DataType::Float32) * col("duration"))
+                .otherwise(col("total_pay"))
+                .alias("wages"),
+        )
+        .collect()
+}
+
+/// Hours counted toward an employee's share of the pool.
+fn weighted_hours() -> Expr {
+    col("duration") * col("share_weight")
+}
+
+/// Total `fixed_tips` paid to the rows matching `filter`.
+fn fixed_tips(df: &DataFrame, filter: Expr) -> Result<f32, PolarsError> {
+    df.clone()
+        .lazy()
+        .filter(filter)
+        .collect()?
+        .column("fixed_tips")?
+        .sum()
 }
 
 fn compute_base_hours(df: DataFrame) -> Result<DataFrame, PolarsError> {
     df.lazy()
         .group_by([col("role")])
-        .agg([col("duration").sum().alias("role_hours")])
+        .agg([weighted_hours().sum().alias("role_hours")])
         .collect()
 }
 
-fn proportion_of_total_tipped_hours(
-    df: DataFrame,
-    base_hours: DataFrame,
-) -> Result<DataFrame, PolarsError> {
-    let tipped_hours: f32 = base_hours
+/// Weighted hours worked by the roles matching `filter`.
+fn role_hours(base_hours: &DataFrame, filter: Expr) -> Result<f32, PolarsError> {
+    base_hours
+        .clone()
         .lazy()
-        .filter(col("role").neq(lit("Steward")))
+        .filter(filter)
         .collect()?
         .column("role_hours")?
-        .sum()?;
+        .sum()
+}
 
+/// `weighted_hours()` over `hours`, or zero when no one worked any.
+fn share_of(hours: f32) -> Expr {
+    match hours > 0.0 {
+        true => weighted_hours() / lit(hours),
+        false => lit(0.0f32),
+    }
+}
+
+fn proportion_of_total_tipped_hours(
+    df: DataFrame,
+    tipped_hours: f32,
+) -> Result<DataFrame, PolarsError> {
     df.lazy()
         .with_column(
             when(col("role").neq(lit("Steward")))
-                .then(col("duration") / lit(tipped_hours))
+                .then(share_of(tipped_hours))
                 .otherwise(0)
                 .alias("proportion_of_total_tipped_hours"),
         )
@@ -51,19 +231,12 @@ fn proportion_of_total_tipped_hours(
 
 fn proportion_of_total_steward_hours(
     df: DataFrame,
-    base_hours: DataFrame,
+    steward_hours: f32,
 ) -> Result<DataFrame, PolarsError> {
-    let steward_hours: f32 = base_hours
-        .lazy()
-        .filter(col("role").eq(lit("Steward")))
-        .collect()?
-        .column("role_hours")?
-        .sum()?;
-
     df.lazy()
         .with_column(
             when(col("role").eq(lit("Steward")))
-                .then(col("duration") / lit(steward_hours))
+                .then(share_of(steward_hours))
                 .otherwise(0)
                 .alias("proportion_of_total_steward_hours"),
         )
@@ -88,12 +261,11 @@ fn proportion_of_total_sales(total_sales: f32, df: DataFrame) -> Result<DataFram
         .collect()
 }
 
-fn steward_tip_out(df: DataFrame) -> Result<DataFrame, PolarsError> {
-    let steward_tip_out = 0.025;
+fn steward_tip_out(df: DataFrame, rate: f32) -> Result<DataFrame, PolarsError> {
     df.lazy()
         .select([
             col("*"),
-            (col("proportion_of_total_sales") * lit(steward_tip_out)).alias("steward_tip_out"),
+            (col("proportion_of_total_sales") * lit(rate)).alias("steward_tip_out"),
         ])
         .collect()
 }
@@ -101,7 +273,9 @@ fn steward_tip_out(df: DataFrame) -> Result<DataFrame, PolarsError> {
 fn net_tips(df: DataFrame) -> Result<DataFrame, PolarsError> {
     df.lazy()
         .with_column(
-            when(col("role").neq(lit("Steward")))
+            when(col("fixed_tips").is_not_null())
+                .then(col("fixed_tips"))
+                .when(col("role").neq(lit("Steward")))
                 .then(col("proportion_of_total_tips") - col("steward_tip_out"))
                 .otherwise(col("proportion_of_total_steward_tips"))
                 .alias("net_tips"),
@@ -113,8 +287,19 @@ fn total_steward_tip_out(df: DataFrame) -> Result<f32, PolarsError> {
     df.column("steward_tip_out")?.sum::<f32>()
 }
 
-fn proportion_of_total_steward_tips(df: DataFrame) -> Result<DataFrame, PolarsError> {
-    let total_steward_tip_out = total_steward_tip_out(df.clone())?;
+/// Splits the tip-out, plus any of the pool `redirected_tips` that no
+/// server or bartender could take, among the stewards by hours.
+fn proportion_of_total_steward_tips(
+    df: DataFrame,
+    redirected_tips: f32,
+) -> Result<DataFrame, PolarsError> {
+    let total_steward_tip_out = total_steward_tip_out(df.clone())? + redirected_tips
+        - fixed_tips(&df, col("role").eq(lit("Steward")))?;
+    if total_steward_tip_out < 0.0 {
+        return Err(PolarsError::ComputeError(
+            "Fixed amounts for stewards add up to more than their tip-out".into(),
+        ));
+    }
 
     df.lazy()
         .select([
@@ -129,7 +314,7 @@ fn total_pay_for_night(df: DataFrame) -> Result<DataFrame, PolarsError> {
     df.lazy()
         .select([
             col("*"),
-            (col("total_pay") + col("net_tips")).alias("total_pay_for_night"),
+            (col("wages") + col("net_tips")).alias("total_pay_for_night"),
         ])
         .collect()
 }
@@ -138,23 +323,50 @@ fn hourly_pay_for_night(df: DataFrame) -> Result<DataFrame, PolarsError> {
     df.lazy()
         .select([
             col("*"),
-            (col("total_pay_for_night") / col("duration")).alias("hourly_pay_for_night"),
+            per_hour(col("total_pay_for_night")).alias("hourly_pay_for_night"),
         ])
         .sort(["role"], Default::default())
         .collect()
 }
 
 fn tipped_hourly_for_night(df: DataFrame) -> Result<DataFrame, PolarsError> {
-    dbg!(&df);
-    let df = df
-        .lazy()
-        .clone()
+    df.lazy()
         .select([
             col("*"),
-            (col("net_tips") / col("duration")).alias("tipped_hourly_for_night"),
+            per_hour(col("net_tips")).alias("tipped_hourly_for_night"),
         ])
-        .collect();
+        .collect()
+}
+
+/// `amount` per hour worked, or zero for a shift with no hours.
+fn per_hour(amount: Expr) -> Expr {
+    when(col("duration").gt(lit(0.0f32)))
+        .then(amount / col("duration"))
+        .otherwise(lit(0.0f32))
+}
 
-    dbg!(&df);
-    df
+/// Rejects a calculation that came out with an infinite or NaN amount, so
+/// one never reaches the database or a payout file.
+fn ensure_finite(df: DataFrame) -> Result<DataFrame, PolarsError> {
+    for series in df.get_columns() {
+        if !series.dtype().is_float() {
+            continue;
+        }
+        let values = series.cast(&DataType::Float64)?;
+        if values
+            .f64()?
+            .into_iter()
+            .flatten()
+            .any(|value| !value.is_finite())
+        {
+            return Err(PolarsError::ComputeError(
+                format!(
+                    "{} is not a finite amount for every employee",
+                    series.name()
+                )
+                .into(),
+            ));
+        }
+    }
+    Ok(df)
 }
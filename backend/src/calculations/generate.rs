@@ -1,25 +1,28 @@
 use chrono::{DateTime, NaiveDate, Utc};
-use futures::future::join_all;
+use futures::future::try_join_all;
 use polars::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
 use std::error::Error;
-use std::hash::Hash;
 use surrealdb::sql::Thing;
 
+use crate::downloads::Export;
 use crate::DB;
 
-use super::{Summary, TippedDayCalculation};
+use super::{
+    policy::TipPolicy, LaborReportUpload, LaborRow, Summary, TipOverride, TippedDayCalculation,
+};
 
 pub async fn generate(
     df: DataFrame,
-    date: String,
-) -> Result<(String, String, Summary, Vec<TippedDayCalculation>), Box<dyn Error>> {
-    let df = verify_cardholders(df)?;
-    let df = verify_unique_timekeepers(df)?;
-    let df = join_card_ids(df, date.clone())?;
+    night: &LaborReportUpload,
+    policy: &TipPolicy,
+) -> Result<(Export, Export, Summary, Vec<TippedDayCalculation>), Box<dyn Error>> {
+    let date = night.date.to_string();
+    let df = add_date(df, date.clone())?;
 
-    let tips: Vec<TippedDayCalculation> = post_to_db(df.clone()).await?;
+    let tips = tip_rows(&df, &night.overrides)?;
+    post_to_db(tips.clone(), policy.version).await?;
+    post_night_to_db(night, labor_rows(&df)?, policy.version).await?;
 
     let mut df = df.sort(["role"], Default::default())?;
 
@@ -29,23 +32,22 @@ pub async fn generate(
 
     let template_file_name = format!("{}_rapidpay_upload_template.csv", date);
 
-    let mut file = std::fs::File::create(format!("public/downloads/{}", data_filename))
-        .expect("Could not create .csv file in public/downlads (1)");
-    CsvWriter::new(&mut file)
-        .finish(&mut df)
-        .expect("Could not write to .csv file");
-    let mut file = std::fs::File::create(format!("public/downloads/{}", template_file_name))
-        .expect("Could not create .csv file in public/downloads (2)");
-    CsvWriter::new(&mut file)
-        .finish(&mut template_df)
-        .expect("Could not write to .csv file");
+    let mut data_csv = Vec::new();
+    CsvWriter::new(&mut data_csv).finish(&mut df)?;
+    let mut template_csv = Vec::new();
+    CsvWriter::new(&mut template_csv).finish(&mut template_df)?;
 
     let summary: Summary = summarize(df)?;
 
-    Ok((data_filename, template_file_name, summary, tips))
+    Ok((
+        Export::csv(data_filename, data_csv),
+        Export::csv(template_file_name, template_csv),
+        summary,
+        tips,
+    ))
 }
 
-fn generate_upload_template(df: DataFrame, date: String) -> Result<DataFrame, PolarsError> {
+pub fn generate_upload_template(df: DataFrame, date: String) -> Result<DataFrame, PolarsError> {
     let df = df
         .lazy()
         .select(&[
@@ -78,72 +80,20 @@ fn generate_upload_template(df: DataFrame, date: String) -> Result<DataFrame, Po
         .collect()
 }
 
-fn verify_cardholders(df: DataFrame) -> Result<DataFrame, Box<dyn Error>> {
-    let mut cardholders: Vec<String> = Vec::new();
-
-    let mut reader = csv::Reader::from_path("public/cards.csv")?;
-    for result in reader.deserialize() {
-        let cardholder: String = result?;
-        cardholders.push(cardholder);
-    }
-
-    let workers = df
-        .column("employee")?
-        .str()?
-        .into_iter()
-        .map(|str| str.unwrap_or("NoWorker").to_string())
-        .collect::<Vec<String>>();
-
-    let eval = workers.iter().all(|item| cardholders.contains(item));
-
-    match eval {
-        true => Ok(df),
-        false => Err("Staff member not a cardholder".into()),
-    }
-}
-
-fn verify_unique_timekeepers(df: DataFrame) -> Result<DataFrame, Box<dyn Error>> {
-    let workers = df
-        .column("employee")?
-        .str()?
-        .into_iter()
-        .map(|str| str.unwrap_or("NoWorker").to_string())
-        .collect::<Vec<String>>();
-
-    let eval = has_unique_elements(workers);
-
-    match eval {
-        true => Ok(df),
-        false => Err("Timekeepers not unique".into()),
-    }
-}
-
-fn has_unique_elements<T>(iter: T) -> bool
-where
-    T: IntoIterator,
-    T::Item: Eq + Hash,
-{
-    let mut uniq = HashSet::new();
-    iter.into_iter().all(move |x| uniq.insert(x))
-}
-
-fn join_card_ids(df: DataFrame, date: String) -> Result<DataFrame, PolarsError> {
-    let df_card_ids = CsvReadOptions::default()
-        .with_has_header(true)
-        .try_into_reader_with_file_path(Some("public/cards.csv".into()))?
-        .finish()?;
-
+fn add_date(df: DataFrame, date: String) -> Result<DataFrame, PolarsError> {
     df.lazy()
-        .join(
-            df_card_ids.lazy(),
-            [col("employee")],
-            [col("name")],
-            JoinArgs::new(JoinType::Inner),
-        )
         .with_columns(vec![lit(date).alias("date")])
         .collect()
 }
 
+/// Each employee's tips for the night, without saving them.
+pub fn calculated_tips(
+    df: DataFrame,
+    night: &LaborReportUpload,
+) -> Result<Vec<TippedDayCalculation>, PolarsError> {
+    tip_rows(&add_date(df, night.date.to_string())?, &night.overrides)
+}
+
 fn summarize(df: DataFrame) -> Result<Summary, Box<dyn Error>> {
     let total_tips: f32 = df.column("net_tips")?.sum()?;
 
@@ -151,80 +101,187 @@ fn summarize(df: DataFrame) -> Result<Summary, Box<dyn Error>> {
 
     let num_employees = df.column("employee")?.len();
 
-    let average_net_hourly_pay: f32 = total_hourly_pay / num_employees as f32;
+    let average_net_hourly_pay: f32 = match num_employees {
+        0 => 0.0,
+        num_employees => total_hourly_pay / num_employees as f32,
+    };
 
     Ok(Summary {
         total_tips,
         average_net_hourly_pay,
+        ..Default::default()
     })
 }
 
-async fn post_to_db(df: DataFrame) -> Result<Vec<TippedDayCalculation>, PolarsError> {
-    let tips: Vec<TippedDayCalculation> = df
-        .into_struct("StructChunked")
-        .iter()
-        .map(|row| TippedDayCalculation {
-            employee: row[0].get_str().unwrap().to_string(),
-            role: row[2].get_str().unwrap().to_string(),
-            net_tips: row[11].try_extract().unwrap(),
-            total_pay_for_night: row[12].try_extract().unwrap(),
-            hourly_pay_for_night: row[14].try_extract().unwrap(),
-            tipped_hour_for_night: row[13].try_extract().unwrap(),
-            duration: row[4].try_extract().unwrap(),
-            eid: row[16].try_extract().unwrap(),
-            date: row[17].get_str().unwrap().to_string(),
+fn tip_rows(
+    df: &DataFrame,
+    overrides: &[TipOverride],
+) -> Result<Vec<TippedDayCalculation>, PolarsError> {
+    let employees = df.column("employee")?.str()?;
+    let roles = df.column("role")?.str()?;
+    let pool_share = floats(df, "proportion_of_total_tips")?;
+    let tip_out_paid = floats(df, "steward_tip_out")?;
+    let tip_out_received = floats(df, "proportion_of_total_steward_tips")?;
+    let net_tips = floats(df, "net_tips")?;
+    let total_pay_for_night = floats(df, "total_pay_for_night")?;
+    let hourly_pay_for_night = floats(df, "hourly_pay_for_night")?;
+    let tipped_hourly_for_night = floats(df, "tipped_hourly_for_night")?;
+    let durations = floats(df, "duration")?;
+    let eids = df.column("eid")?.i32()?;
+    let dates = df.column("date")?.str()?;
+
+    Ok((0..df.height())
+        .map(|i| {
+            let eid = eids.get(i).unwrap_or_default();
+            TippedDayCalculation {
+                employee: employees.get(i).unwrap_or_default().to_string(),
+                role: roles.get(i).unwrap_or_default().to_string(),
+                pool_share: pool_share.get(i).unwrap_or_default(),
+                tip_out_paid: tip_out_paid.get(i).unwrap_or_default(),
+                tip_out_received: tip_out_received.get(i).unwrap_or_default(),
+                net_tips: net_tips.get(i).unwrap_or_default(),
+                total_pay_for_night: total_pay_for_night.get(i).unwrap_or_default(),
+                hourly_pay_for_night: hourly_pay_for_night.get(i).unwrap_or_default(),
+                tipped_hour_for_night: tipped_hourly_for_night.get(i).unwrap_or_default(),
+                duration: durations.get(i).unwrap_or_default(),
+                eid,
+                date: dates.get(i).unwrap_or_default().to_string(),
+                adjustment: overrides
+                    .iter()
+                    .find(|adjustment| adjustment.eid == eid)
+                    .cloned(),
+            }
         })
-        .collect();
-
-    join_all(
-        tips.clone()
-            .into_iter()
-            .map(|tip| async move {
-                DB.update::<Option<TippedDayForCreate>>((
-                    "tips",
-                    format!("{}_{}", tip.eid.clone(), tip.date.clone().as_str()),
-                ))
-                .content(TippedDayForCreate {
-                    name: tip.employee,
-                    employee: Thing {
-                        tb: "staff".to_string(),
-                        id: tip.eid.into(),
-                    },
-                    role: tip.role,
-                    net_tips: tip.net_tips,
-                    total_pay_for_night: tip.total_pay_for_night,
-                    hourly_pay_for_night: tip.hourly_pay_for_night,
-                    tipped_hour_for_night: tip.tipped_hour_for_night,
-                    duration: tip.duration,
-                    eid: tip.eid,
-                    date: NaiveDate::parse_from_str(tip.date.as_str(), "%Y-%m-%d")
-                        .expect("Could not create NaiveDate"),
-                    created: Utc::now(),
-                    modified: Utc::now(),
-                })
-                .await
-                .expect("Could not post tab to DB")
-                .expect("Could not post tab to DB")
-            })
-            .collect::<Vec<_>>(),
+        .collect())
+}
+
+async fn post_to_db(
+    tips: Vec<TippedDayCalculation>,
+    policy_version: u32,
+) -> Result<(), Box<dyn Error>> {
+    let records = tips
+        .into_iter()
+        .map(|tip| {
+            let id = format!("{}_{}", tip.eid, tip.date);
+            Ok((id, TippedDayForCreate::new(tip, policy_version)?))
+        })
+        .collect::<Result<Vec<_>, chrono::ParseError>>()?;
+
+    try_join_all(records.into_iter().map(|(id, record)| async move {
+        DB.update::<Option<TippedDayForCreate>>(("tips", id))
+            .content(record)
+            .await
+    }))
+    .await?;
+    Ok(())
+}
+
+/// The labor report rows the night was calculated from, with the staff
+/// member each one was matched to.
+fn labor_rows(df: &DataFrame) -> Result<Vec<LaborRow>, PolarsError> {
+    let employees = df.column("employee")?.str()?;
+    let payroll_ids = df.column("payroll_id")?.str()?;
+    let roles = df.column("reported_role")?.str()?;
+    let total_pay = df.column("total_pay")?.f32()?;
+    let durations = df.column("reported_duration")?.f32()?;
+    let eids = df.column("eid")?.i32()?;
+
+    Ok((0..df.height())
+        .map(|i| LaborRow {
+            employee: employees.get(i).unwrap_or_default().to_string(),
+            payroll_id: payroll_ids.get(i).unwrap_or_default().to_string(),
+            role: roles.get(i).unwrap_or_default().to_string(),
+            total_pay: total_pay.get(i).unwrap_or_default(),
+            duration: durations.get(i).unwrap_or_default(),
+            eid: eids.get(i).unwrap_or_default(),
+        })
+        .collect())
+}
+
+/// Keeps the manager-entered inputs and labor rows for the night alongside
+/// the tips so it can be reported on and recalculated later.
+async fn post_night_to_db(
+    night: &LaborReportUpload,
+    labor_rows: Vec<LaborRow>,
+    policy_version: u32,
+) -> Result<(), Box<dyn Error>> {
+    DB.query(
+        "
+        UPDATE type::thing('nights', $date) SET
+            date = $date,
+            total_sales = $total_sales,
+            go_tab_tips = $go_tab_tips,
+            cash_tips = $cash_tips,
+            labor_rows = $labor_rows,
+            overrides = $overrides,
+            policy_version = $policy_version,
+            created = created ?? $now,
+            modified = $now;
+        ",
     )
-    .await;
+    .bind(("date", night.date))
+    .bind(("total_sales", night.total_sales))
+    .bind(("go_tab_tips", night.go_tab_tips))
+    .bind(("cash_tips", night.cash_tips))
+    .bind(("labor_rows", labor_rows))
+    .bind(("overrides", night.overrides.clone()))
+    .bind(("policy_version", policy_version))
+    .bind(("now", Utc::now()))
+    .await?
+    .check()?;
+
+    Ok(())
+}
 
-    Ok(tips)
+fn floats(df: &DataFrame, name: &str) -> Result<Float32Chunked, PolarsError> {
+    Ok(df.column(name)?.cast(&DataType::Float32)?.f32()?.clone())
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct TippedDayForCreate {
+pub struct TippedDayForCreate {
     name: String,
     employee: Thing,
     role: String,
+    pool_share: f32,
+    tip_out_paid: f32,
+    tip_out_received: f32,
     net_tips: f32,
     total_pay_for_night: f32,
     hourly_pay_for_night: f32,
     tipped_hour_for_night: f32,
     duration: f32,
     eid: i32,
+    adjustment: Option<TipOverride>,
+    /// Version of the tip policy the row was calculated under.
+    policy_version: u32,
     date: NaiveDate,
     created: DateTime<Utc>,
     modified: DateTime<Utc>,
 }
+
+impl TippedDayForCreate {
+    pub fn new(tip: TippedDayCalculation, policy_version: u32) -> Result<Self, chrono::ParseError> {
+        Ok(Self {
+            name: tip.employee,
+            employee: Thing {
+                tb: "staff".to_string(),
+                id: tip.eid.into(),
+            },
+            role: tip.role,
+            pool_share: tip.pool_share,
+            tip_out_paid: tip.tip_out_paid,
+            tip_out_received: tip.tip_out_received,
+            net_tips: tip.net_tips,
+            total_pay_for_night: tip.total_pay_for_night,
+            hourly_pay_for_night: tip.hourly_pay_for_night,
+            tipped_hour_for_night: tip.tipped_hour_for_night,
+            duration: tip.duration,
+            eid: tip.eid,
+            adjustment: tip.adjustment,
+            policy_version,
+            date: NaiveDate::parse_from_str(tip.date.as_str(), "%Y-%m-%d")?,
+            created: Utc::now(),
+            modified: Utc::now(),
+        })
+    }
+}
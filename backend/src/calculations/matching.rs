@@ -0,0 +1,265 @@
+use chrono::Utc;
+use futures::future::join_all;
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+
+use super::Role;
+use crate::DB;
+
+const FUZZY_MATCH_THRESHOLD: f64 = 0.85;
+const MAX_CANDIDATES: usize = 3;
+
+/// Matches every labor report row to a staff record by payroll ID and
+/// appends the staff member's `card_id` and `eid` columns, with the
+/// `point_weight` and `hourly_wage` from their employment terms.
+///
+/// A row whose role is not one the staff record lists is paid in the
+/// staff member's primary role; the report's role is kept as
+/// `reported_role`. Without a primary role on record the report's is used.
+///
+/// Rows whose payroll ID is unknown are only accepted when the manager has
+/// confirmed a staff member for them in `confirmed`, keyed by
+/// [`match_key`]. Confirmed payroll IDs are saved on the staff record so
+/// the next report matches directly.
+pub async fn match_staff(
+    df: DataFrame,
+    confirmed: &HashMap<String, i32>,
+) -> Result<DataFrame, Box<dyn Error>> {
+    let staff: Vec<StaffIdentity> = DB
+        .query(
+            "
+            SELECT eid, name, card_id, payroll_ids ?? [] AS payroll_ids, active ?? true AS active,
+                employment.primary_role AS primary_role,
+                employment.secondary_roles ?? [] AS secondary_roles,
+                employment.hourly_wage AS hourly_wage,
+                employment.point_weight ?? 1.0 AS point_weight
+            FROM staff;
+            ",
+        )
+        .await?
+        .take(0)?;
+
+    let employees = df.column("employee")?.str()?;
+    let payroll_ids = df.column("payroll_id")?.str()?;
+    let reported_roles = df.column("role")?.str()?;
+
+    let mut card_ids: Vec<String> = Vec::with_capacity(df.height());
+    let mut eids: Vec<i32> = Vec::with_capacity(df.height());
+    let mut roles: Vec<String> = Vec::with_capacity(df.height());
+    let mut point_weights: Vec<f32> = Vec::with_capacity(df.height());
+    let mut hourly_wages: Vec<Option<f32>> = Vec::with_capacity(df.height());
+    let mut unmatched: Vec<UnmatchedEmployee> = Vec::new();
+    let mut newly_confirmed: Vec<(String, i32)> = Vec::new();
+
+    for ((employee, payroll_id), reported_role) in
+        employees.into_iter().zip(payroll_ids).zip(reported_roles)
+    {
+        let employee = employee.unwrap_or_default();
+        let payroll_id = payroll_id.unwrap_or_default().trim();
+
+        let member = match staff.iter().find(|member| {
+            !payroll_id.is_empty() && member.payroll_ids.iter().any(|id| id == payroll_id)
+        }) {
+            Some(member) => Some(member),
+            None => match confirmed.get(&match_key(employee, payroll_id)) {
+                Some(eid) => {
+                    let member =
+                        staff
+                            .iter()
+                            .find(|member| member.eid == *eid)
+                            .ok_or_else(|| {
+                                format!("Confirmed match for {} is not a staff member", employee)
+                            })?;
+                    if !payroll_id.is_empty() {
+                        newly_confirmed.push((payroll_id.to_string(), member.eid));
+                    }
+                    Some(member)
+                }
+                None => None,
+            },
+        };
+
+        match member {
+            Some(member) if member.card_id.trim().is_empty() => {
+                return Err(format!("Staff member not a cardholder: {}", member.name).into());
+            }
+            Some(member) => {
+                card_ids.push(member.card_id.clone());
+                eids.push(member.eid);
+                roles.push(member.role_for(reported_role.unwrap_or_default()));
+                point_weights.push(member.point_weight);
+                hourly_wages.push(member.hourly_wage);
+            }
+            None => unmatched.push(UnmatchedEmployee {
+                key: match_key(employee, payroll_id),
+                employee: employee.to_string(),
+                payroll_id: payroll_id.to_string(),
+                candidates: candidates(employee, &staff),
+            }),
+        }
+    }
+
+    if !unmatched.is_empty() {
+        return Err(Box::new(StaffMatchError { unmatched }));
+    }
+
+    let mut seen = HashSet::new();
+    if !eids.iter().all(|eid| seen.insert(*eid)) {
+        return Err("Timekeepers not unique".into());
+    }
+
+    remember_payroll_ids(newly_confirmed).await?;
+
+    let mut df = df;
+    let reported_role = df.column("role")?.clone().with_name("reported_role");
+    df.with_column(Series::new("role", roles))?;
+    df.hstack_mut(&[
+        reported_role,
+        Series::new("card_id", card_ids),
+        Series::new("eid", eids),
+        Series::new("point_weight", point_weights),
+        Series::new("hourly_wage", hourly_wages),
+    ])?;
+    Ok(df)
+}
+
+/// Key a manager confirms a match under: the payroll ID, or the employee
+/// name when the report row has none.
+pub fn match_key(employee: &str, payroll_id: &str) -> String {
+    match payroll_id.trim() {
+        "" => employee.trim().to_string(),
+        payroll_id => payroll_id.to_string(),
+    }
+}
+
+fn candidates(employee: &str, staff: &[StaffIdentity]) -> Vec<MatchCandidate> {
+    let mut candidates: Vec<MatchCandidate> = staff
+        .iter()
+        .filter(|member| member.active)
+        .map(|member| MatchCandidate {
+            eid: member.eid,
+            name: member.name.clone(),
+            score: name_similarity(employee, &member.name),
+        })
+        .filter(|candidate| candidate.score >= FUZZY_MATCH_THRESHOLD)
+        .collect();
+
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+    candidates.truncate(MAX_CANDIDATES);
+    candidates
+}
+
+/// Similarity of two names, insensitive to case, punctuation and word order
+/// so that "Smith, Jon" still lines up with "Jon Smith".
+fn name_similarity(a: &str, b: &str) -> f64 {
+    fn tokens(name: &str) -> Vec<String> {
+        let mut tokens: Vec<String> = name
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|token| !token.is_empty())
+            .map(|token| token.to_lowercase())
+            .collect();
+        tokens.sort();
+        tokens
+    }
+
+    let (a, b) = (tokens(a), tokens(b));
+    f64::max(
+        strsim::jaro_winkler(&a.join(" "), &b.join(" ")),
+        strsim::jaro_winkler(&a.concat(), &b.concat()),
+    )
+}
+
+async fn remember_payroll_ids(confirmed: Vec<(String, i32)>) -> Result<(), Box<dyn Error>> {
+    let results = join_all(confirmed.into_iter().map(|(payroll_id, eid)| async move {
+        DB.query(
+            "
+            UPDATE type::thing('staff', $eid) SET
+                payroll_ids = array::union(payroll_ids ?? [], [$payroll_id]),
+                modified = $now;
+            ",
+        )
+        .bind(("eid", eid))
+        .bind(("payroll_id", payroll_id))
+        .bind(("now", Utc::now()))
+        .await?
+        .check()
+    }))
+    .await;
+
+    for result in results {
+        result?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct StaffIdentity {
+    eid: i32,
+    name: String,
+    card_id: String,
+    payroll_ids: Vec<String>,
+    active: bool,
+    primary_role: Option<Role>,
+    secondary_roles: Vec<Role>,
+    hourly_wage: Option<f32>,
+    point_weight: f32,
+}
+
+impl StaffIdentity {
+    /// Role to pay a shift the labor report gives as `reported` in.
+    fn role_for(&self, reported: &str) -> String {
+        match self.primary_role {
+            Some(primary)
+                if primary.as_str() != reported
+                    && !self
+                        .secondary_roles
+                        .iter()
+                        .any(|role| role.as_str() == reported) =>
+            {
+                primary.as_str().to_string()
+            }
+            _ => reported.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MatchCandidate {
+    pub eid: i32,
+    pub name: String,
+    pub score: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct UnmatchedEmployee {
+    /// Key to send the confirmed `eid` under in `confirmedMatches`.
+    pub key: String,
+    pub employee: String,
+    pub payroll_id: String,
+    pub candidates: Vec<MatchCandidate>,
+}
+
+/// Labor report rows that need a manager to confirm which staff member
+/// they belong to before the night can be calculated.
+#[derive(Debug, Serialize)]
+pub struct StaffMatchError {
+    pub unmatched: Vec<UnmatchedEmployee>,
+}
+
+impl fmt::Display for StaffMatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} labor report employee(s) need a confirmed staff match",
+            self.unmatched.len()
+        )
+    }
+}
+
+impl Error for StaffMatchError {}
+
+#[cfg(test)]
+mod tests;
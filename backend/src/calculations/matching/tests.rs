@@ -0,0 +1,67 @@
+use super::*;
+
+fn member(eid: i32, name: &str, active: bool) -> StaffIdentity {
+    StaffIdentity {
+        eid,
+        name: name.to_string(),
+        card_id: format!("9000{}", eid),
+        payroll_ids: Vec::new(),
+        active,
+        primary_role: None,
+        secondary_roles: Vec::new(),
+        hourly_wage: None,
+        point_weight: 1.0,
+    }
+}
+
+fn eids(candidates: &[MatchCandidate]) -> Vec<i32> {
+    candidates.iter().map(|candidate| candidate.eid).collect()
+}
+
+#[test]
+fn match_key_is_the_payroll_id_or_else_the_name() {
+    assert_eq!(match_key("Jon Smith", " P204 "), "P204");
+    assert_eq!(match_key(" Jon Smith ", ""), "Jon Smith");
+    assert_eq!(match_key("Jon Smith", "   "), "Jon Smith");
+}
+
+#[test]
+fn word_order_case_and_punctuation_do_not_matter() {
+    assert_eq!(name_similarity("Smith, Jon", "Jon Smith"), 1.0);
+    assert_eq!(name_similarity("JON  smith.", "Jon Smith"), 1.0);
+    assert!(name_similarity("Mary-Kate Olsen", "Marykate Olsen") >= FUZZY_MATCH_THRESHOLD);
+    assert!(name_similarity("Jon Smith", "Jon Smyth") >= FUZZY_MATCH_THRESHOLD);
+    assert!(name_similarity("Jon Smith", "Ana Rivera") < FUZZY_MATCH_THRESHOLD);
+}
+
+#[test]
+fn candidates_are_close_active_names_best_first() {
+    let staff = vec![
+        member(1, "Ana Rivera", true),
+        member(2, "Jon Smyth", true),
+        member(3, "Smith, Jon", true),
+        member(4, "Jon Smith", false),
+    ];
+
+    let candidates = candidates("Jon Smith", &staff);
+    assert_eq!(eids(&candidates), vec![3, 2]);
+    assert!(candidates[0].score > candidates[1].score);
+}
+
+#[test]
+fn candidates_stop_at_the_maximum() {
+    let staff = vec![
+        member(1, "Jon Smith", true),
+        member(2, "Jon Smithe", true),
+        member(3, "Jon Smyth", true),
+        member(4, "John Smith", true),
+        member(5, "Jon Smithson", true),
+    ];
+
+    let candidates = candidates("Jon Smith", &staff);
+    assert_eq!(candidates.len(), MAX_CANDIDATES);
+    assert_eq!(candidates[0].eid, 1);
+    assert!(candidates
+        .windows(2)
+        .all(|pair| pair[0].score >= pair[1].score));
+}
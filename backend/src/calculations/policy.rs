@@ -0,0 +1,42 @@
+use serde::Serialize;
+
+/// Rules the tip pool has been split under, oldest first. A change to how
+/// tips are split adds a version here rather than editing one, so stored
+/// nights can be recalculated under any of them.
+pub const POLICIES: &[TipPolicy] = &[
+    TipPolicy {
+        version: 1,
+        description: "2.5% of sales tipped out to stewards",
+        steward_tip_out: 0.025,
+        short_staffed_rules: false,
+    },
+    TipPolicy {
+        version: 2,
+        description: "2.5% steward tip-out, waived on nights without stewards; \
+            the pool goes to the stewards on nights without servers or bartenders",
+        steward_tip_out: 0.025,
+        short_staffed_rules: true,
+    },
+];
+
+#[derive(Debug, Serialize, Clone, Copy)]
+pub struct TipPolicy {
+    pub version: u32,
+    pub description: &'static str,
+    /// Share of each server's and bartender's sales paid out to the
+    /// stewards.
+    pub steward_tip_out: f32,
+    /// Waives the tip-out when no stewards worked and passes the pool to
+    /// the stewards when no servers or bartenders did. Without it that
+    /// money goes to no one.
+    pub short_staffed_rules: bool,
+}
+
+/// The policy new calculations use.
+pub fn current() -> &'static TipPolicy {
+    POLICIES.last().expect("No tip policies defined")
+}
+
+pub fn find(version: u32) -> Option<&'static TipPolicy> {
+    POLICIES.iter().find(|policy| policy.version == version)
+}
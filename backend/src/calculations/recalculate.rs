@@ -0,0 +1,264 @@
+use chrono::NaiveDate;
+use polars::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+
+use crate::downloads::Export;
+use crate::DB;
+
+use super::{
+    compute, generate, matching, policy, transform, LaborReportUpload, LaborRow, NightInputs,
+    Summary, TipOverride,
+};
+
+/// Differences under half a cent are rounding, not a payout.
+const MIN_ADJUSTMENT: f32 = 0.005;
+
+/// Corrections to a stored night. Inputs left out keep their stored value.
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct NightAmendment {
+    pub total_sales: Option<f32>,
+    pub go_tab_tips: Option<f32>,
+    pub cash_tips: Option<f32>,
+    /// Corrected hours worked, keyed by `eid`.
+    pub hours: HashMap<i32, f32>,
+    /// Replaces the overrides the night was calculated with, when given.
+    pub overrides: Option<Vec<TipOverride>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Recalculation {
+    pub before: NightInputs,
+    pub after: NightInputs,
+    pub summary: Summary,
+    pub adjustments: Vec<TipAdjustment>,
+    /// Calculation sheet for the corrected night.
+    #[serde(skip)]
+    pub calculations: Export,
+    /// Payout file covering only employees who are owed more; it is `None`
+    /// when nobody is.
+    #[serde(skip)]
+    pub adjustment_template: Option<Export>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TipAdjustment {
+    pub eid: i32,
+    pub name: String,
+    pub hours_before: f32,
+    pub hours_after: f32,
+    pub net_tips_before: f32,
+    pub net_tips_after: f32,
+    /// Amount still owed to the employee; negative when they were overpaid.
+    pub difference: f32,
+}
+
+/// Calculates the night on `date` again from its stored labor rows with
+/// `amendment` applied, overwriting its tips, and returns what changed for
+/// each employee. Returns `None` when no night is stored for `date`.
+pub async fn recalculate(
+    date: NaiveDate,
+    amendment: NightAmendment,
+) -> Result<Option<Recalculation>, Box<dyn Error>> {
+    let Some(before) = load_night(date).await? else {
+        return Ok(None);
+    };
+    if before.labor_rows.is_empty() {
+        return Err(format!(
+            "The labor rows for {} were not kept; upload the labor report again instead",
+            date
+        )
+        .into());
+    }
+
+    for eid in amendment.hours.keys() {
+        if !before.labor_rows.iter().any(|row| row.eid == *eid) {
+            return Err(format!("No one with eid {} worked on {}", eid, date).into());
+        }
+    }
+    if let Some((eid, _)) = amendment.hours.iter().find(|(_, hours)| **hours < 0.0) {
+        return Err(format!("Hours for eid {} cannot be negative", eid).into());
+    }
+
+    let previous_tips = load_net_tips(date).await?;
+
+    let labor_rows: Vec<LaborRow> = before
+        .labor_rows
+        .iter()
+        .cloned()
+        .map(|mut row| {
+            if let Some(hours) = amendment.hours.get(&row.eid) {
+                row.duration = *hours;
+            }
+            row
+        })
+        .collect();
+
+    let upload = LaborReportUpload {
+        date,
+        total_sales: amendment.total_sales.unwrap_or(before.total_sales),
+        go_tab_tips: amendment.go_tab_tips.unwrap_or(before.go_tab_tips),
+        cash_tips: amendment.cash_tips.unwrap_or(before.cash_tips),
+        confirmed_matches: confirmed_matches(&labor_rows),
+        overrides: amendment
+            .overrides
+            .unwrap_or_else(|| before.overrides.clone()),
+    };
+
+    let policy = policy::current();
+    let df = transform::from_labor_rows(&labor_rows)?;
+    let df = matching::match_staff(df, &upload.confirmed_matches).await?;
+    let (df, warnings) = compute::compute(upload.clone(), df, policy)?;
+    let (calculations, _, mut summary, tips) = generate::generate(df, &upload, policy).await?;
+    summary.warnings = warnings;
+
+    let hours_before: HashMap<i32, f32> = before
+        .labor_rows
+        .iter()
+        .map(|row| (row.eid, row.duration))
+        .collect();
+
+    let mut adjustments: BTreeMap<i32, TipAdjustment> = BTreeMap::new();
+    for tip in tips {
+        let net_tips_before = previous_tips.get(&tip.eid).copied().unwrap_or_default();
+        adjustments.insert(
+            tip.eid,
+            TipAdjustment {
+                eid: tip.eid,
+                name: tip.employee,
+                hours_before: hours_before.get(&tip.eid).copied().unwrap_or_default(),
+                hours_after: tip.duration,
+                net_tips_before,
+                net_tips_after: tip.net_tips,
+                difference: tip.net_tips - net_tips_before,
+            },
+        );
+    }
+    let adjustments: Vec<TipAdjustment> = adjustments.into_values().collect();
+
+    let adjustment_template = adjustment_template(date, &adjustments).await?;
+
+    Ok(Some(Recalculation {
+        after: NightInputs {
+            date,
+            total_sales: upload.total_sales,
+            go_tab_tips: upload.go_tab_tips,
+            cash_tips: upload.cash_tips,
+            labor_rows,
+            overrides: upload.overrides,
+            policy_version: Some(policy.version),
+        },
+        before,
+        summary,
+        adjustments,
+        calculations,
+        adjustment_template,
+    }))
+}
+
+pub async fn load_night(date: NaiveDate) -> Result<Option<NightInputs>, Box<dyn Error>> {
+    let night: Option<NightInputs> = DB
+        .query(
+            "
+            SELECT date, total_sales, go_tab_tips, cash_tips, labor_rows ?? [] AS labor_rows,
+                overrides ?? [] AS overrides, policy_version
+            FROM type::thing('nights', $date);
+            ",
+        )
+        .bind(("date", date))
+        .await?
+        .take(0)?;
+    Ok(night)
+}
+
+/// Matches for every stored row, which were all matched when the night was
+/// first calculated.
+pub(super) fn confirmed_matches(labor_rows: &[LaborRow]) -> HashMap<String, i32> {
+    labor_rows
+        .iter()
+        .map(|row| (matching::match_key(&row.employee, &row.payroll_id), row.eid))
+        .collect()
+}
+
+async fn load_net_tips(date: NaiveDate) -> Result<HashMap<i32, f32>, Box<dyn Error>> {
+    #[derive(Deserialize)]
+    struct NetTips {
+        eid: i32,
+        net_tips: f32,
+    }
+
+    let tips: Vec<NetTips> = DB
+        .query(
+            "
+            SELECT eid, net_tips FROM tips WHERE date = $date;
+            ",
+        )
+        .bind(("date", date))
+        .await?
+        .take(0)?;
+
+    Ok(tips
+        .into_iter()
+        .map(|tip| (tip.eid, tip.net_tips))
+        .collect())
+}
+
+/// RapidPay upload for the amounts still owed after a recalculation.
+/// Overpayments cannot be taken back through the card, so they are left
+/// for the manager to settle.
+async fn adjustment_template(
+    date: NaiveDate,
+    adjustments: &[TipAdjustment],
+) -> Result<Option<Export>, Box<dyn Error>> {
+    let owed: Vec<&TipAdjustment> = adjustments
+        .iter()
+        .filter(|adjustment| adjustment.difference >= MIN_ADJUSTMENT)
+        .collect();
+    if owed.is_empty() {
+        return Ok(None);
+    }
+
+    #[derive(Deserialize)]
+    struct CardId {
+        eid: i32,
+        card_id: String,
+    }
+
+    let card_ids: Vec<CardId> = DB
+        .query(
+            "
+            SELECT eid, card_id FROM staff WHERE eid IN $eids;
+            ",
+        )
+        .bind((
+            "eids",
+            owed.iter()
+                .map(|adjustment| adjustment.eid)
+                .collect::<Vec<_>>(),
+        ))
+        .await?
+        .take(0)?;
+    let card_ids: HashMap<i32, String> = card_ids
+        .into_iter()
+        .map(|card| (card.eid, card.card_id))
+        .collect();
+
+    let df = df!(
+        "card_id" => owed
+            .iter()
+            .map(|adjustment| card_ids.get(&adjustment.eid).cloned().unwrap_or_default())
+            .collect::<Vec<_>>(),
+        "net_tips" => owed.iter().map(|adjustment| adjustment.difference).collect::<Vec<_>>(),
+    )?;
+
+    let mut template = generate::generate_upload_template(df, format!("{} adjustment", date))?;
+    let mut csv = Vec::new();
+    CsvWriter::new(&mut csv).finish(&mut template)?;
+
+    Ok(Some(Export::csv(
+        format!("{}_rapidpay_adjustment_template.csv", date),
+        csv,
+    )))
+}
@@ -0,0 +1,342 @@
+//! Labor reports in `tests/fixtures` run through the whole calculation and
+//! compared with the outputs in `tests/golden`. Run with `UPDATE_GOLDEN=1`
+//! to rewrite the golden files after an intended change, and review the
+//! diff before committing it.
+
+use chrono::NaiveDate;
+use polars::prelude::*;
+use proptest::prelude::*;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+
+use super::{
+    compute, policy, read_csv, recalculate_range, BatchRecalculation, LaborReportUpload,
+    StaffMatchError,
+};
+use crate::testing::block_on;
+use crate::DB;
+
+/// Calculates `fixture` as the night of `date` and checks the outcome
+/// against `tests/golden/{fixture}.json` and, when it succeeds, the payout
+/// file against `tests/golden/{fixture}_rapidpay_upload_template.csv`.
+fn check_golden(fixture: &str, date: &str, total_sales: f32, go_tab_tips: f32, cash_tips: f32) {
+    let bytes = std::fs::read(
+        fixtures_dir()
+            .join("fixtures")
+            .join(format!("{}.csv", fixture)),
+    )
+    .expect("Could not read fixture");
+    let night = LaborReportUpload {
+        date: NaiveDate::parse_from_str(date, "%Y-%m-%d").expect("Could not parse date"),
+        total_sales,
+        go_tab_tips,
+        cash_tips,
+        ..Default::default()
+    };
+
+    let outcome = block_on(async {
+        read_csv(night, &bytes)
+            .await
+            .map_err(|err| match err.downcast_ref::<StaffMatchError>() {
+                Some(err) => json!({ "error": err.to_string(), "unmatched": err.unmatched }),
+                None => json!({ "error": err.to_string() }),
+            })
+    });
+
+    let outcome = match outcome {
+        Ok((_, template, summary, tips)) => {
+            assert_golden(
+                &format!("{}_rapidpay_upload_template.csv", fixture),
+                &String::from_utf8(template.bytes).expect("Template is not UTF-8"),
+            );
+            json!({ "summary": summary, "tips": tips })
+        }
+        Err(err) => err,
+    };
+    assert_golden(
+        &format!("{}.json", fixture),
+        &format!(
+            "{}\n",
+            serde_json::to_string_pretty(&rounded(outcome)).expect("Could not serialize")
+        ),
+    );
+}
+
+fn fixtures_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+fn assert_golden(name: &str, actual: &str) {
+    let path = fixtures_dir().join("golden").join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, actual).expect("Could not write golden file");
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&path)
+        .unwrap_or_else(|_| panic!("Missing {}; run with UPDATE_GOLDEN=1", path.display()));
+    assert_eq!(expected, actual, "{} differs", name);
+}
+
+/// Rounds every number to four places so golden files don't change with
+/// the last bits of an `f32`.
+fn rounded(value: Value) -> Value {
+    match value {
+        Value::Number(number) if number.is_f64() => {
+            let number = number.as_f64().unwrap_or_default();
+            json!((number * 10_000.0).round() / 10_000.0)
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(rounded).collect()),
+        Value::Object(values) => Value::Object(
+            values
+                .into_iter()
+                .map(|(key, value)| (key, rounded(value)))
+                .collect(),
+        ),
+        value => value,
+    }
+}
+
+#[test]
+fn normal_night() {
+    check_golden("normal_night", "2024-03-01", 8000.0, 1200.0, 300.0);
+}
+
+#[test]
+fn no_stewards() {
+    check_golden("no_stewards", "2024-03-02", 6000.0, 900.0, 200.0);
+}
+
+#[test]
+fn single_employee() {
+    check_golden("single_employee", "2024-03-03", 1500.0, 250.0, 50.0);
+}
+
+#[test]
+fn zero_tips() {
+    check_golden("zero_tips", "2024-03-04", 8000.0, 0.0, 0.0);
+}
+
+#[test]
+fn duplicate_timekeeper() {
+    check_golden("duplicate_timekeeper", "2024-03-05", 3000.0, 400.0, 100.0);
+}
+
+#[test]
+fn unknown_cardholder() {
+    check_golden("unknown_cardholder", "2024-03-06", 4000.0, 500.0, 100.0);
+}
+
+#[test]
+fn stewards_only() {
+    check_golden("stewards_only", "2024-03-08", 2000.0, 300.0, 60.0);
+}
+
+#[test]
+fn missing_breaks() {
+    check_golden("missing_breaks", "2024-03-07", 5000.0, 800.0, 150.0);
+}
+
+/// A matched labor report with `tipped` servers and `stewards` stewards,
+/// each given as `(hours, wages)`.
+fn labor_report(tipped: &[(f32, f32)], stewards: &[(f32, f32)]) -> DataFrame {
+    let shifts: Vec<(&str, f32, f32)> = tipped
+        .iter()
+        .map(|(hours, wages)| ("Server", *hours, *wages))
+        .chain(
+            stewards
+                .iter()
+                .map(|(hours, wages)| ("Steward", *hours, *wages)),
+        )
+        .collect();
+    let eids: Vec<i32> = (1..=shifts.len() as i32).collect();
+
+    df!(
+        "employee" => eids.iter().map(|eid| format!("Employee {}", eid)).collect::<Vec<_>>(),
+        "payroll_id" => eids.iter().map(|eid| format!("P{}", eid)).collect::<Vec<_>>(),
+        "role" => shifts.iter().map(|shift| shift.0).collect::<Vec<_>>(),
+        "total_pay" => shifts.iter().map(|shift| shift.2).collect::<Vec<_>>(),
+        "duration" => shifts.iter().map(|shift| shift.1).collect::<Vec<_>>(),
+        "reported_role" => shifts.iter().map(|shift| shift.0).collect::<Vec<_>>(),
+        "card_id" => eids.iter().map(|eid| format!("9000{}", eid)).collect::<Vec<_>>(),
+        "eid" => eids.clone(),
+        "point_weight" => eids.iter().map(|_| 1.0f32).collect::<Vec<_>>(),
+        "hourly_wage" => eids.iter().map(|_| None::<f32>).collect::<Vec<_>>(),
+    )
+    .expect("Could not build labor report")
+}
+
+fn sum_where(df: &DataFrame, column: &str, role: Option<&str>) -> f32 {
+    let roles = df.column("role").unwrap().str().unwrap().clone();
+    let values = df
+        .column(column)
+        .unwrap()
+        .cast(&DataType::Float32)
+        .unwrap()
+        .f32()
+        .unwrap()
+        .clone();
+    roles
+        .into_iter()
+        .zip(&values)
+        .filter(|(row_role, _)| role.is_none() || *row_role == role)
+        .map(|(_, value)| value.unwrap_or_default())
+        .sum()
+}
+
+/// `column` of the row for `eid`.
+fn value_for(df: &DataFrame, column: &str, eid: i32) -> f32 {
+    let eids = df.column("eid").unwrap().i32().unwrap().clone();
+    let values = df
+        .column(column)
+        .unwrap()
+        .cast(&DataType::Float32)
+        .unwrap()
+        .f32()
+        .unwrap()
+        .clone();
+    let value = eids
+        .into_iter()
+        .zip(&values)
+        .find(|(row_eid, _)| *row_eid == Some(eid))
+        .and_then(|(_, value)| value);
+    value.expect("No row for eid")
+}
+
+#[test]
+fn staff_record_terms_are_used() {
+    let mut df = labor_report(&[(5.0, 50.0), (5.0, 50.0)], &[]);
+    // Employee 1 was on the report as a steward but is a server on record
+    df.with_column(Series::new("reported_role", ["Steward", "Server"]))
+        .unwrap();
+    df.with_column(Series::new("point_weight", [2.0f32, 1.0]))
+        .unwrap();
+    df.with_column(Series::new("hourly_wage", [Some(15.0f32), None]))
+        .unwrap();
+    let night = LaborReportUpload {
+        go_tab_tips: 300.0,
+        ..Default::default()
+    };
+
+    let (df, warnings) = compute::compute(night, df, policy::current()).unwrap();
+
+    assert!((value_for(&df, "net_tips", 1) - 200.0).abs() < 0.01);
+    assert!((value_for(&df, "net_tips", 2) - 100.0).abs() < 0.01);
+    assert!((value_for(&df, "total_pay_for_night", 1) - 275.0).abs() < 0.01);
+    assert!((value_for(&df, "total_pay_for_night", 2) - 150.0).abs() < 0.01);
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].starts_with("Employee 1 was a Steward"));
+}
+
+fn shift() -> impl Strategy<Value = (f32, f32)> {
+    (0.5f32..12.0, 0.0f32..200.0)
+}
+
+proptest! {
+    #[test]
+    fn net_tips_add_up_to_total_tips(
+        tipped in prop::collection::vec(shift(), 0..8),
+        stewards in prop::collection::vec(shift(), 0..4),
+        total_sales in 0.0f32..50_000.0,
+        go_tab_tips in 0.0f32..5_000.0,
+        cash_tips in 0.0f32..1_000.0,
+    ) {
+        let night = LaborReportUpload {
+            total_sales,
+            go_tab_tips,
+            cash_tips,
+            ..Default::default()
+        };
+        prop_assume!(!tipped.is_empty() || !stewards.is_empty());
+        let (df, _) = compute::compute(night, labor_report(&tipped, &stewards), policy::current()).unwrap();
+
+        let total_tips = go_tab_tips + cash_tips;
+        let net_tips = sum_where(&df, "net_tips", None);
+        prop_assert!(
+            (net_tips - total_tips).abs() <= 0.01 + total_tips * 1e-4,
+            "net tips {} != total tips {}", net_tips, total_tips
+        );
+    }
+
+    #[test]
+    fn stewards_receive_the_whole_tip_out(
+        tipped in prop::collection::vec(shift(), 1..8),
+        stewards in prop::collection::vec(shift(), 1..4),
+        total_sales in 0.0f32..50_000.0,
+        go_tab_tips in 0.0f32..5_000.0,
+    ) {
+        let night = LaborReportUpload {
+            total_sales,
+            go_tab_tips,
+            ..Default::default()
+        };
+        let (df, _) = compute::compute(night, labor_report(&tipped, &stewards), policy::current()).unwrap();
+
+        let tip_out = sum_where(&df, "steward_tip_out", Some("Server"));
+        let steward_tips = sum_where(&df, "net_tips", Some("Steward"));
+        prop_assert!(
+            (steward_tips - tip_out).abs() <= 0.01 + tip_out * 1e-4,
+            "stewards got {} of a {} tip-out", steward_tips, tip_out
+        );
+    }
+}
+
+#[test]
+fn batch_recalculation_compares_and_archives() {
+    let date = NaiveDate::from_ymd_opt(2024, 4, 2).expect("Invalid date");
+    let bytes = std::fs::read(fixtures_dir().join("fixtures").join("no_stewards.csv"))
+        .expect("Could not read fixture");
+    let night = LaborReportUpload {
+        date,
+        total_sales: 6000.0,
+        go_tab_tips: 900.0,
+        cash_tips: 200.0,
+        ..Default::default()
+    };
+    let batch = |policy_version, commit| BatchRecalculation {
+        start: date,
+        end: date,
+        policy_version: Some(policy_version),
+        commit,
+    };
+
+    block_on(async {
+        read_csv(night, &bytes).await.expect("Could not calculate");
+
+        // Without short-staffed rules the tip-out is paid to no one
+        let report = recalculate_range(batch(1, false), "test", &|_, _| {})
+            .await
+            .expect("Could not recalculate");
+        assert!(!report.committed);
+        assert_eq!(report.nights.len(), 1);
+        assert_eq!(report.nights[0].policy_version_before, Some(2));
+        assert!((report.net_tips_before - 1100.0).abs() < 0.01);
+        assert!((report.net_tips_before - report.net_tips_after - 150.0).abs() < 0.01);
+
+        let report = recalculate_range(batch(2, true), "test", &|_, _| {})
+            .await
+            .expect("Could not recalculate");
+        assert!(report.committed);
+        assert!((report.net_tips_after - 1100.0).abs() < 0.01);
+
+        let archived: Vec<String> = DB
+            .query("SELECT VALUE <string> recalculation FROM tips_archive WHERE date = $date;")
+            .bind(("date", date))
+            .await
+            .expect("Could not load archived tips")
+            .take(0)
+            .expect("Could not load archived tips");
+        assert_eq!(archived.len(), report.nights[0].employees.len());
+        assert!(archived.iter().all(|id| Some(id) == report.id.as_ref()));
+
+        let versions: Vec<Option<u32>> = DB
+            .query("SELECT VALUE policy_version FROM tips WHERE date = $date;")
+            .bind(("date", date))
+            .await
+            .expect("Could not load tips")
+            .take(0)
+            .expect("Could not load tips");
+        assert_eq!(versions.len(), archived.len());
+        assert!(versions.iter().all(|version| *version == Some(2)));
+    });
+}
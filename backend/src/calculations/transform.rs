@@ -3,6 +3,8 @@ use polars::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 
+use super::LaborRow;
+
 #[derive(Debug, Deserialize, Serialize)]
 struct LaborReportRecord {
     #[serde(rename = "Employee")]
@@ -30,7 +32,11 @@ struct LaborReportRecord {
 pub fn transform(bytes: &[u8]) -> Result<DataFrame, Box<dyn Error>> {
     let file = std::str::from_utf8(bytes)?;
     let file = file.replace("\"Shifts\"\n", "");
-    let (file, _) = file.split_once("\"Breaks").expect("Could not split file");
+    // Reports without any breaks may leave the section out entirely
+    let file = match file.split_once("\"Breaks") {
+        Some((shifts, _)) => shifts,
+        None => file.as_str(),
+    };
     let reader = csv::Reader::from_reader(file.as_bytes());
 
     let df = convert_reader_to_df(reader)?;
@@ -81,3 +87,15 @@ fn transform_types(df: DataFrame) -> Result<DataFrame, PolarsError> {
         ])
         .collect()
 }
+
+/// Rebuilds the frame `transform` produces from labor rows stored with a
+/// night, so the night can be calculated again.
+pub fn from_labor_rows(rows: &[LaborRow]) -> Result<DataFrame, PolarsError> {
+    df!(
+        "employee" => rows.iter().map(|row| row.employee.clone()).collect::<Vec<_>>(),
+        "payroll_id" => rows.iter().map(|row| row.payroll_id.clone()).collect::<Vec<_>>(),
+        "role" => rows.iter().map(|row| row.role.clone()).collect::<Vec<_>>(),
+        "total_pay" => rows.iter().map(|row| row.total_pay).collect::<Vec<_>>(),
+        "duration" => rows.iter().map(|row| row.duration).collect::<Vec<_>>(),
+    )
+}
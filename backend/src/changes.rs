@@ -0,0 +1,127 @@
+use futures::StreamExt;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use surrealdb::{sql::Thing, Action};
+use tokio::sync::broadcast;
+
+use crate::DB;
+
+/// Tables the dashboard shows, and the topic a change to each is sent
+/// under. A calculation or recalculation writes its night to `nights`.
+const WATCHED: &[(&str, &str)] = &[
+    ("nights", "calculations"),
+    ("tips", "tips"),
+    ("staff", "staff"),
+    ("commissions", "commissions"),
+    ("wines", "wines"),
+];
+
+/// How long changes to a table are gathered into one notification, so a
+/// calculation writing a row per employee is sent once.
+const GATHER_MILLISECONDS: u64 = 250;
+/// How long to wait before watching a table again after losing its live
+/// query.
+const RETRY_SECONDS: u64 = 5;
+
+/// Every change to a watched table, for dashboards to refresh from.
+static CHANGES: Lazy<broadcast::Sender<Change>> = Lazy::new(|| broadcast::channel(64).0);
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Change {
+    pub topic: &'static str,
+    pub action: ChangeAction,
+    /// Ids of the records that changed, without the table name.
+    pub ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeAction {
+    Created,
+    Updated,
+    Deleted,
+}
+
+#[derive(Deserialize)]
+struct Changed {
+    id: Thing,
+}
+
+pub fn subscribe() -> broadcast::Receiver<Change> {
+    CHANGES.subscribe()
+}
+
+/// Follows every watched table with a SurrealDB live query for the life of
+/// the server. Against a SurrealDB server, changes other processes make are
+/// seen too. A `rocksdb://` or `mem://` database is embedded in this
+/// process, so only its own changes happen there; see [`crate::database`].
+pub async fn watch_tables() {
+    for &(table, topic) in WATCHED {
+        tokio::spawn(async move {
+            loop {
+                match watch(table, topic).await.map_err(|err| err.to_string()) {
+                    Ok(()) => tracing::warn!("Live query on {} ended", table),
+                    Err(err) => tracing::warn!("Could not watch {}: {}", table, err),
+                }
+                tokio::time::sleep(std::time::Duration::from_secs(RETRY_SECONDS)).await;
+            }
+        });
+    }
+}
+
+async fn watch(table: &'static str, topic: &'static str) -> Result<(), Box<dyn Error>> {
+    let mut stream = DB.select::<Vec<Changed>>(table).live().await?;
+    let gather = std::time::Duration::from_millis(GATHER_MILLISECONDS);
+
+    while let Some(first) = stream.next().await {
+        let first = first?;
+        let mut notifications = vec![(first.action, first.data.id)];
+        let window = tokio::time::sleep(gather);
+        tokio::pin!(window);
+        loop {
+            tokio::select! {
+                _ = &mut window => break,
+                next = stream.next() => match next {
+                    Some(notification) => {
+                        let notification = notification?;
+                        notifications.push((notification.action, notification.data.id));
+                    }
+                    None => break,
+                },
+            }
+        }
+
+        for change in gather_changes(topic, notifications) {
+            // Nobody listening is not an error
+            let _ = CHANGES.send(change);
+        }
+    }
+    Ok(())
+}
+
+/// One change per action, in the order the actions first happened.
+fn gather_changes(topic: &'static str, notifications: Vec<(Action, Thing)>) -> Vec<Change> {
+    let mut changes: Vec<Change> = Vec::new();
+    for (action, thing) in notifications {
+        let action = match action {
+            Action::Create => ChangeAction::Created,
+            Action::Update => ChangeAction::Updated,
+            Action::Delete => ChangeAction::Deleted,
+            _ => continue,
+        };
+        let id = thing.id.to_raw();
+        match changes.iter_mut().find(|change| change.action == action) {
+            Some(change) => change.ids.push(id),
+            None => changes.push(Change {
+                topic,
+                action,
+                ids: vec![id],
+            }),
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,45 @@
+use super::*;
+
+fn staff(eid: i64) -> Thing {
+    Thing::from(("staff", eid.to_string().as_str()))
+}
+
+#[test]
+fn changes_are_grouped_by_action_in_the_order_they_first_happened() {
+    let changes = gather_changes(
+        "staff",
+        vec![
+            (Action::Update, staff(101)),
+            (Action::Create, staff(106)),
+            (Action::Update, staff(102)),
+            (Action::Delete, staff(103)),
+            (Action::Create, staff(107)),
+        ],
+    );
+
+    let gathered: Vec<_> = changes
+        .iter()
+        .map(|change| (change.topic, change.action, change.ids.clone()))
+        .collect();
+    assert_eq!(
+        gathered,
+        vec![
+            (
+                "staff",
+                ChangeAction::Updated,
+                vec!["101".to_string(), "102".to_string()]
+            ),
+            (
+                "staff",
+                ChangeAction::Created,
+                vec!["106".to_string(), "107".to_string()]
+            ),
+            ("staff", ChangeAction::Deleted, vec!["103".to_string()]),
+        ]
+    );
+}
+
+#[test]
+fn nothing_is_sent_without_notifications() {
+    assert!(gather_changes("tips", Vec::new()).is_empty());
+}
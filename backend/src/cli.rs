@@ -0,0 +1,322 @@
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+use crate::backup;
+use crate::calculations::{self, BatchRecalculation, LaborReportUpload, StaffMatchError};
+use crate::exports::{self, ExportFilter};
+use crate::repo::AppState;
+use crate::routes::staff;
+use crate::DB;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "wine-list",
+    about = "Tip pool and wine list backend",
+    after_help = "Commands open the database in DATABASE_URL themselves. An embedded \
+                  rocksdb:// database can only be open in one process, so stop the server \
+                  before running other commands against it, or share a SurrealDB server \
+                  (ws://) between them."
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the HTTP server (the default).
+    Serve,
+    /// Apply any pending database migrations and exit.
+    Migrate,
+    /// Calculate a night's tips from a labor report, saving them like an
+    /// upload would and writing the calculation sheet and payout file.
+    Calculate {
+        /// Night being calculated, e.g. 2024-03-01.
+        #[arg(long)]
+        date: NaiveDate,
+        /// Total sales for the night.
+        #[arg(long)]
+        sales: f32,
+        /// GoTab tips for the night.
+        #[arg(long)]
+        gotab: f32,
+        /// Cash tips for the night.
+        #[arg(long)]
+        cash: f32,
+        /// JSON file of manager overrides, in the shape the upload form
+        /// sends.
+        #[arg(long)]
+        overrides: Option<PathBuf>,
+        /// Staff member for a labor report row that did not match, as
+        /// `KEY=EID`; repeat for each row.
+        #[arg(long = "confirm", value_parser = parse_confirmation)]
+        confirmed: Vec<(String, i32)>,
+        /// Directory to write the calculation sheet and payout file to.
+        #[arg(long, short, default_value = ".")]
+        output: PathBuf,
+        /// Labor report CSV exported from the timekeeping system.
+        report: PathBuf,
+    },
+    /// Calculate the stored nights in a date range again and compare each
+    /// employee's net tips with what is stored.
+    Recalculate {
+        #[arg(long)]
+        start: NaiveDate,
+        #[arg(long)]
+        end: NaiveDate,
+        /// Tip policy version to use; defaults to the current one.
+        #[arg(long = "policy")]
+        policy_version: Option<u32>,
+        /// Replace the stored tips, archiving the old ones.
+        #[arg(long)]
+        commit: bool,
+        /// File to write the per-employee comparison CSV to.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Create or update staff from a CSV of name, card_id, eid and an
+    /// optional payroll_id.
+    ImportStaff { file: PathBuf },
+    /// Write stored tips as CSV.
+    ExportTips {
+        #[arg(long)]
+        start: Option<NaiveDate>,
+        #[arg(long)]
+        end: Option<NaiveDate>,
+        #[arg(long)]
+        eid: Option<i32>,
+        #[arg(long)]
+        role: Option<String>,
+        /// File to write; defaults to standard output.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Write a backup archive of all business data.
+    Backup {
+        /// File to write; defaults to a timestamped file in the current
+        /// directory.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Replace the tables in a backup archive with its contents.
+    Restore {
+        /// Archive written by `backup` or `POST /admin/backup`.
+        archive: PathBuf,
+        /// Only print what the restore would change.
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+fn parse_confirmation(value: &str) -> Result<(String, i32), String> {
+    let (key, eid) = value
+        .rsplit_once('=')
+        .ok_or_else(|| format!("expected KEY=EID, got {}", value))?;
+    let eid = eid
+        .trim()
+        .parse()
+        .map_err(|_| format!("{} is not an eid", eid))?;
+    Ok((key.trim().to_string(), eid))
+}
+
+pub fn migrate(applied: Vec<String>) -> Result<(), Box<dyn Error>> {
+    if applied.is_empty() {
+        println!("The database is up to date");
+    }
+    for migration in applied {
+        println!("Applied {}", migration);
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn calculate(
+    date: NaiveDate,
+    sales: f32,
+    gotab: f32,
+    cash: f32,
+    overrides: Option<PathBuf>,
+    confirmed: Vec<(String, i32)>,
+    output: PathBuf,
+    report: PathBuf,
+) -> Result<(), Box<dyn Error>> {
+    let overrides = match overrides {
+        Some(path) => serde_json::from_slice(&tokio::fs::read(path).await?)
+            .map_err(|err| format!("Invalid overrides: {}", err))?,
+        None => Vec::new(),
+    };
+    let upload = LaborReportUpload {
+        date,
+        total_sales: sales,
+        go_tab_tips: gotab,
+        cash_tips: cash,
+        confirmed_matches: confirmed.into_iter().collect(),
+        overrides,
+    };
+
+    let bytes = tokio::fs::read(&report).await?;
+    let (data_csv, template_csv, summary, tips) = match calculations::read_csv(upload, &bytes).await
+    {
+        Ok(calculation) => calculation,
+        Err(err) => {
+            if let Some(match_error) = err.downcast_ref::<StaffMatchError>() {
+                print_unmatched(match_error);
+                return Err("Confirm who each employee above is with --confirm".into());
+            }
+            return Err(err);
+        }
+    };
+
+    for tip in &tips {
+        println!(
+            "{:<24} {:<10} {:>6.2} h {:>9.2}",
+            tip.employee, tip.role, tip.duration, tip.net_tips
+        );
+    }
+    println!("Total tips: {:.2}", summary.total_tips);
+    println!(
+        "Average net hourly pay: {:.2}",
+        summary.average_net_hourly_pay
+    );
+    for warning in &summary.warnings {
+        println!("Warning: {}", warning);
+    }
+
+    tokio::fs::create_dir_all(&output).await?;
+    for export in [data_csv, template_csv] {
+        let path = output.join(&export.filename);
+        tokio::fs::write(&path, &export.bytes).await?;
+        println!("Wrote {}", path.display());
+    }
+    Ok(())
+}
+
+fn print_unmatched(match_error: &StaffMatchError) {
+    eprintln!("{}:", match_error);
+    for unmatched in &match_error.unmatched {
+        let candidates: Vec<String> = unmatched
+            .candidates
+            .iter()
+            .map(|candidate| format!("{} ({})", candidate.name, candidate.eid))
+            .collect();
+        eprintln!(
+            "  {} ({}): --confirm '{}=EID'{}",
+            unmatched.employee,
+            unmatched.payroll_id,
+            unmatched.key,
+            match candidates.is_empty() {
+                true => String::new(),
+                false => format!(", perhaps {}", candidates.join(" or ")),
+            }
+        );
+    }
+}
+
+pub async fn recalculate(
+    batch: BatchRecalculation,
+    output: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let report = calculations::recalculate_range(batch, "cli", &|done, total| {
+        eprint!("\rRecalculating night {} of {}", done + 1, total);
+    })
+    .await?;
+    eprintln!();
+
+    for night in &report.nights {
+        println!(
+            "{}  policy {} -> {}  {:>10.2} -> {:>10.2}",
+            night.date,
+            night
+                .policy_version_before
+                .map_or("?".to_string(), |version| version.to_string()),
+            report.policy_version,
+            night.net_tips_before,
+            night.net_tips_after
+        );
+        for warning in &night.warnings {
+            println!("  Warning: {}", warning);
+        }
+    }
+    for night in &report.skipped {
+        println!("{}  skipped: {}", night.date, night.reason);
+    }
+    println!(
+        "Net tips {:.2} -> {:.2} over {} nights",
+        report.net_tips_before,
+        report.net_tips_after,
+        report.nights.len()
+    );
+
+    if let Some(path) = output {
+        tokio::fs::write(&path, report.csv()?.bytes).await?;
+        println!("Wrote {}", path.display());
+    }
+    match report.id {
+        Some(id) => println!("Committed as {}", id),
+        None => println!("Nothing was changed; use --commit to replace the stored tips"),
+    }
+    Ok(())
+}
+
+pub async fn import_staff(file: PathBuf) -> Result<(), Box<dyn Error>> {
+    let state = AppState::surreal(DB.clone());
+    let bytes = tokio::fs::read(&file).await?;
+    let imported = staff::read_import_csv(state.staff.as_ref(), &bytes).await?;
+    println!("Imported {} staff members", imported.len());
+    Ok(())
+}
+
+pub async fn export_tips(
+    filter: ExportFilter,
+    output: Option<PathBuf>,
+) -> Result<(), Box<dyn Error>> {
+    let state = AppState::surreal(DB.clone());
+    let fetch_page = |offset, limit| state.tips.export_page(filter.clone(), offset, limit);
+
+    let rows = match &output {
+        Some(path) => exports::write_csv(std::fs::File::create(path)?, fetch_page).await?,
+        None => exports::write_csv(std::io::stdout().lock(), fetch_page).await?,
+    };
+    if let Some(path) = output {
+        println!("Wrote {} rows to {}", rows, path.display());
+    }
+    Ok(())
+}
+
+pub async fn back_up(output: Option<PathBuf>) -> Result<(), Box<dyn Error>> {
+    let path = match output {
+        Some(path) => {
+            tokio::fs::write(&path, backup::export(&DB).await?).await?;
+            path
+        }
+        None => backup::write_backup(&DB, Path::new(".")).await?,
+    };
+    println!("Backed up to {}", path.display());
+    Ok(())
+}
+
+pub async fn restore(archive: PathBuf, dry_run: bool) -> Result<(), Box<dyn Error>> {
+    let archive = backup::parse(&tokio::fs::read(&archive).await?)?;
+    println!(
+        "Archive from {} (schema version {})",
+        archive.header.created, archive.header.schema_version
+    );
+
+    let diff = backup::restore(&DB, archive, dry_run).await?;
+    for table in &diff.tables {
+        println!(
+            "{:<16} {} added, {} changed, {} removed, {} unchanged",
+            table.table,
+            table.added.len(),
+            table.changed.len(),
+            table.removed.len(),
+            table.unchanged
+        );
+    }
+    if diff.dry_run {
+        println!("Dry run; nothing was changed");
+    }
+    Ok(())
+}
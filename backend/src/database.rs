@@ -0,0 +1,55 @@
+use std::error::Error;
+use surrealdb::{engine::any::Any, opt::auth::Root, Surreal};
+
+const DEFAULT_URL: &str = "ws://127.0.0.1:8000";
+
+/// Connects `db` to the database named by `DATABASE_URL`:
+///
+/// - `ws://host:port` (the default, `ws://127.0.0.1:8000`) or `wss://…`: a
+///   SurrealDB server, signed in as `DATABASE_USER` / `DATABASE_PASS`
+///   (`root` / `root` unless set).
+/// - `rocksdb://path/to/dir`: embedded in this process and stored in that
+///   directory, so no separate server is needed. Requires building with
+///   `--features rocksdb`. Only one process can hold the directory open, so
+///   CLI commands cannot run while the server is using it.
+/// - `mem://`: embedded and in memory only; nothing survives a restart.
+///
+/// Setting `DEMO_MODE` still works as it did before `DATABASE_URL` and means
+/// `mem://`, whatever `DATABASE_URL` says.
+pub async fn connect(db: &Surreal<Any>) -> Result<(), Box<dyn Error>> {
+    let url = match std::env::var_os("DEMO_MODE") {
+        Some(_) => "mem://".to_string(),
+        None => std::env::var("DATABASE_URL").unwrap_or_else(|_| DEFAULT_URL.to_string()),
+    };
+    if url.starts_with("rocksdb://") && !cfg!(feature = "rocksdb") {
+        return Err("DATABASE_URL is a rocksdb:// path but this build has no embedded database; rebuild with `--features rocksdb`".into());
+    }
+
+    tracing::info!("Connecting to database at {}", url);
+    if let Err(err) = db.connect(url.as_str()).await {
+        if url.starts_with("rocksdb://") {
+            return Err(format!(
+                "Could not open {}: {}. Is the server or another command using it?",
+                url, err
+            )
+            .into());
+        }
+        return Err(err.into());
+    }
+
+    if url.starts_with("ws://") || url.starts_with("wss://") {
+        let username = std::env::var("DATABASE_USER").unwrap_or_else(|_| "root".to_string());
+        let password = std::env::var("DATABASE_PASS").unwrap_or_else(|_| "root".to_string());
+
+        // Signin as a namespace, database, or root user
+        db.signin(Root {
+            username: &username,
+            password: &password,
+        })
+        .await?;
+    }
+
+    // Select a specific namespace / database
+    db.use_ns("test").use_db("test").await?;
+    Ok(())
+}
@@ -0,0 +1,227 @@
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::error::Error;
+use std::path::PathBuf;
+
+use crate::DB;
+
+const LINK_LIFETIME_MINUTES: i64 = 15;
+const CLEAN_UP_INTERVAL_MINUTES: u64 = 10;
+
+/// Key download links are signed with. Set `DOWNLOAD_SIGNING_KEY` to keep
+/// links valid across restarts; otherwise a key is generated at startup.
+static SIGNING_KEY: Lazy<Vec<u8>> = Lazy::new(|| match std::env::var("DOWNLOAD_SIGNING_KEY") {
+    Ok(key) if !key.is_empty() => key.into_bytes(),
+    _ => {
+        let mut key = vec![0u8; 32];
+        OsRng.fill_bytes(&mut key);
+        key
+    }
+});
+
+/// Private directory generated files are kept in until they are downloaded.
+static DOWNLOADS_DIR: Lazy<PathBuf> = Lazy::new(|| {
+    std::env::var("DOWNLOADS_DIR")
+        .unwrap_or_else(|_| "downloads".to_string())
+        .into()
+});
+
+/// A generated file waiting to be handed to the user.
+#[derive(Debug, Clone)]
+pub struct Export {
+    pub filename: String,
+    pub content_type: &'static str,
+    pub bytes: Vec<u8>,
+}
+
+impl Export {
+    pub fn csv(filename: String, bytes: Vec<u8>) -> Self {
+        Self {
+            filename,
+            content_type: "text/csv",
+            bytes,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Download {
+    filename: String,
+    content_type: String,
+    owner: String,
+    expires: DateTime<Utc>,
+    created: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct DownloadLink {
+    pub filename: String,
+    pub url: String,
+    pub expires: DateTime<Utc>,
+}
+
+/// Saves `export` for `owner` and returns a signed, single-use link to it.
+pub async fn store(export: Export, owner: &str) -> Result<DownloadLink, Box<dyn Error>> {
+    let mut id = [0u8; 16];
+    OsRng.fill_bytes(&mut id);
+    let id = hex::encode(id);
+    let expires = Utc::now() + Duration::minutes(LINK_LIFETIME_MINUTES);
+
+    tokio::fs::create_dir_all(DOWNLOADS_DIR.as_path()).await?;
+    tokio::fs::write(DOWNLOADS_DIR.join(&id), &export.bytes).await?;
+
+    let _: Option<Download> = DB
+        .create(("downloads", id.as_str()))
+        .content(Download {
+            filename: export.filename.clone(),
+            content_type: export.content_type.to_string(),
+            owner: owner.to_string(),
+            expires,
+            created: Utc::now(),
+        })
+        .await?;
+
+    Ok(DownloadLink {
+        url: format!(
+            "/downloads/{}?expires={}&signature={}",
+            id,
+            expires.timestamp(),
+            signature(&id, owner, expires.timestamp())
+        ),
+        filename: export.filename,
+        expires,
+    })
+}
+
+pub async fn store_all(
+    exports: Vec<Export>,
+    owner: &str,
+) -> Result<Vec<DownloadLink>, Box<dyn Error>> {
+    let mut links = Vec::with_capacity(exports.len());
+    for export in exports {
+        links.push(store(export, owner).await?);
+    }
+    Ok(links)
+}
+
+/// Hands out the file behind a link exactly once, to the user it was made
+/// for, if its signature is valid and it has not expired. The signature
+/// covers the owner, so it only verifies for `username`'s own links.
+pub async fn redeem(
+    id: &str,
+    expires: i64,
+    signature_hex: &str,
+    username: &str,
+) -> Result<Option<Export>, Box<dyn Error>> {
+    if expires < Utc::now().timestamp() || !verify_signature(id, username, expires, signature_hex) {
+        return Ok(None);
+    }
+
+    // Deleting the record claims the download, so a second request with the
+    // same link finds nothing.
+    let claimed: Vec<Download> = DB
+        .query(
+            "
+            DELETE type::thing('downloads', $id) WHERE owner = $owner AND expires > $now RETURN BEFORE;
+            ",
+        )
+        .bind(("id", id))
+        .bind(("owner", username))
+        .bind(("now", Utc::now()))
+        .await?
+        .take(0)?;
+
+    let Some(download) = claimed.into_iter().next() else {
+        return Ok(None);
+    };
+
+    let path = DOWNLOADS_DIR.join(id);
+    let bytes = tokio::fs::read(&path).await?;
+    tokio::fs::remove_file(&path).await?;
+
+    Ok(Some(Export {
+        filename: download.filename,
+        content_type: match download.content_type.as_str() {
+            "text/csv" => "text/csv",
+            "text/html" => "text/html",
+            "application/pdf" => "application/pdf",
+            "application/x-ndjson" => "application/x-ndjson",
+            _ => "application/octet-stream",
+        },
+        bytes,
+    }))
+}
+
+/// Removes expired downloads and any stray files left in the downloads
+/// directory, every few minutes for the life of the server.
+pub async fn clean_up_periodically() {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+        CLEAN_UP_INTERVAL_MINUTES * 60,
+    ));
+
+    loop {
+        interval.tick().await;
+        if let Err(err) = clean_up().await {
+            tracing::warn!("Could not clean up downloads: {}", err);
+        }
+    }
+}
+
+async fn clean_up() -> Result<(), Box<dyn Error>> {
+    let expired: Vec<String> = DB
+        .query(
+            "
+            SELECT VALUE meta::id(id) FROM downloads WHERE expires <= $now;
+            DELETE downloads WHERE expires <= $now;
+            ",
+        )
+        .bind(("now", Utc::now()))
+        .await?
+        .take(0)?;
+
+    for id in expired {
+        let _ = tokio::fs::remove_file(DOWNLOADS_DIR.join(id)).await;
+    }
+
+    let Ok(mut entries) = tokio::fs::read_dir(DOWNLOADS_DIR.as_path()).await else {
+        return Ok(());
+    };
+    let stale_after = std::time::Duration::from_secs(LINK_LIFETIME_MINUTES as u64 * 60 * 2);
+    while let Some(entry) = entries.next_entry().await? {
+        let age = entry
+            .metadata()
+            .await?
+            .modified()?
+            .elapsed()
+            .unwrap_or_default();
+        if age > stale_after {
+            let _ = tokio::fs::remove_file(entry.path()).await;
+        }
+    }
+
+    Ok(())
+}
+
+fn mac(id: &str, owner: &str, expires: i64) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&SIGNING_KEY).expect("HMAC takes any key size");
+    mac.update(format!("{}:{}:{}", id, owner, expires).as_bytes());
+    mac
+}
+
+fn signature(id: &str, owner: &str, expires: i64) -> String {
+    hex::encode(mac(id, owner, expires).finalize().into_bytes())
+}
+
+fn verify_signature(id: &str, owner: &str, expires: i64, signature_hex: &str) -> bool {
+    match hex::decode(signature_hex) {
+        Ok(signature) => mac(id, owner, expires).verify_slice(&signature).is_ok(),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,66 @@
+use axum::{
+    body::Body,
+    http::{Request, StatusCode},
+};
+use tower::ServiceExt;
+
+use super::*;
+use crate::repo::AppState;
+use crate::routes;
+use crate::testing::block_on;
+
+/// The id, expiry and signature a link's URL carries.
+fn parts(link: &DownloadLink) -> (String, i64, String) {
+    let (path, query) = link.url.split_once('?').expect("No query in the link");
+    let id = path.trim_start_matches("/downloads/").to_string();
+    let mut expires = 0;
+    let mut signature = String::new();
+    for (name, value) in query.split('&').filter_map(|pair| pair.split_once('=')) {
+        match name {
+            "expires" => expires = value.parse().expect("Bad expiry"),
+            "signature" => signature = value.to_string(),
+            _ => {}
+        }
+    }
+    (id, expires, signature)
+}
+
+async fn redeem_as(link: &DownloadLink, username: &str) -> Option<Vec<u8>> {
+    let (id, expires, signature) = parts(link);
+    redeem(&id, expires, &signature, username)
+        .await
+        .map_err(|err| err.to_string())
+        .expect("Could not redeem the download")
+        .map(|export| export.bytes)
+}
+
+#[test]
+fn link_opens_once_and_only_for_its_owner() {
+    block_on(async {
+        let export = Export::csv("tips.csv".to_string(), b"eid,net_tips\n101,12.5\n".to_vec());
+        let link = store(export, "manager")
+            .await
+            .map_err(|err| err.to_string())
+            .expect("Could not store the download");
+
+        let request = Request::get(&link.url)
+            .body(Body::empty())
+            .expect("Could not build request");
+        let response = routes::routes(AppState::surreal(DB.clone()))
+            .oneshot(request)
+            .await
+            .expect("Could not send request");
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        assert_eq!(redeem_as(&link, "other").await, None);
+        let mut tampered = link.clone();
+        tampered.url = link.url.replace("signature=", "signature=00");
+        assert_eq!(redeem_as(&tampered, "manager").await, None);
+
+        assert_eq!(
+            redeem_as(&link, "manager").await.as_deref(),
+            Some(&b"eid,net_tips\n101,12.5\n"[..])
+        );
+        assert_eq!(redeem_as(&link, "manager").await, None);
+    });
+}
@@ -0,0 +1,111 @@
+use axum::{
+    body::{Body, Bytes},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use chrono::NaiveDate;
+use futures::{stream, Future};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+/// Rows fetched from the database per chunk of a streamed export.
+const PAGE_SIZE: usize = 500;
+
+type StreamError = Box<dyn Error + Send + Sync>;
+
+/// Filters shared by the CSV exports, read from the query string.
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
+#[serde(default)]
+pub struct ExportFilter {
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+    pub eid: Option<i32>,
+    pub role: Option<String>,
+}
+
+/// A row of a CSV export. The header is written even when there are no
+/// rows, so it has to be known without one.
+pub trait CsvRow: Serialize {
+    /// Column names, in the order the fields are serialized.
+    const HEADER: &'static [&'static str];
+}
+
+/// Writes rows to `writer` as CSV, fetching them a page at a time like
+/// [`stream_csv`], and returns how many were written.
+pub async fn write_csv<T, W, F, Fut>(writer: W, fetch_page: F) -> Result<usize, Box<dyn Error>>
+where
+    T: CsvRow,
+    W: std::io::Write,
+    F: Fn(usize, usize) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, surrealdb::Error>>,
+{
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(writer);
+    writer.write_record(T::HEADER)?;
+    let mut offset = 0;
+    loop {
+        let rows = fetch_page(offset, PAGE_SIZE).await?;
+        for row in &rows {
+            writer.serialize(row)?;
+        }
+        offset += rows.len();
+        if rows.len() < PAGE_SIZE {
+            break;
+        }
+    }
+    writer.flush()?;
+    Ok(offset)
+}
+
+/// Streams rows as a CSV attachment, fetching them with
+/// `fetch_page(offset, limit)` one page at a time so a large date range is
+/// never held in memory all at once.
+pub fn stream_csv<T, F, Fut>(filename: String, fetch_page: F) -> Response
+where
+    T: CsvRow + Send + 'static,
+    F: Fn(usize, usize) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Vec<T>, surrealdb::Error>> + Send + 'static,
+{
+    let chunks = stream::try_unfold(Some(0), move |offset: Option<usize>| {
+        let page = offset.map(|offset| fetch_page(offset, PAGE_SIZE));
+        async move {
+            let (Some(offset), Some(page)) = (offset, page) else {
+                return Ok::<_, StreamError>(None);
+            };
+
+            let rows = page.await?;
+            let mut writer = csv::WriterBuilder::new()
+                .has_headers(false)
+                .from_writer(Vec::new());
+            if offset == 0 {
+                writer.write_record(T::HEADER)?;
+            }
+            for row in &rows {
+                writer.serialize(row)?;
+            }
+            let bytes = writer.into_inner().map_err(|err| err.to_string())?;
+
+            let next = match rows.len() < PAGE_SIZE {
+                true => None,
+                false => Some(offset + rows.len()),
+            };
+            Ok(Some((Bytes::from(bytes), next)))
+        }
+    });
+
+    (
+        [
+            (header::CONTENT_TYPE, "text/csv".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        Body::from_stream(chunks),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,61 @@
+use serde::de::DeserializeOwned;
+use serde_json::json;
+
+use super::*;
+use crate::model::staff::StaffCsvData;
+use crate::model::tips::TipsCsvData;
+use crate::testing::block_on;
+
+/// Writes an export whose query finds no rows.
+fn write_empty<T: CsvRow>() -> String {
+    let mut bytes = Vec::new();
+    block_on(write_csv(&mut bytes, |_, _| async { Ok(Vec::<T>::new()) }))
+        .map_err(|err| err.to_string())
+        .expect("Could not write the CSV");
+    String::from_utf8(bytes).expect("The CSV is not UTF-8")
+}
+
+/// Serializes `row` with serde's own field names, to check the header
+/// against.
+fn serde_header<T: DeserializeOwned + Serialize>(row: serde_json::Value) -> String {
+    let row: T = serde_json::from_value(row).expect("Could not build the row");
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.serialize(row).expect("Could not serialize the row");
+    let csv = String::from_utf8(writer.into_inner().expect("Could not flush")).unwrap();
+    csv.lines().next().unwrap_or_default().to_string()
+}
+
+#[test]
+fn empty_export_still_has_a_header() {
+    assert_eq!(
+        write_empty::<TipsCsvData>(),
+        TipsCsvData::HEADER.join(",") + "\n"
+    );
+    assert_eq!(
+        write_empty::<StaffCsvData>(),
+        StaffCsvData::HEADER.join(",") + "\n"
+    );
+}
+
+#[test]
+fn headers_match_the_serialized_fields() {
+    let tip = json!({
+        "date": "2024-05-01", "name": "Ada", "eid": 101, "role": "server",
+        "duration": 6.0, "pool_share": 1.0, "tip_out_paid": 0.0, "tip_out_received": 0.0,
+        "net_tips": 80.0, "total_pay_for_night": 140.0, "hourly_pay_for_night": 23.3,
+        "tipped_hour_for_night": 13.3
+    });
+    assert_eq!(
+        serde_header::<TipsCsvData>(tip),
+        TipsCsvData::HEADER.join(",")
+    );
+
+    let staff = json!({
+        "name": "Ada", "role": "server", "date": "2024-05-01", "net_tips": 80.0,
+        "total_pay_for_night": 140.0, "hourly_pay_for_night": 23.3
+    });
+    assert_eq!(
+        serde_header::<StaffCsvData>(staff),
+        StaffCsvData::HEADER.join(",")
+    );
+}
@@ -0,0 +1,617 @@
+use chrono::{DateTime, Duration, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+use surrealdb::sql::Id;
+use tokio::sync::{broadcast, mpsc, Notify};
+
+use crate::calculations::{
+    self, BatchRecalculation, BatchReport, LaborReportUpload, StaffMatchError, Summary,
+    TipOverride, TippedDayCalculation,
+};
+use crate::downloads::{self, DownloadLink, Export};
+use crate::exports::{self, ExportFilter};
+use crate::repo::AppState;
+use crate::DB;
+
+/// How long the worker waits to be told about a new job before checking
+/// the queue anyway.
+const POLL_INTERVAL_SECONDS: u64 = 30;
+const CLEAN_UP_INTERVAL_MINUTES: u64 = 60;
+/// Days a finished job, and the upload of a failed one, is kept for.
+const RETENTION_DAYS: i64 = 7;
+const LIST_LIMIT: usize = 50;
+
+/// Private directory uploads are kept in until their job succeeds, so a
+/// failed job can be retried without uploading again.
+static JOBS_DIR: Lazy<PathBuf> = Lazy::new(|| {
+    std::env::var("JOBS_DIR")
+        .unwrap_or_else(|_| "jobs".to_string())
+        .into()
+});
+
+/// Wakes the worker when a job is queued.
+static QUEUED: Lazy<Notify> = Lazy::new(Notify::new);
+
+/// Every change to a job, for clients following its progress.
+static UPDATES: Lazy<broadcast::Sender<Job>> = Lazy::new(|| broadcast::channel(64).0);
+
+/// Work run in the background rather than in the request that asked for it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JobInput {
+    /// A labor report upload; the report itself is kept in `JOBS_DIR`.
+    Calculation {
+        night: LaborReportUpload,
+    },
+    BatchRecalculation {
+        batch: BatchRecalculation,
+    },
+    TipsExport {
+        filter: ExportFilter,
+    },
+}
+
+impl JobInput {
+    /// Applies corrections sent with a retry. Only a calculation takes them.
+    pub fn amend(&mut self, amendment: RetryAmendment) -> Result<(), String> {
+        if amendment.confirmed_matches.is_empty() && amendment.overrides.is_empty() {
+            return Ok(());
+        }
+        let JobInput::Calculation { night } = self else {
+            return Err(
+                "Only a calculation can be retried with confirmedMatches or overrides".into(),
+            );
+        };
+
+        night.confirmed_matches.extend(amendment.confirmed_matches);
+        for amended in amendment.overrides {
+            night
+                .overrides
+                .retain(|existing| existing.eid != amended.eid);
+            night.overrides.push(amended);
+        }
+        Ok(())
+    }
+}
+
+/// Corrections to a failed calculation's input, such as matches for the
+/// labor report rows it could not place.
+#[derive(Debug, Deserialize, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub struct RetryAmendment {
+    /// Added to the matches already confirmed, replacing any for the same
+    /// row.
+    pub confirmed_matches: HashMap<String, i32>,
+    /// Each replaces any override already given for the same employee.
+    pub overrides: Vec<TipOverride>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl JobStatus {
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Succeeded | JobStatus::Failed)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Progress {
+    pub step: String,
+    pub done: usize,
+    /// Amount of work in the step, when it is known up front.
+    pub total: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Job {
+    pub id: String,
+    pub status: JobStatus,
+    pub input: JobInput,
+    pub progress: Progress,
+    /// What the job produced, in the shape the endpoint that queued it
+    /// used to respond with.
+    #[serde(default)]
+    pub result: Option<Value>,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// More about the failure, such as labor report rows that need a
+    /// confirmed staff match.
+    #[serde(default)]
+    pub details: Option<Value>,
+    pub attempts: u32,
+    pub owner: String,
+    pub created: DateTime<Utc>,
+    pub modified: DateTime<Utc>,
+    #[serde(default)]
+    pub started: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub finished: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+struct CalculationResult {
+    calculations_link: DownloadLink,
+    template_link: DownloadLink,
+    summary: Summary,
+    tips: Vec<TippedDayCalculation>,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchResult {
+    /// Comparison of old and new net tips per employee and night.
+    report_link: DownloadLink,
+    #[serde(flatten)]
+    report: BatchReport,
+}
+
+#[derive(Debug, Serialize)]
+struct ExportResult {
+    download_link: DownloadLink,
+    rows: usize,
+}
+
+struct JobFailure {
+    message: String,
+    details: Option<Value>,
+}
+
+impl From<Box<dyn Error>> for JobFailure {
+    fn from(err: Box<dyn Error>) -> Self {
+        JobFailure {
+            details: err
+                .downcast_ref::<StaffMatchError>()
+                .map(|err| json!({ "unmatched": err.unmatched })),
+            message: err.to_string(),
+        }
+    }
+}
+
+fn failed(message: impl Into<String>) -> JobFailure {
+    JobFailure {
+        message: message.into(),
+        details: None,
+    }
+}
+
+/// Hands progress from a running job to the task that saves it.
+struct Reporter(mpsc::UnboundedSender<Progress>);
+
+impl Reporter {
+    fn report(&self, step: &str, done: usize, total: Option<usize>) {
+        let _ = self.0.send(Progress {
+            step: step.to_string(),
+            done,
+            total,
+        });
+    }
+}
+
+/// Saves a job for the worker to pick up, with the file it works on.
+pub async fn enqueue(
+    input: JobInput,
+    upload: Option<&[u8]>,
+    owner: &str,
+) -> Result<Job, Box<dyn Error>> {
+    let id = Id::rand().to_raw();
+    if let Some(upload) = upload {
+        tokio::fs::create_dir_all(JOBS_DIR.as_path()).await?;
+        tokio::fs::write(JOBS_DIR.join(&id), upload).await?;
+    }
+
+    DB.query(
+        "
+        CREATE type::thing('jobs', $id) CONTENT {
+            status: 'queued',
+            input: $input,
+            progress: $progress,
+            attempts: 0,
+            owner: $owner,
+            created: $now,
+            modified: $now
+        };
+        ",
+    )
+    .bind(("id", id.as_str()))
+    .bind(("input", input))
+    .bind(("progress", queued()))
+    .bind(("owner", owner))
+    .bind(("now", Utc::now()))
+    .await?
+    .check()?;
+
+    let job = load(&id).await?.ok_or("Could not queue the job")?;
+    publish(&job);
+    QUEUED.notify_one();
+    Ok(job)
+}
+
+/// The job with `id`, if it belongs to `owner`.
+pub async fn get(id: &str, owner: &str) -> Result<Option<Job>, Box<dyn Error>> {
+    Ok(load(id).await?.filter(|job| job.owner == owner))
+}
+
+/// `owner`'s most recent jobs, newest first.
+pub async fn list(owner: &str) -> Result<Vec<Job>, Box<dyn Error>> {
+    let jobs: Vec<Job> = DB
+        .query(
+            "
+            SELECT *, meta::id(id) AS id FROM jobs WHERE owner = $owner
+            ORDER BY created DESC LIMIT $limit;
+            ",
+        )
+        .bind(("owner", owner))
+        .bind(("limit", LIST_LIMIT))
+        .await?
+        .take(0)?;
+    Ok(jobs)
+}
+
+/// Queues a failed job to run again from `job.input`, which may have been
+/// amended since it failed. Returns `None` when the job had not failed.
+pub async fn retry(job: &Job) -> Result<Option<Job>, Box<dyn Error>> {
+    let retried: Vec<String> = DB
+        .query(
+            "
+            UPDATE type::thing('jobs', $id) SET
+                status = 'queued',
+                input = $input,
+                progress = $progress,
+                result = NONE,
+                error = NONE,
+                details = NONE,
+                started = NONE,
+                finished = NONE,
+                modified = $now
+            WHERE status = 'failed'
+            RETURN VALUE meta::id(id);
+            ",
+        )
+        .bind(("id", job.id.as_str()))
+        .bind(("input", &job.input))
+        .bind(("progress", queued()))
+        .bind(("now", Utc::now()))
+        .await?
+        .take(0)?;
+    if retried.is_empty() {
+        return Ok(None);
+    }
+
+    let job = load(&job.id).await?.ok_or("Could not queue the job")?;
+    publish(&job);
+    QUEUED.notify_one();
+    Ok(Some(job))
+}
+
+/// Changes to jobs from now on.
+pub fn subscribe() -> broadcast::Receiver<Job> {
+    UPDATES.subscribe()
+}
+
+/// Runs queued jobs one at a time, oldest first, for the life of the
+/// server. Jobs left running by a previous server are marked failed so
+/// they can be retried. Each job runs in its own task, so one that panics
+/// fails instead of stopping the queue.
+pub async fn run_queue(state: AppState) {
+    if let Err(err) = fail_interrupted().await.map_err(|err| err.to_string()) {
+        tracing::warn!("Could not fail interrupted jobs: {}", err);
+    }
+
+    let poll_interval = std::time::Duration::from_secs(POLL_INTERVAL_SECONDS);
+    loop {
+        match claim_next().await.map_err(|err| err.to_string()) {
+            Ok(Some(job)) => run(job, &state).await,
+            Ok(None) => {
+                let _ = tokio::time::timeout(poll_interval, QUEUED.notified()).await;
+            }
+            Err(err) => {
+                tracing::warn!("Could not check the job queue: {}", err);
+                tokio::time::sleep(poll_interval).await;
+            }
+        }
+    }
+}
+
+/// Removes jobs finished more than `RETENTION_DAYS` ago and their uploads,
+/// every hour for the life of the server.
+pub async fn clean_up_periodically() {
+    let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+        CLEAN_UP_INTERVAL_MINUTES * 60,
+    ));
+
+    loop {
+        interval.tick().await;
+        if let Err(err) = clean_up().await {
+            tracing::warn!("Could not clean up jobs: {}", err);
+        }
+    }
+}
+
+async fn clean_up() -> Result<(), Box<dyn Error>> {
+    let expired: Vec<String> = DB
+        .query(
+            "
+            SELECT VALUE meta::id(id) FROM jobs WHERE finished != NONE AND finished < $cutoff;
+            DELETE jobs WHERE finished != NONE AND finished < $cutoff;
+            ",
+        )
+        .bind(("cutoff", Utc::now() - Duration::days(RETENTION_DAYS)))
+        .await?
+        .take(0)?;
+
+    for id in expired {
+        let _ = tokio::fs::remove_file(JOBS_DIR.join(id)).await;
+    }
+    Ok(())
+}
+
+fn queued() -> Progress {
+    Progress {
+        step: "Queued".to_string(),
+        ..Default::default()
+    }
+}
+
+fn publish(job: &Job) {
+    // Nobody may be listening, which is fine
+    let _ = UPDATES.send(job.clone());
+}
+
+async fn load(id: &str) -> Result<Option<Job>, Box<dyn Error>> {
+    let job: Option<Job> = DB
+        .query(
+            "
+            SELECT *, meta::id(id) AS id FROM type::thing('jobs', $id);
+            ",
+        )
+        .bind(("id", id))
+        .await?
+        .take(0)?;
+    Ok(job)
+}
+
+async fn fail_interrupted() -> Result<(), Box<dyn Error>> {
+    DB.query(
+        "
+        UPDATE jobs SET
+            status = 'failed',
+            error = 'The server restarted while the job was running',
+            finished = $now,
+            modified = $now
+        WHERE status = 'running';
+        ",
+    )
+    .bind(("now", Utc::now()))
+    .await?
+    .check()?;
+    Ok(())
+}
+
+/// Marks the oldest queued job as running and returns it.
+async fn claim_next() -> Result<Option<Job>, Box<dyn Error>> {
+    let claimed: Vec<String> = DB
+        .query(
+            "
+            UPDATE (SELECT id, created FROM jobs WHERE status = 'queued' ORDER BY created ASC LIMIT 1).id SET
+                status = 'running',
+                attempts += 1,
+                started = $now,
+                modified = $now
+            WHERE status = 'queued'
+            RETURN VALUE meta::id(id);
+            ",
+        )
+        .bind(("now", Utc::now()))
+        .await?
+        .take(0)?;
+
+    let Some(id) = claimed.into_iter().next() else {
+        return Ok(None);
+    };
+    let job = load(&id).await?;
+    if let Some(job) = &job {
+        publish(job);
+    }
+    Ok(job)
+}
+
+async fn run(job: Job, state: &AppState) {
+    tracing::info!("Running job {}", job.id);
+
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let id = job.id.clone();
+    let progress_writer = tokio::spawn(async move {
+        while let Some(progress) = receiver.recv().await {
+            if let Err(err) = save_progress(&id, progress)
+                .await
+                .map_err(|err| err.to_string())
+            {
+                tracing::warn!("Could not save progress of job {}: {}", id, err);
+            }
+        }
+    });
+
+    let task = {
+        let (job, state) = (job.clone(), state.clone());
+        tokio::spawn(async move { execute(&job, &state, &Reporter(sender)).await })
+    };
+    let outcome = task.await.unwrap_or_else(|err| {
+        tracing::error!("Job {} stopped unexpectedly: {}", job.id, err);
+        Err(failed("The job stopped unexpectedly"))
+    });
+    let _ = progress_writer.await;
+
+    if let Err(failure) = &outcome {
+        tracing::warn!("Job {} failed: {}", job.id, failure.message);
+    }
+    if let Err(err) = finish(&job.id, outcome)
+        .await
+        .map_err(|err| err.to_string())
+    {
+        tracing::warn!("Could not save the outcome of job {}: {}", job.id, err);
+    }
+}
+
+async fn execute(job: &Job, state: &AppState, reporter: &Reporter) -> Result<Value, JobFailure> {
+    match job.input.clone() {
+        JobInput::Calculation { night } => calculate(job, night, reporter).await,
+        JobInput::BatchRecalculation { batch } => recalculate(job, batch, reporter).await,
+        JobInput::TipsExport { filter } => export_tips(job, filter, state, reporter).await,
+    }
+}
+
+async fn calculate(
+    job: &Job,
+    night: LaborReportUpload,
+    reporter: &Reporter,
+) -> Result<Value, JobFailure> {
+    let labor_report = tokio::fs::read(JOBS_DIR.join(&job.id))
+        .await
+        .map_err(|_| failed("The uploaded labor report is no longer available"))?;
+
+    reporter.report("Calculating tips", 0, Some(2));
+    let (data_csv, template_csv, summary, tips) =
+        calculations::read_csv(night, &labor_report).await?;
+
+    reporter.report("Saving files", 1, Some(2));
+    let links = downloads::store_all(vec![data_csv, template_csv], &job.owner).await?;
+    let [calculations_link, template_link] = <[DownloadLink; 2]>::try_from(links)
+        .map_err(|_| failed("Could not save calculation files"))?;
+
+    to_result(CalculationResult {
+        calculations_link,
+        template_link,
+        summary,
+        tips,
+    })
+}
+
+async fn recalculate(
+    job: &Job,
+    batch: BatchRecalculation,
+    reporter: &Reporter,
+) -> Result<Value, JobFailure> {
+    let report = calculations::recalculate_range(batch, &job.owner, &|done, total| {
+        reporter.report("Recalculating nights", done, Some(total))
+    })
+    .await?;
+
+    let export = report.csv()?;
+    let report_link = downloads::store(export, &job.owner).await?;
+    to_result(BatchResult {
+        report_link,
+        report,
+    })
+}
+
+async fn export_tips(
+    job: &Job,
+    filter: ExportFilter,
+    state: &AppState,
+    reporter: &Reporter,
+) -> Result<Value, JobFailure> {
+    let mut bytes = Vec::new();
+    let rows = exports::write_csv(&mut bytes, |offset, limit| {
+        reporter.report("Writing rows", offset, None);
+        state.tips.export_page(filter.clone(), offset, limit)
+    })
+    .await?;
+    reporter.report("Writing rows", rows, Some(rows));
+
+    let export = Export::csv(
+        format!(
+            "tips-data-export_{}.csv",
+            Utc::now().format("%Y-%m-%dT%H-%M-%SZ")
+        ),
+        bytes,
+    );
+    let download_link = downloads::store(export, &job.owner).await?;
+    to_result(ExportResult {
+        download_link,
+        rows,
+    })
+}
+
+fn to_result<T: Serialize>(result: T) -> Result<Value, JobFailure> {
+    serde_json::to_value(result).map_err(|err| failed(err.to_string()))
+}
+
+async fn save_progress(id: &str, progress: Progress) -> Result<(), Box<dyn Error>> {
+    DB.query(
+        "
+        UPDATE type::thing('jobs', $id) SET progress = $progress, modified = $now;
+        ",
+    )
+    .bind(("id", id))
+    .bind(("progress", progress))
+    .bind(("now", Utc::now()))
+    .await?
+    .check()?;
+
+    let job = load(id).await?;
+    if let Some(job) = job {
+        publish(&job);
+    }
+    Ok(())
+}
+
+async fn finish(id: &str, outcome: Result<Value, JobFailure>) -> Result<(), Box<dyn Error>> {
+    let query = match &outcome {
+        Ok(_) => {
+            "
+            UPDATE type::thing('jobs', $id) SET
+                status = 'succeeded',
+                progress.step = 'Done',
+                progress.done = progress.total ?? progress.done,
+                result = $result,
+                finished = $now,
+                modified = $now;
+            "
+        }
+        Err(_) => {
+            "
+            UPDATE type::thing('jobs', $id) SET
+                status = 'failed',
+                error = $error,
+                details = $details,
+                finished = $now,
+                modified = $now;
+            "
+        }
+    };
+    let (result, error, details) = match outcome {
+        Ok(result) => (Some(result), None, None),
+        Err(failure) => (None, Some(failure.message), failure.details),
+    };
+
+    DB.query(query)
+        .bind(("id", id))
+        .bind(("result", result))
+        .bind(("error", error))
+        .bind(("details", details))
+        .bind(("now", Utc::now()))
+        .await?
+        .check()?;
+
+    let job = load(id).await?;
+    if let Some(job) = job {
+        if job.status == JobStatus::Succeeded {
+            let _ = tokio::fs::remove_file(JOBS_DIR.join(id)).await;
+        }
+        publish(&job);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
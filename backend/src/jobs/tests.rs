@@ -0,0 +1,163 @@
+use chrono::NaiveDate;
+use tokio::sync::Mutex;
+
+use super::*;
+use crate::testing::block_on;
+
+/// `claim_next` and `fail_interrupted` act on every job in the shared
+/// database, so the tests take turns.
+static QUEUE: Mutex<()> = Mutex::const_new(());
+
+fn calculation(confirmed: &[(&str, i32)]) -> JobInput {
+    JobInput::Calculation {
+        night: LaborReportUpload {
+            date: NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(),
+            confirmed_matches: confirmed
+                .iter()
+                .map(|(row, eid)| (row.to_string(), *eid))
+                .collect(),
+            ..Default::default()
+        },
+    }
+}
+
+fn night(input: &JobInput) -> &LaborReportUpload {
+    match input {
+        JobInput::Calculation { night } => night,
+        other => panic!("Expected a calculation, found {:?}", other),
+    }
+}
+
+async fn claim() -> Job {
+    claim_next()
+        .await
+        .map_err(|err| err.to_string())
+        .unwrap()
+        .expect("No job was queued")
+}
+
+#[test]
+fn queued_job_is_claimed_run_and_finished() {
+    block_on(async {
+        let _queue = QUEUE.lock().await;
+        let filter = ExportFilter {
+            eid: Some(101),
+            ..Default::default()
+        };
+        let queued = enqueue(JobInput::TipsExport { filter }, None, "manager")
+            .await
+            .map_err(|err| err.to_string())
+            .unwrap();
+        assert_eq!(queued.status, JobStatus::Queued);
+
+        let claimed = claim().await;
+        assert_eq!(claimed.id, queued.id);
+        assert_eq!(claimed.status, JobStatus::Running);
+        assert_eq!(claimed.attempts, 1);
+        assert!(claim_next()
+            .await
+            .map_err(|err| err.to_string())
+            .unwrap()
+            .is_none());
+
+        run(claimed, &AppState::surreal(DB.clone())).await;
+
+        let finished = get(&queued.id, "manager")
+            .await
+            .map_err(|err| err.to_string())
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            finished.status,
+            JobStatus::Succeeded,
+            "{:?}",
+            finished.error
+        );
+        assert!(finished.finished.is_some());
+        let result = finished.result.expect("No result was saved");
+        assert!(result["download_link"]["url"].is_string());
+        assert!(get(&queued.id, "someone else")
+            .await
+            .map_err(|err| err.to_string())
+            .unwrap()
+            .is_none());
+    });
+}
+
+#[test]
+fn interrupted_job_fails_and_retries_with_amendments() {
+    block_on(async {
+        let _queue = QUEUE.lock().await;
+        let queued = enqueue(calculation(&[("P900", 101)]), None, "manager")
+            .await
+            .map_err(|err| err.to_string())
+            .unwrap();
+        claim().await;
+
+        fail_interrupted()
+            .await
+            .map_err(|err| err.to_string())
+            .unwrap();
+        let mut failed = get(&queued.id, "manager")
+            .await
+            .map_err(|err| err.to_string())
+            .unwrap()
+            .unwrap();
+        assert_eq!(failed.status, JobStatus::Failed);
+        assert_eq!(
+            failed.error.as_deref(),
+            Some("The server restarted while the job was running")
+        );
+
+        let amendment: RetryAmendment = serde_json::from_value(json!({
+            "confirmedMatches": { "P900": 102, "Dee L": 104 },
+            "overrides": [{ "eid": 103, "exclude": true, "reason": "Left early" }]
+        }))
+        .unwrap();
+        failed.input.amend(amendment).unwrap();
+        let retried = retry(&failed)
+            .await
+            .map_err(|err| err.to_string())
+            .unwrap()
+            .expect("The failed job was not retried");
+        assert_eq!(retried.status, JobStatus::Queued);
+        assert_eq!(retried.error, None);
+        assert_eq!(night(&retried.input).confirmed_matches["P900"], 102);
+        assert_eq!(night(&retried.input).confirmed_matches["Dee L"], 104);
+        assert_eq!(night(&retried.input).overrides.len(), 1);
+
+        // Only a failed job can be retried
+        assert!(retry(&retried)
+            .await
+            .map_err(|err| err.to_string())
+            .unwrap()
+            .is_none());
+
+        let claimed = claim().await;
+        assert_eq!(claimed.id, queued.id);
+        assert_eq!(claimed.attempts, 2);
+        assert_eq!(night(&claimed.input).confirmed_matches["P900"], 102);
+        fail_interrupted()
+            .await
+            .map_err(|err| err.to_string())
+            .unwrap();
+    });
+}
+
+#[test]
+fn only_calculations_take_amendments() {
+    let amendment = || RetryAmendment {
+        confirmed_matches: HashMap::from([("P900".to_string(), 101)]),
+        ..Default::default()
+    };
+
+    let mut export = JobInput::TipsExport {
+        filter: ExportFilter::default(),
+    };
+    assert!(export.amend(amendment()).is_err());
+    assert!(export.amend(RetryAmendment::default()).is_ok());
+
+    let mut input = calculation(&[]);
+    input.amend(amendment()).unwrap();
+    assert_eq!(night(&input).confirmed_matches["P900"], 101);
+}
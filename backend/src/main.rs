@@ -1,49 +1,131 @@
 use axum::extract::DefaultBodyLimit;
+use clap::Parser;
 use once_cell::sync::Lazy;
-use surrealdb::{
-    engine::remote::ws::{Client, Ws},
-    opt::auth::Root,
-    Surreal,
-};
+use std::error::Error;
+use surrealdb::{engine::any::Any, Surreal};
 use tower_http::limit::RequestBodyLimitLayer;
-use tower_http::services::ServeDir;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod analytics;
+mod auth;
+mod backup;
 mod calculations;
+mod changes;
+mod cli;
+mod database;
+mod downloads;
+mod exports;
+mod jobs;
+mod migrations;
+mod model;
+mod pricing;
+mod repo;
 mod routes;
+#[cfg(test)]
+mod testing;
+mod wine_list;
 
-static DB: Lazy<Surreal<Client>> = Lazy::new(Surreal::init);
+static DB: Lazy<Surreal<Any>> = Lazy::new(Surreal::init);
 
 #[tokio::main]
-async fn main() -> surrealdb::Result<()> {
+async fn main() -> Result<(), Box<dyn Error>> {
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| "wine-list=debug,tower_http=debug".into()),
         )
-        .with(tracing_subscriber::fmt::layer())
+        // Logs go to stderr so commands that write to stdout, like
+        // `export-tips`, produce clean output
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
         .init();
 
-    // Connect to the server
-    DB.connect::<Ws>("127.0.0.1:8000").await?;
+    let cli = cli::Cli::parse();
 
-    // Signin as a namespace, database, or root user
-    DB.signin(Root {
-        username: "root",
-        password: "root",
-    })
-    .await?;
+    database::connect(&DB).await?;
 
-    // Select a specific namespace / database
-    DB.use_ns("test").use_db("test").await?;
+    let applied = migrations::run(&DB).await?;
 
-    let app = routes::routes()
-        .nest_service("/public", ServeDir::new("public"))
+    match cli.command.unwrap_or(cli::Command::Serve) {
+        cli::Command::Serve => serve().await,
+        cli::Command::Migrate => cli::migrate(applied),
+        cli::Command::Calculate {
+            date,
+            sales,
+            gotab,
+            cash,
+            overrides,
+            confirmed,
+            output,
+            report,
+        } => {
+            cli::calculate(
+                date, sales, gotab, cash, overrides, confirmed, output, report,
+            )
+            .await
+        }
+        cli::Command::Recalculate {
+            start,
+            end,
+            policy_version,
+            commit,
+            output,
+        } => {
+            let batch = calculations::BatchRecalculation {
+                start,
+                end,
+                policy_version,
+                commit,
+            };
+            cli::recalculate(batch, output).await
+        }
+        cli::Command::ImportStaff { file } => cli::import_staff(file).await,
+        cli::Command::ExportTips {
+            start,
+            end,
+            eid,
+            role,
+            output,
+        } => {
+            let filter = exports::ExportFilter {
+                start,
+                end,
+                eid,
+                role,
+            };
+            cli::export_tips(filter, output).await
+        }
+        cli::Command::Backup { output } => cli::back_up(output).await,
+        cli::Command::Restore { archive, dry_run } => cli::restore(archive, dry_run).await,
+    }
+}
+
+async fn serve() -> Result<(), Box<dyn Error>> {
+    tokio::spawn(downloads::clean_up_periodically());
+    tokio::spawn(backup::back_up_periodically());
+    tokio::spawn(jobs::clean_up_periodically());
+    tokio::spawn(changes::watch_tables());
+
+    let state = repo::AppState::surreal(DB.clone());
+    tokio::spawn(jobs::run_queue(state.clone()));
+
+    let app = routes::routes(state)
         .layer(DefaultBodyLimit::disable())
         .layer(RequestBodyLimitLayer::new(
             250 * 1024 * 1024, /* 250mb */
         ))
-        .layer(tower_http::trace::TraceLayer::new_for_http());
+        .layer(
+            // Spans leave out the query string, which carries stream tokens
+            // and download link signatures
+            tower_http::trace::TraceLayer::new_for_http().make_span_with(
+                |request: &axum::extract::Request| {
+                    tracing::debug_span!(
+                        "request",
+                        method = %request.method(),
+                        path = %request.uri().path(),
+                    )
+                },
+            ),
+        );
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
     axum::serve(listener, app).await.unwrap();
@@ -0,0 +1,105 @@
+use chrono::Utc;
+use std::error::Error;
+use surrealdb::{engine::any::Any, Surreal};
+
+/// Schema changes in the order they are applied. Append new migrations to
+/// the end; never edit or reorder one that has shipped.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "define_tables",
+        sql: include_str!("../migrations/0001_define_tables.surql"),
+    },
+    Migration {
+        version: 2,
+        name: "tip_policy_versions",
+        sql: include_str!("../migrations/0002_tip_policy_versions.surql"),
+    },
+    Migration {
+        version: 3,
+        name: "define_jobs",
+        sql: include_str!("../migrations/0003_define_jobs.surql"),
+    },
+];
+
+/// Version of the newest migration this build knows about.
+pub fn latest_version() -> u32 {
+    MIGRATIONS
+        .iter()
+        .map(|migration| migration.version)
+        .max()
+        .unwrap_or_default()
+}
+
+struct Migration {
+    version: u32,
+    name: &'static str,
+    sql: &'static str,
+}
+
+/// Applies every migration not yet recorded in the `migrations` table,
+/// each in its own transaction together with its history record, and
+/// returns the names of those it applied.
+pub async fn run(db: &Surreal<Any>) -> Result<Vec<String>, Box<dyn Error>> {
+    db.query(
+        "
+        DEFINE TABLE migrations SCHEMAFULL;
+        DEFINE FIELD version ON migrations TYPE int;
+        DEFINE FIELD name ON migrations TYPE string;
+        DEFINE FIELD applied ON migrations TYPE string;
+        ",
+    )
+    .await?
+    .check()?;
+
+    let applied: Vec<u32> = db
+        .query(
+            "
+            SELECT VALUE version FROM migrations;
+            ",
+        )
+        .await?
+        .take(0)?;
+
+    let mut newly_applied = Vec::new();
+    for migration in MIGRATIONS {
+        if applied.contains(&migration.version) {
+            continue;
+        }
+
+        tracing::info!(
+            "Applying migration {:04}_{}",
+            migration.version,
+            migration.name
+        );
+
+        db.query(format!(
+            "
+            BEGIN TRANSACTION;
+            {}
+            CREATE type::thing('migrations', $version) CONTENT {{
+                version: $version,
+                name: $name,
+                applied: $now
+            }};
+            COMMIT TRANSACTION;
+            ",
+            migration.sql
+        ))
+        .bind(("version", migration.version))
+        .bind(("name", migration.name))
+        .bind(("now", Utc::now()))
+        .await?
+        .check()
+        .map_err(|err| {
+            format!(
+                "Migration {:04}_{} failed: {}",
+                migration.version, migration.name, err
+            )
+        })?;
+
+        newly_applied.push(format!("{:04}_{}", migration.version, migration.name));
+    }
+
+    Ok(newly_applied)
+}
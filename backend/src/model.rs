@@ -0,0 +1,7 @@
+//! Records kept in the database, shared by the repositories, the routes
+//! and the CLI.
+
+pub mod commissions;
+pub mod staff;
+pub mod tips;
+pub mod wines;
@@ -0,0 +1,21 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Commission {
+    pub name: Thing,
+    pub wine: Thing,
+    pub amount: i32,
+    pub date: NaiveDate,
+    pub created: DateTime<Utc>,
+    pub modified: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommissionSummary {
+    name: String,
+    wine: String,
+    amount: i32,
+    date: NaiveDate,
+}
@@ -0,0 +1,227 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::calculations::Role;
+use crate::exports::CsvRow;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct EidName {
+    name: String,
+    eid: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct StaffCsvData {
+    name: String,
+    role: String,
+    date: String,
+    net_tips: f32,
+    total_pay_for_night: f32,
+    hourly_pay_for_night: f32,
+}
+
+impl CsvRow for StaffCsvData {
+    const HEADER: &'static [&'static str] = &[
+        "name",
+        "role",
+        "date",
+        "net_tips",
+        "total_pay_for_night",
+        "hourly_pay_for_night",
+    ];
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct StaffMember {
+    pub name: String,
+    pub card_id: String,
+    pub eid: i32,
+    /// IDs the labor report uses for this person; one per payroll system.
+    #[serde(default)]
+    pub payroll_ids: Vec<String>,
+    #[serde(default)]
+    pub employment: Employment,
+    #[serde(default = "default_active")]
+    pub active: bool,
+    #[serde(default)]
+    pub deactivated: Option<DateTime<Utc>>,
+    pub created: DateTime<Utc>,
+    pub modified: DateTime<Utc>,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+/// Employment terms kept on the staff record so calculations don't have to
+/// trust the role printed on the nightly labor report.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(default)]
+pub struct Employment {
+    pub primary_role: Option<Role>,
+    pub secondary_roles: Vec<Role>,
+    /// Cash wage paid per hour before tips.
+    pub hourly_wage: Option<f32>,
+    pub tip_credit_eligible: bool,
+    pub hire_date: Option<NaiveDate>,
+    pub termination_date: Option<NaiveDate>,
+    /// Share weight for point-based pools; 1.0 is a full share.
+    pub point_weight: f32,
+}
+
+impl Default for Employment {
+    fn default() -> Self {
+        Self {
+            primary_role: None,
+            secondary_roles: Vec::new(),
+            hourly_wage: None,
+            tip_credit_eligible: false,
+            hire_date: None,
+            termination_date: None,
+            point_weight: 1.0,
+        }
+    }
+}
+
+impl Employment {
+    pub fn validate(&self) -> Result<(), String> {
+        if self
+            .hourly_wage
+            .is_some_and(|wage| !wage.is_finite() || wage < 0.0)
+        {
+            return Err("hourly_wage must be zero or more".to_string());
+        }
+        if !self.point_weight.is_finite() || self.point_weight <= 0.0 {
+            return Err("point_weight must be greater than zero".to_string());
+        }
+        if let (Some(hire_date), Some(termination_date)) = (self.hire_date, self.termination_date) {
+            if termination_date < hire_date {
+                return Err("termination_date cannot be before hire_date".to_string());
+            }
+        }
+        if self
+            .primary_role
+            .is_some_and(|role| self.secondary_roles.contains(&role))
+        {
+            return Err("primary_role cannot also be a secondary role".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct StaffMemberForCreate {
+    pub name: String,
+    pub card_id: String,
+    pub eid: i32,
+    #[serde(default)]
+    pub payroll_ids: Vec<String>,
+    #[serde(default)]
+    pub employment: Option<Employment>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StaffMemberForUpdate {
+    pub name: String,
+    pub card_id: String,
+    #[serde(default)]
+    pub payroll_ids: Vec<String>,
+    #[serde(default)]
+    pub employment: Employment,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StaffMemberPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub card_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payroll_ids: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub employment: Option<EmploymentPatch>,
+}
+
+/// Employment terms to change. Fields left out are kept; `null` clears
+/// the optional ones.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EmploymentPatch {
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "::serde_with::rust::double_option"
+    )]
+    primary_role: Option<Option<Role>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    secondary_roles: Option<Vec<Role>>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "::serde_with::rust::double_option"
+    )]
+    hourly_wage: Option<Option<f32>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tip_credit_eligible: Option<bool>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "::serde_with::rust::double_option"
+    )]
+    hire_date: Option<Option<NaiveDate>>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "::serde_with::rust::double_option"
+    )]
+    termination_date: Option<Option<NaiveDate>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    point_weight: Option<f32>,
+}
+
+impl EmploymentPatch {
+    pub fn apply(&self, employment: Employment) -> Employment {
+        Employment {
+            primary_role: self.primary_role.unwrap_or(employment.primary_role),
+            secondary_roles: self
+                .secondary_roles
+                .clone()
+                .unwrap_or(employment.secondary_roles),
+            hourly_wage: self.hourly_wage.unwrap_or(employment.hourly_wage),
+            tip_credit_eligible: self
+                .tip_credit_eligible
+                .unwrap_or(employment.tip_credit_eligible),
+            hire_date: self.hire_date.unwrap_or(employment.hire_date),
+            termination_date: self.termination_date.unwrap_or(employment.termination_date),
+            point_weight: self.point_weight.unwrap_or(employment.point_weight),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct SummaryParams {
+    pub start: Option<NaiveDate>,
+    pub end: Option<NaiveDate>,
+    pub role: Option<Role>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(default)]
+pub struct StaffSummaryStats {
+    pub nights: u32,
+    pub net_tips_sum: f32,
+    pub total_hours: f32,
+    /// Hourly wages, i.e. total pay less net tips.
+    pub total_wages: f32,
+    pub total_pay: f32,
+    /// Net tips per hour worked.
+    pub average_tipped_hourly: f32,
+    /// Wages plus tips per hour worked.
+    pub average_total_hourly: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TipSummary {
+    date: NaiveDate,
+    net_tips: f32,
+}
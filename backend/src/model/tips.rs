@@ -0,0 +1,135 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use surrealdb::sql::Thing;
+
+use crate::calculations::TipOverride;
+use crate::exports::CsvRow;
+
+#[derive(Serialize, Deserialize)]
+pub struct TipsCsvData {
+    date: NaiveDate,
+    name: String,
+    eid: i32,
+    role: String,
+    duration: f32,
+    pool_share: f32,
+    tip_out_paid: f32,
+    tip_out_received: f32,
+    net_tips: f32,
+    total_pay_for_night: f32,
+    hourly_pay_for_night: f32,
+    tipped_hour_for_night: f32,
+}
+
+impl CsvRow for TipsCsvData {
+    const HEADER: &'static [&'static str] = &[
+        "date",
+        "name",
+        "eid",
+        "role",
+        "duration",
+        "pool_share",
+        "tip_out_paid",
+        "tip_out_received",
+        "net_tips",
+        "total_pay_for_night",
+        "hourly_pay_for_night",
+        "tipped_hour_for_night",
+    ];
+}
+
+#[derive(Debug, Deserialize, Default, Clone, Copy)]
+pub enum TipsSort {
+    #[serde(rename = "date")]
+    DateAsc,
+    #[default]
+    #[serde(rename = "-date")]
+    DateDesc,
+    #[serde(rename = "name")]
+    NameAsc,
+    #[serde(rename = "-name")]
+    NameDesc,
+    #[serde(rename = "net_tips")]
+    NetTipsAsc,
+    #[serde(rename = "-net_tips")]
+    NetTipsDesc,
+}
+
+impl TipsSort {
+    pub fn order_by(self) -> &'static str {
+        match self {
+            TipsSort::DateAsc => "date ASC, name ASC",
+            TipsSort::DateDesc => "date DESC, name ASC",
+            TipsSort::NameAsc => "name ASC, date DESC",
+            TipsSort::NameDesc => "name DESC, date DESC",
+            TipsSort::NetTipsAsc => "net_tips ASC, date DESC",
+            TipsSort::NetTipsDesc => "net_tips DESC, date DESC",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct TipsTotals {
+    /// Matching rows, one per employee and night.
+    pub rows: u32,
+    net_tips: f32,
+    hours: f32,
+    total_pay: f32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TippedDay {
+    name: String,
+    employee: Thing,
+    role: String,
+    #[serde(default)]
+    pool_share: f32,
+    #[serde(default)]
+    tip_out_paid: f32,
+    #[serde(default)]
+    tip_out_received: f32,
+    net_tips: f32,
+    total_pay_for_night: f32,
+    hourly_pay_for_night: f32,
+    tipped_hour_for_night: f32,
+    duration: f32,
+    eid: i32,
+    #[serde(default)]
+    adjustment: Option<TipOverride>,
+    date: NaiveDate,
+    created: DateTime<Utc>,
+    modified: DateTime<Utc>,
+}
+
+/// One stored tip row, as `nightly_breakdown` reads it.
+#[derive(Debug, Deserialize)]
+pub struct TipNight {
+    pub date: NaiveDate,
+    pub role: String,
+    pub duration: f32,
+    pub pool_share: f32,
+    pub tip_out_paid: f32,
+    pub tip_out_received: f32,
+    pub net_tips: f32,
+    pub total_pay_for_night: f32,
+}
+
+/// One employee's night, as the owner analytics read it.
+#[derive(Debug, Deserialize)]
+pub struct AnalyticsTip {
+    pub date: NaiveDate,
+    pub eid: i32,
+    pub name: String,
+    pub role: String,
+    pub duration: f32,
+    pub net_tips: f32,
+}
+
+/// The manager-entered totals stored for a night.
+#[derive(Debug, Deserialize)]
+pub struct NightInputs {
+    pub date: NaiveDate,
+    pub total_sales: f32,
+    pub total_tips: f32,
+}
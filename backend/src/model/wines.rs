@@ -0,0 +1,9 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WinesBottlePrice {
+    pub base_price: i32,
+    pub display_price: Option<String>,
+    pub name: String,
+    pub product_id: i32,
+}
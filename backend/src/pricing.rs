@@ -0,0 +1,243 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+use crate::model::wines::WinesBottlePrice;
+use crate::repo::WinesRepo;
+
+pub async fn load_policy(wines: &dyn WinesRepo) -> Result<PricingPolicy, Box<dyn Error>> {
+    let policy = wines.pricing_policy().await?;
+    Ok(policy.unwrap_or_default())
+}
+
+pub async fn save_policy(
+    wines: &dyn WinesRepo,
+    policy: PricingPolicy,
+) -> Result<PricingPolicy, Box<dyn Error>> {
+    policy.validate()?;
+    let policy = wines.save_pricing_policy(policy).await?;
+    policy.ok_or_else(|| "Could not save pricing policy".into())
+}
+
+pub async fn wine_prices(repo: &dyn WinesRepo) -> Result<Vec<WinePricing>, Box<dyn Error>> {
+    let wines = repo.bottle_prices().await?;
+    let policy = load_policy(repo).await?;
+
+    Ok(wines
+        .into_iter()
+        .map(|wine| policy.evaluate(wine))
+        .collect())
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct PricingPolicy {
+    /// Cost multipliers, checked in order; the first band whose `max_cost`
+    /// covers the wine's `base_price` applies. A band without `max_cost`
+    /// catches everything above the previous bands.
+    pub bands: Vec<PriceBand>,
+    pub bottle_rounding: Rounding,
+    /// Number of pours a bottle is priced against for the glass price.
+    pub pours_per_bottle: f32,
+    /// Multiplier on the per-pour price to cover waste from open bottles.
+    pub glass_premium: f32,
+    pub glass_rounding: Rounding,
+    /// Fraction of the suggested price a stored display price may deviate
+    /// by before it is flagged.
+    pub tolerance: f32,
+}
+
+impl Default for PricingPolicy {
+    fn default() -> Self {
+        Self {
+            bands: vec![
+                PriceBand {
+                    max_cost: Some(15),
+                    multiplier: 3.0,
+                },
+                PriceBand {
+                    max_cost: Some(40),
+                    multiplier: 2.5,
+                },
+                PriceBand {
+                    max_cost: None,
+                    multiplier: 2.0,
+                },
+            ],
+            bottle_rounding: Rounding::Whole,
+            pours_per_bottle: 4.0,
+            glass_premium: 1.0,
+            glass_rounding: Rounding::Half,
+            tolerance: 0.05,
+        }
+    }
+}
+
+impl PricingPolicy {
+    fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.bands.is_empty() {
+            return Err("Pricing policy needs at least one price band".into());
+        }
+        if self.bands.iter().any(|band| band.multiplier <= 0.0) {
+            return Err("Price band multipliers must be positive".into());
+        }
+        if self.pours_per_bottle <= 0.0 || self.glass_premium <= 0.0 {
+            return Err("Glass pricing values must be positive".into());
+        }
+        if self.tolerance < 0.0 {
+            return Err("Tolerance cannot be negative".into());
+        }
+        if let Rounding::Psychological { ending } = self.bottle_rounding {
+            Rounding::validate_ending(ending)?;
+        }
+        if let Rounding::Psychological { ending } = self.glass_rounding {
+            Rounding::validate_ending(ending)?;
+        }
+        Ok(())
+    }
+
+    pub fn multiplier(&self, cost: i32) -> f32 {
+        self.bands
+            .iter()
+            .find(|band| band.max_cost.is_none_or(|max_cost| cost <= max_cost))
+            .or(self.bands.last())
+            .map_or(1.0, |band| band.multiplier)
+    }
+
+    pub fn bottle_price(&self, cost: i32) -> f32 {
+        self.bottle_rounding
+            .apply(cost as f32 * self.multiplier(cost))
+    }
+
+    pub fn glass_price(&self, bottle_price: f32) -> f32 {
+        self.glass_rounding
+            .apply(bottle_price / self.pours_per_bottle * self.glass_premium)
+    }
+
+    pub fn evaluate(&self, wine: WinesBottlePrice) -> WinePricing {
+        let suggested_bottle_price = self.bottle_price(wine.base_price);
+        let suggested_glass_price = self.glass_price(suggested_bottle_price);
+        let suggested_display_price = format_price(suggested_bottle_price);
+
+        // A blank display price is treated as missing, like the wine list does
+        let display_price = wine
+            .display_price
+            .as_deref()
+            .map(str::trim)
+            .filter(|price| !price.is_empty());
+        let stored = display_price.and_then(parse_display_price);
+
+        let (status, deviation) = match stored {
+            None if display_price.is_none() => (PriceStatus::Missing, None),
+            None => (PriceStatus::Unparseable, None),
+            Some(stored) => {
+                let deviation = (stored - suggested_bottle_price) / suggested_bottle_price;
+                if !deviation.is_finite() || deviation.abs() > self.tolerance {
+                    (PriceStatus::Deviates, Some(deviation))
+                } else {
+                    (PriceStatus::Ok, Some(deviation))
+                }
+            }
+        };
+
+        WinePricing {
+            name: wine.name,
+            product_id: wine.product_id,
+            base_price: wine.base_price,
+            display_price: wine.display_price,
+            multiplier: self.multiplier(wine.base_price),
+            suggested_bottle_price,
+            suggested_glass_price,
+            suggested_display_price,
+            deviation,
+            status,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PriceBand {
+    pub max_cost: Option<i32>,
+    pub multiplier: f32,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Rounding {
+    /// Round to the nearest dollar (.00).
+    Whole,
+    /// Round to the nearest half dollar (.00 or .50).
+    Half,
+    /// Round up to the next price ending in `ending` cents, e.g. 0.99.
+    Psychological { ending: f32 },
+}
+
+impl Rounding {
+    fn validate_ending(ending: f32) -> Result<(), Box<dyn Error>> {
+        match (0.0..1.0).contains(&ending) {
+            true => Ok(()),
+            false => Err("Psychological price endings must be between 0.00 and 0.99".into()),
+        }
+    }
+
+    pub fn apply(&self, price: f32) -> f32 {
+        match self {
+            Rounding::Whole => price.round(),
+            Rounding::Half => (price * 2.0).round() / 2.0,
+            Rounding::Psychological { ending } => {
+                let candidate = price.floor() + ending;
+                let candidate = if candidate + 0.005 < price {
+                    candidate + 1.0
+                } else {
+                    candidate
+                };
+                (candidate * 100.0).round() / 100.0
+            }
+        }
+    }
+}
+
+/// Reads the bottle price out of a free-text display price such as `"48"`,
+/// `"$47.50"` or `"12 / 48"`, where the last amount is the bottle price.
+pub fn parse_display_price(display_price: &str) -> Option<f32> {
+    display_price
+        .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .map(|amount| amount.trim_matches('.'))
+        .filter(|amount| !amount.is_empty())
+        .filter_map(|amount| amount.parse::<f32>().ok())
+        .next_back()
+}
+
+pub fn format_price(price: f32) -> String {
+    if price.fract() == 0.0 {
+        format!("{}", price as i64)
+    } else {
+        format!("{:.2}", price)
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceStatus {
+    Ok,
+    Deviates,
+    Missing,
+    Unparseable,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WinePricing {
+    pub name: String,
+    pub product_id: i32,
+    pub base_price: i32,
+    pub display_price: Option<String>,
+    pub multiplier: f32,
+    pub suggested_bottle_price: f32,
+    pub suggested_glass_price: f32,
+    pub suggested_display_price: String,
+    /// Relative difference between the stored and suggested bottle price.
+    pub deviation: Option<f32>,
+    pub status: PriceStatus,
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,110 @@
+use super::*;
+
+fn wine(base_price: i32, display_price: Option<&str>) -> WinesBottlePrice {
+    WinesBottlePrice {
+        base_price,
+        display_price: display_price.map(str::to_string),
+        name: "Txakoli".to_string(),
+        product_id: 2001,
+    }
+}
+
+#[test]
+fn band_upper_bounds_are_inclusive() {
+    let policy = PricingPolicy::default();
+
+    assert_eq!(policy.multiplier(15), 3.0);
+    assert_eq!(policy.multiplier(16), 2.5);
+    assert_eq!(policy.multiplier(40), 2.5);
+    assert_eq!(policy.multiplier(41), 2.0);
+}
+
+#[test]
+fn cost_above_every_band_uses_the_last() {
+    let policy = PricingPolicy {
+        bands: vec![
+            PriceBand {
+                max_cost: Some(15),
+                multiplier: 3.0,
+            },
+            PriceBand {
+                max_cost: Some(40),
+                multiplier: 2.5,
+            },
+        ],
+        ..Default::default()
+    };
+
+    assert_eq!(policy.multiplier(100), 2.5);
+}
+
+#[test]
+fn rounding_edges() {
+    assert_eq!(Rounding::Whole.apply(47.5), 48.0);
+    assert_eq!(Rounding::Whole.apply(47.49), 47.0);
+    assert_eq!(Rounding::Half.apply(12.24), 12.0);
+    assert_eq!(Rounding::Half.apply(12.25), 12.5);
+    assert_eq!(Rounding::Half.apply(12.76), 13.0);
+
+    let ninety_nine = Rounding::Psychological { ending: 0.99 };
+    assert_eq!(ninety_nine.apply(47.0), 47.99);
+    assert_eq!(ninety_nine.apply(47.99), 47.99);
+    assert_eq!(ninety_nine.apply(48.0), 48.99);
+    let whole = Rounding::Psychological { ending: 0.0 };
+    assert_eq!(whole.apply(47.0), 47.0);
+    assert_eq!(whole.apply(47.2), 48.0);
+}
+
+#[test]
+fn psychological_ending_must_be_a_fraction() {
+    let policy = |ending| PricingPolicy {
+        bottle_rounding: Rounding::Psychological { ending },
+        ..Default::default()
+    };
+
+    assert!(policy(0.99).validate().is_ok());
+    assert!(policy(1.0).validate().is_err());
+    assert!(policy(-0.01).validate().is_err());
+}
+
+#[test]
+fn display_price_reads_the_last_amount() {
+    assert_eq!(parse_display_price("48"), Some(48.0));
+    assert_eq!(parse_display_price("$47.50"), Some(47.5));
+    assert_eq!(parse_display_price("12 / 48"), Some(48.0));
+    assert_eq!(parse_display_price("48."), Some(48.0));
+}
+
+#[test]
+fn display_price_without_an_amount_is_unparseable() {
+    assert_eq!(parse_display_price("Market price"), None);
+    assert_eq!(parse_display_price("..."), None);
+    assert_eq!(parse_display_price("$"), None);
+    assert_eq!(parse_display_price("1.2.3"), None);
+
+    let policy = PricingPolicy::default();
+    assert_eq!(
+        policy.evaluate(wine(16, Some("Market price"))).status,
+        PriceStatus::Unparseable
+    );
+    assert_eq!(
+        policy.evaluate(wine(16, Some("  "))).status,
+        PriceStatus::Missing
+    );
+    assert_eq!(policy.evaluate(wine(16, None)).status, PriceStatus::Missing);
+}
+
+#[test]
+fn deviation_is_flagged_beyond_the_tolerance() {
+    let policy = PricingPolicy::default();
+
+    // 16 × 2.5 = 40, and the default tolerance is 5%
+    let ok = policy.evaluate(wine(16, Some("$42")));
+    assert_eq!(ok.status, PriceStatus::Ok);
+    assert_eq!(ok.suggested_display_price, "40");
+    assert_eq!(ok.suggested_glass_price, 10.0);
+    assert_eq!(
+        policy.evaluate(wine(16, Some("43"))).status,
+        PriceStatus::Deviates
+    );
+}
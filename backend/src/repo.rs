@@ -0,0 +1,141 @@
+use async_trait::async_trait;
+use chrono::NaiveDate;
+use std::sync::Arc;
+use surrealdb::{engine::any::Any, Surreal};
+
+use crate::analytics::AnalyticsRange;
+use crate::exports::ExportFilter;
+use crate::model::commissions::{Commission, CommissionSummary};
+use crate::model::staff::{
+    EidName, StaffCsvData, StaffMember, StaffMemberForCreate, StaffMemberForUpdate,
+    StaffMemberPatch, StaffSummaryStats, SummaryParams, TipSummary,
+};
+use crate::model::tips::{
+    AnalyticsTip, NightInputs, TipNight, TippedDay, TipsCsvData, TipsSort, TipsTotals,
+};
+use crate::model::wines::WinesBottlePrice;
+use crate::pricing::PricingPolicy;
+use crate::wine_list::WineListEntry;
+
+mod surreal;
+#[cfg(test)]
+mod tests;
+
+pub use surreal::SurrealStore;
+
+pub type RepoResult<T> = Result<T, surrealdb::Error>;
+
+/// Whether a write failed because its record id, or a value in a unique
+/// index, is already taken.
+pub fn is_duplicate(err: &surrealdb::Error) -> bool {
+    use surrealdb::error::{Api, Db};
+
+    match err {
+        surrealdb::Error::Db(Db::RecordExists { .. } | Db::IndexExists { .. }) => true,
+        // A remote database only sends the message
+        surrealdb::Error::Api(Api::Query(message)) => {
+            message.contains("already exists") || message.contains("already contains")
+        }
+        _ => false,
+    }
+}
+
+/// Repositories the handlers reach the database through, shared as axum
+/// `State`.
+#[derive(Clone)]
+pub struct AppState {
+    pub staff: Arc<dyn StaffRepo>,
+    pub tips: Arc<dyn TipsRepo>,
+    pub wines: Arc<dyn WinesRepo>,
+    pub commissions: Arc<dyn CommissionsRepo>,
+}
+
+impl AppState {
+    pub fn surreal(db: Surreal<Any>) -> Self {
+        let store = Arc::new(SurrealStore::new(db));
+        Self {
+            staff: store.clone(),
+            tips: store.clone(),
+            wines: store.clone(),
+            commissions: store,
+        }
+    }
+}
+
+#[async_trait]
+pub trait StaffRepo: Send + Sync {
+    async fn list(&self, include_inactive: bool) -> RepoResult<Vec<StaffMember>>;
+    /// Active staff, by name.
+    async fn eid_names(&self) -> RepoResult<Vec<EidName>>;
+    async fn get(&self, eid: i32) -> RepoResult<Option<StaffMember>>;
+    /// Fails if a staff member with the same `eid` exists.
+    async fn create(&self, member: StaffMember) -> RepoResult<Option<StaffMember>>;
+    async fn update(&self, eid: i32, data: StaffMemberForUpdate)
+        -> RepoResult<Option<StaffMember>>;
+    async fn patch(&self, eid: i32, data: StaffMemberPatch) -> RepoResult<Option<StaffMember>>;
+    async fn set_active(&self, eid: i32, active: bool) -> RepoResult<Option<StaffMember>>;
+    async fn delete(&self, eid: i32) -> RepoResult<Option<StaffMember>>;
+    /// Some other staff member already holding one of `payroll_ids`.
+    async fn payroll_id_owner(&self, eid: i32, payroll_ids: Vec<String>)
+        -> RepoResult<Option<i32>>;
+    /// Creates or updates each member, keeping existing employment terms
+    /// and adding any new payroll IDs to the ones on record.
+    async fn import(&self, members: Vec<StaffMemberForCreate>) -> RepoResult<()>;
+}
+
+#[async_trait]
+pub trait TipsRepo: Send + Sync {
+    async fn page(
+        &self,
+        filter: ExportFilter,
+        sort: TipsSort,
+        limit: u32,
+        offset: u64,
+    ) -> RepoResult<(Vec<TippedDay>, TipsTotals)>;
+    async fn for_staff(&self, eid: i32) -> RepoResult<Vec<TippedDay>>;
+    /// The employee's latest `limit` nights.
+    async fn recent(&self, eid: i32, limit: u32) -> RepoResult<Vec<TipSummary>>;
+    async fn count_for_staff(&self, eid: i32) -> RepoResult<i64>;
+    /// Totals of the employee's nights, without the hourly averages.
+    async fn totals(&self, eid: i32, params: SummaryParams) -> RepoResult<StaffSummaryStats>;
+    /// The employee's nights between `start` and `end`, newest first.
+    async fn nights(
+        &self,
+        eid: i32,
+        start: Option<NaiveDate>,
+        end: Option<NaiveDate>,
+    ) -> RepoResult<Vec<TipNight>>;
+    async fn export_page(
+        &self,
+        filter: ExportFilter,
+        offset: usize,
+        limit: usize,
+    ) -> RepoResult<Vec<TipsCsvData>>;
+    async fn staff_export_page(
+        &self,
+        filter: ExportFilter,
+        offset: usize,
+        limit: usize,
+    ) -> RepoResult<Vec<StaffCsvData>>;
+    /// Everyone's nights in `range`.
+    async fn analytics_tips(&self, range: AnalyticsRange) -> RepoResult<Vec<AnalyticsTip>>;
+    /// Totals stored for the nights in `range` that were calculated with
+    /// their inputs kept.
+    async fn night_inputs(&self, range: AnalyticsRange) -> RepoResult<Vec<NightInputs>>;
+}
+
+#[async_trait]
+pub trait WinesRepo: Send + Sync {
+    async fn bottle_prices(&self) -> RepoResult<Vec<WinesBottlePrice>>;
+    /// Wines still on sale, by name, for the printed list.
+    async fn list_entries(&self) -> RepoResult<Vec<WineListEntry>>;
+    async fn pricing_policy(&self) -> RepoResult<Option<PricingPolicy>>;
+    async fn save_pricing_policy(&self, policy: PricingPolicy)
+        -> RepoResult<Option<PricingPolicy>>;
+}
+
+#[async_trait]
+pub trait CommissionsRepo: Send + Sync {
+    async fn list(&self) -> RepoResult<Vec<CommissionSummary>>;
+    async fn create(&self, commission: Commission) -> RepoResult<Vec<Commission>>;
+}
@@ -0,0 +1,448 @@
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use futures::future::join_all;
+use serde::Serialize;
+use surrealdb::{engine::any::Any, Surreal};
+
+use super::{CommissionsRepo, RepoResult, StaffRepo, TipsRepo, WinesRepo};
+use crate::analytics::AnalyticsRange;
+use crate::exports::ExportFilter;
+use crate::model::commissions::{Commission, CommissionSummary};
+use crate::model::staff::{
+    EidName, StaffCsvData, StaffMember, StaffMemberForCreate, StaffMemberForUpdate,
+    StaffMemberPatch, StaffSummaryStats, SummaryParams, TipSummary,
+};
+use crate::model::tips::{
+    AnalyticsTip, NightInputs, TipNight, TippedDay, TipsCsvData, TipsSort, TipsTotals,
+};
+use crate::model::wines::WinesBottlePrice;
+use crate::pricing::PricingPolicy;
+use crate::wine_list::WineListEntry;
+
+/// Repositories backed by SurrealDB, over whichever engine `db` was
+/// connected with.
+pub struct SurrealStore {
+    db: Surreal<Any>,
+}
+
+impl SurrealStore {
+    pub fn new(db: Surreal<Any>) -> Self {
+        Self { db }
+    }
+
+    async fn merge_staff_member<T: Serialize + Send + 'static>(
+        &self,
+        eid: i32,
+        data: T,
+    ) -> RepoResult<Option<StaffMember>> {
+        #[derive(Serialize)]
+        struct StaffMemberMerge<T> {
+            #[serde(flatten)]
+            data: T,
+            modified: DateTime<Utc>,
+        }
+
+        let existing: Option<StaffMember> = self.db.select(("staff", eid)).await?;
+        if existing.is_none() {
+            return Ok(None);
+        }
+
+        self.db
+            .update(("staff", eid))
+            .merge(StaffMemberMerge {
+                data,
+                modified: Utc::now(),
+            })
+            .await
+    }
+}
+
+#[async_trait]
+impl StaffRepo for SurrealStore {
+    async fn list(&self, include_inactive: bool) -> RepoResult<Vec<StaffMember>> {
+        self.db
+            .query(
+                "
+                SELECT * from staff WHERE $include_inactive OR active != false;
+                ",
+            )
+            .bind(("include_inactive", include_inactive))
+            .await?
+            .take(0)
+    }
+
+    async fn eid_names(&self) -> RepoResult<Vec<EidName>> {
+        self.db
+            .query(
+                "
+                SELECT name, eid FROM staff WHERE active != false ORDER BY name ASC;
+                ",
+            )
+            .await?
+            .take(0)
+    }
+
+    async fn get(&self, eid: i32) -> RepoResult<Option<StaffMember>> {
+        self.db
+            .query(
+                "
+                SELECT * FROM staff WHERE eid=$eid;
+                ",
+            )
+            .bind(("eid", eid))
+            .await?
+            .take(0)
+    }
+
+    async fn create(&self, member: StaffMember) -> RepoResult<Option<StaffMember>> {
+        self.db.create(("staff", member.eid)).content(member).await
+    }
+
+    async fn update(
+        &self,
+        eid: i32,
+        data: StaffMemberForUpdate,
+    ) -> RepoResult<Option<StaffMember>> {
+        self.merge_staff_member(eid, data).await
+    }
+
+    async fn patch(&self, eid: i32, data: StaffMemberPatch) -> RepoResult<Option<StaffMember>> {
+        self.merge_staff_member(eid, data).await
+    }
+
+    async fn set_active(&self, eid: i32, active: bool) -> RepoResult<Option<StaffMember>> {
+        let members: Vec<StaffMember> = self
+            .db
+            .query(
+                "
+                UPDATE type::thing('staff', $eid) SET
+                    active = $active,
+                    deactivated = $deactivated,
+                    modified = $now
+                WHERE eid != NONE;
+                ",
+            )
+            .bind(("eid", eid))
+            .bind(("active", active))
+            .bind(("deactivated", (!active).then(Utc::now)))
+            .bind(("now", Utc::now()))
+            .await?
+            .take(0)?;
+        Ok(members.into_iter().next())
+    }
+
+    async fn delete(&self, eid: i32) -> RepoResult<Option<StaffMember>> {
+        self.db.delete(("staff", eid)).await
+    }
+
+    async fn payroll_id_owner(
+        &self,
+        eid: i32,
+        payroll_ids: Vec<String>,
+    ) -> RepoResult<Option<i32>> {
+        let taken: Vec<i32> = self
+            .db
+            .query(
+                "
+                SELECT VALUE eid FROM staff WHERE eid != $eid AND payroll_ids CONTAINSANY $payroll_ids;
+                ",
+            )
+            .bind(("eid", eid))
+            .bind(("payroll_ids", payroll_ids))
+            .await?
+            .take(0)?;
+        Ok(taken.first().copied())
+    }
+
+    async fn import(&self, members: Vec<StaffMemberForCreate>) -> RepoResult<()> {
+        let results = join_all(members.into_iter().map(|member| async move {
+            self.db
+                .query(
+                    "
+                    UPDATE type::thing('staff', $eid) SET
+                        name = $name,
+                        card_id = $card_id,
+                        eid = $eid,
+                        payroll_ids = array::union(payroll_ids ?? [], $payroll_ids),
+                        active = active ?? true,
+                        created = created ?? $now,
+                        modified = $now;
+                    ",
+                )
+                .bind(("eid", member.eid))
+                .bind(("name", member.name))
+                .bind(("card_id", member.card_id))
+                .bind(("payroll_ids", member.payroll_ids))
+                .bind(("now", Utc::now()))
+                .await?
+                .check()
+        }))
+        .await;
+
+        for result in results {
+            result?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl TipsRepo for SurrealStore {
+    async fn page(
+        &self,
+        filter: ExportFilter,
+        sort: TipsSort,
+        limit: u32,
+        offset: u64,
+    ) -> RepoResult<(Vec<TippedDay>, TipsTotals)> {
+        let mut response = self
+            .db
+            .query(format!(
+                "
+                SELECT * FROM tips
+                WHERE (!$start OR date >= $start) AND (!$end OR date <= $end)
+                    AND (!$eid OR eid = $eid) AND (!$role OR role = $role)
+                ORDER BY {}
+                LIMIT $limit START $offset;
+                SELECT count() AS rows, math::sum(net_tips) AS net_tips,
+                    math::sum(duration) AS hours, math::sum(total_pay_for_night) AS total_pay
+                FROM tips
+                WHERE (!$start OR date >= $start) AND (!$end OR date <= $end)
+                    AND (!$eid OR eid = $eid) AND (!$role OR role = $role)
+                GROUP ALL;
+                ",
+                sort.order_by()
+            ))
+            .bind(filter)
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?;
+
+        let data: Vec<TippedDay> = response.take(0)?;
+        let totals: Option<TipsTotals> = response.take(1)?;
+        Ok((data, totals.unwrap_or_default()))
+    }
+
+    async fn for_staff(&self, eid: i32) -> RepoResult<Vec<TippedDay>> {
+        self.db
+            .query(
+                "
+                SELECT * from tips WHERE eid=$eid ORDER BY name ASC;
+                ",
+            )
+            .bind(("eid", eid))
+            .await?
+            .take(0)
+    }
+
+    async fn recent(&self, eid: i32, limit: u32) -> RepoResult<Vec<TipSummary>> {
+        self.db
+            .query(
+                "
+                SELECT date, net_tips FROM tips WHERE eid=$eid ORDER BY date DESC LIMIT $limit;
+                ",
+            )
+            .bind(("eid", eid))
+            .bind(("limit", limit))
+            .await?
+            .take(0)
+    }
+
+    async fn count_for_staff(&self, eid: i32) -> RepoResult<i64> {
+        let count: Option<i64> = self
+            .db
+            .query(
+                "
+                RETURN count(SELECT id FROM tips WHERE eid=$eid);
+                ",
+            )
+            .bind(("eid", eid))
+            .await?
+            .take(0)?;
+        Ok(count.unwrap_or_default())
+    }
+
+    async fn totals(&self, eid: i32, params: SummaryParams) -> RepoResult<StaffSummaryStats> {
+        let totals: Option<StaffSummaryStats> = self
+            .db
+            .query(
+                "
+                SELECT count() AS nights,
+                    math::sum(net_tips) AS net_tips_sum,
+                    math::sum(duration) AS total_hours,
+                    math::sum(total_pay_for_night - net_tips) AS total_wages,
+                    math::sum(total_pay_for_night) AS total_pay
+                FROM tips
+                WHERE eid=$eid AND (!$start OR date >= $start) AND (!$end OR date <= $end)
+                    AND (!$role OR role = $role)
+                GROUP ALL;
+                ",
+            )
+            .bind(("eid", eid))
+            .bind(params)
+            .await?
+            .take(0)?;
+        Ok(totals.unwrap_or_default())
+    }
+
+    async fn nights(
+        &self,
+        eid: i32,
+        start: Option<NaiveDate>,
+        end: Option<NaiveDate>,
+    ) -> RepoResult<Vec<TipNight>> {
+        self.db
+            .query(
+                "
+                SELECT date, role, duration, net_tips, total_pay_for_night,
+                    pool_share ?? 0 AS pool_share,
+                    tip_out_paid ?? 0 AS tip_out_paid,
+                    tip_out_received ?? 0 AS tip_out_received
+                FROM tips
+                WHERE eid=$eid AND (!$start OR date >= $start) AND (!$end OR date <= $end)
+                ORDER BY date DESC;
+                ",
+            )
+            .bind(("eid", eid))
+            .bind(("start", start))
+            .bind(("end", end))
+            .await?
+            .take(0)
+    }
+
+    async fn export_page(
+        &self,
+        filter: ExportFilter,
+        offset: usize,
+        limit: usize,
+    ) -> RepoResult<Vec<TipsCsvData>> {
+        self.db
+            .query(
+                "
+                SELECT date, name, eid, role, duration, net_tips, total_pay_for_night,
+                    hourly_pay_for_night, tipped_hour_for_night,
+                    pool_share ?? 0 AS pool_share,
+                    tip_out_paid ?? 0 AS tip_out_paid,
+                    tip_out_received ?? 0 AS tip_out_received
+                FROM tips
+                WHERE (!$start OR date >= $start) AND (!$end OR date <= $end)
+                    AND (!$eid OR eid = $eid) AND (!$role OR role = $role)
+                ORDER BY date ASC, name ASC, eid ASC
+                LIMIT $limit START $offset;
+                ",
+            )
+            .bind(filter)
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?
+            .take(0)
+    }
+
+    async fn staff_export_page(
+        &self,
+        filter: ExportFilter,
+        offset: usize,
+        limit: usize,
+    ) -> RepoResult<Vec<StaffCsvData>> {
+        self.db
+            .query(
+                "
+                SELECT name, eid, role, date, net_tips, total_pay_for_night, hourly_pay_for_night
+                FROM tips
+                WHERE (!$start OR date >= $start) AND (!$end OR date <= $end)
+                    AND (!$eid OR eid = $eid) AND (!$role OR role = $role)
+                ORDER BY date DESC, name ASC, eid ASC
+                LIMIT $limit START $offset;
+                ",
+            )
+            .bind(filter)
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?
+            .take(0)
+    }
+
+    async fn analytics_tips(&self, range: AnalyticsRange) -> RepoResult<Vec<AnalyticsTip>> {
+        self.db
+            .query(
+                "
+                SELECT date, eid, name, role, duration, net_tips FROM tips
+                WHERE (!$start OR date >= $start) AND (!$end OR date <= $end);
+                ",
+            )
+            .bind(range)
+            .await?
+            .take(0)
+    }
+
+    async fn night_inputs(&self, range: AnalyticsRange) -> RepoResult<Vec<NightInputs>> {
+        self.db
+            .query(
+                "
+                SELECT date, total_sales, go_tab_tips + cash_tips AS total_tips FROM nights
+                WHERE (!$start OR date >= $start) AND (!$end OR date <= $end);
+                ",
+            )
+            .bind(range)
+            .await?
+            .take(0)
+    }
+}
+
+#[async_trait]
+impl WinesRepo for SurrealStore {
+    async fn bottle_prices(&self) -> RepoResult<Vec<WinesBottlePrice>> {
+        self.db
+            .query(
+                "
+                SELECT base_price, display_price, name, product_id from wines ORDER BY name ASC;
+                ",
+            )
+            .await?
+            .take(0)
+    }
+
+    async fn list_entries(&self) -> RepoResult<Vec<WineListEntry>> {
+        self.db
+            .query(
+                "
+                SELECT name, product_id, base_price, display_price, category, region, vintage
+                FROM wines WHERE active != false ORDER BY name ASC;
+                ",
+            )
+            .await?
+            .take(0)
+    }
+
+    async fn pricing_policy(&self) -> RepoResult<Option<PricingPolicy>> {
+        self.db.select(("pricing_policy", "current")).await
+    }
+
+    async fn save_pricing_policy(
+        &self,
+        policy: PricingPolicy,
+    ) -> RepoResult<Option<PricingPolicy>> {
+        self.db
+            .update(("pricing_policy", "current"))
+            .content(policy)
+            .await
+    }
+}
+
+#[async_trait]
+impl CommissionsRepo for SurrealStore {
+    async fn list(&self) -> RepoResult<Vec<CommissionSummary>> {
+        self.db
+            .query(
+                "
+                SELECT name.name AS name, wine.name AS wine, amount, wine.product_id AS product_id, date FROM commissions ORDER BY name ASC;
+                ",
+            )
+            .await?
+            .take(0)
+    }
+
+    async fn create(&self, commission: Commission) -> RepoResult<Vec<Commission>> {
+        self.db.create("commissions").content(commission).await
+    }
+}
@@ -0,0 +1,111 @@
+use chrono::Utc;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use super::*;
+use crate::model::staff::Employment;
+use crate::testing::{app_state, block_on, STAFF};
+
+fn from_json<T: DeserializeOwned>(value: Value) -> T {
+    serde_json::from_value(value).expect("Could not build the record")
+}
+
+fn member(eid: i32, name: &str) -> StaffMember {
+    StaffMember {
+        name: name.to_string(),
+        card_id: format!("9000{}", eid),
+        eid,
+        payroll_ids: vec![format!("P{}", eid)],
+        employment: Employment::default(),
+        active: true,
+        deactivated: None,
+        created: Utc::now(),
+        modified: Utc::now(),
+    }
+}
+
+#[test]
+fn duplicate_eid_is_reported_as_duplicate() {
+    block_on(async {
+        let state = app_state().await;
+        state
+            .staff
+            .create(member(201, "Fay Ito"))
+            .await
+            .expect("Could not create staff member");
+
+        let err = state
+            .staff
+            .create(member(201, "Gus Park"))
+            .await
+            .expect_err("A second staff member with the same eid was created");
+        assert!(is_duplicate(&err));
+    });
+}
+
+#[test]
+fn each_in_memory_state_has_its_own_data() {
+    block_on(async {
+        let first = app_state().await;
+        first
+            .staff
+            .create(member(202, "Hal Moss"))
+            .await
+            .expect("Could not create staff member");
+
+        let second = app_state().await;
+        assert_eq!(second.staff.list(true).await.unwrap().len(), STAFF.len());
+        assert_eq!(first.staff.list(true).await.unwrap().len(), STAFF.len() + 1);
+    });
+}
+
+#[test]
+fn patch_clears_employment_terms_sent_as_null() {
+    block_on(async {
+        let state = app_state().await;
+        let mut fay = member(203, "Fay Ito");
+        fay.employment.hourly_wage = Some(5.5);
+        fay.employment.point_weight = 0.5;
+        state.staff.create(fay).await.unwrap();
+
+        let patch = from_json(json!({ "employment": { "hourly_wage": null } }));
+        let patched = state.staff.patch(203, patch).await.unwrap().unwrap();
+
+        assert_eq!(patched.employment.hourly_wage, None);
+        assert_eq!(patched.employment.point_weight, 0.5);
+    });
+}
+
+#[test]
+fn import_adds_payroll_ids_and_keeps_employment() {
+    block_on(async {
+        let state = app_state().await;
+        let mut fay = member(204, "Fay Ito");
+        fay.employment.hourly_wage = Some(5.5);
+        state.staff.create(fay).await.unwrap();
+
+        let rows = vec![
+            from_json(json!({
+                "name": "Fay Ito-Lane", "card_id": "9000204", "eid": 204, "payroll_ids": ["Q204"]
+            })),
+            from_json(json!({ "name": "Ivy Ng", "card_id": "9000205", "eid": 205 })),
+        ];
+        state.staff.import(rows).await.unwrap();
+
+        let fay = state.staff.get(204).await.unwrap().unwrap();
+        assert_eq!(fay.name, "Fay Ito-Lane");
+        assert_eq!(fay.payroll_ids, vec!["P204", "Q204"]);
+        assert_eq!(fay.employment.hourly_wage, Some(5.5));
+
+        let ivy = state.staff.get(205).await.unwrap().unwrap();
+        assert!(ivy.active);
+        assert_eq!(
+            state
+                .staff
+                .payroll_id_owner(205, vec!["Q204".to_string()])
+                .await
+                .unwrap(),
+            Some(204)
+        );
+    });
+}
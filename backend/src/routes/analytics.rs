@@ -0,0 +1,119 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use chrono::{Datelike, NaiveDate, Utc};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::analytics::{self, AnalyticsRange, Breakdown, FairnessParams};
+use crate::repo::AppState;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/analytics/nights", get(nights))
+        .route("/analytics/breakdown", get(breakdown))
+        .route("/analytics/year-over-year", get(year_over_year))
+}
+
+/// Reports managers use to check the pool is being split fairly.
+pub fn manager_routes() -> Router<AppState> {
+    Router::new().route("/analytics/fairness", get(fairness))
+}
+
+pub async fn nights(
+    State(state): State<AppState>,
+    Query(range): Query<AnalyticsRange>,
+) -> impl IntoResponse {
+    match analytics::nights(&*state.tips, &range).await {
+        Ok(nights) => Json(nights).into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err.to_string()
+            })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn breakdown(
+    State(state): State<AppState>,
+    Query(params): Query<BreakdownParams>,
+) -> impl IntoResponse {
+    let range = AnalyticsRange {
+        start: params.start,
+        end: params.end,
+    };
+
+    match analytics::breakdown(&*state.tips, &range, params.by).await {
+        Ok(groups) => Json(groups).into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err.to_string()
+            })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn year_over_year(
+    State(state): State<AppState>,
+    Query(params): Query<YearOverYearParams>,
+) -> impl IntoResponse {
+    let year = params.year.unwrap_or_else(|| Utc::now().year());
+    if analytics::year_over_year_range(year).is_none() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(json!({
+            "error": format!("Invalid year: {}", year)
+            })),
+        )
+            .into_response();
+    }
+
+    match analytics::year_over_year(&*state.tips, year).await {
+        Ok(comparison) => Json(comparison).into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err.to_string()
+            })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn fairness(
+    State(state): State<AppState>,
+    Query(params): Query<FairnessParams>,
+) -> impl IntoResponse {
+    match analytics::fairness(&*state.tips, &params).await {
+        Ok(report) => Json(report).into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err.to_string()
+            })),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct BreakdownParams {
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+    by: Breakdown,
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct YearOverYearParams {
+    year: Option<i32>,
+}
@@ -0,0 +1,131 @@
+use axum::{
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::auth::{self, CurrentUser};
+
+/// Routes reachable without a session.
+pub fn public_routes() -> Router {
+    Router::new()
+        .route("/auth/setup", post(setup))
+        .route("/auth/login", post(login))
+}
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/auth/logout", post(logout))
+        .route("/auth/me", get(me))
+        .route("/auth/stream-token", post(stream_token))
+}
+
+/// Creates the first owner account. Only allowed while there are no users.
+pub async fn setup(Json(data): Json<Credentials>) -> impl IntoResponse {
+    if data.username.trim().is_empty() || data.password.len() < 8 {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({
+            "error": "A username and a password of at least 8 characters are required"
+            })),
+        )
+            .into_response();
+    }
+
+    match auth::set_up_owner(data.username.trim(), &data.password)
+        .await
+        .map_err(|err| err.to_string())
+    {
+        Ok(Some(user)) => (StatusCode::CREATED, Json(user)).into_response(),
+        Ok(None) => (
+            StatusCode::CONFLICT,
+            Json(json!({
+            "error": "Setup has already been completed"
+            })),
+        )
+            .into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err
+            })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn login(Json(data): Json<Credentials>) -> impl IntoResponse {
+    match auth::login(&data.username, &data.password).await {
+        Ok(Some((token, expires, user))) => Json(LoginResponse {
+            token,
+            expires,
+            user,
+        })
+        .into_response(),
+        Ok(None) => (
+            StatusCode::UNAUTHORIZED,
+            Json(json!({
+            "error": "Invalid username or password"
+            })),
+        )
+            .into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err.to_string()
+            })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn logout(headers: HeaderMap) -> impl IntoResponse {
+    let Some(token) = auth::bearer_token(&headers) else {
+        return StatusCode::NO_CONTENT.into_response();
+    };
+
+    match auth::logout(token).await.map_err(|err| err.to_string()) {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err
+            })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn me(user: CurrentUser) -> impl IntoResponse {
+    Json(user)
+}
+
+/// A token for opening `/events` or `/jobs/:id/events` as `?token=`, good
+/// for a minute. Fetch a new one to reconnect.
+pub async fn stream_token(user: CurrentUser) -> impl IntoResponse {
+    let (token, expires) = auth::stream_token(&user);
+    Json(StreamTokenResponse { token, expires })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Credentials {
+    username: String,
+    password: String,
+}
+
+#[derive(Debug, Serialize)]
+struct StreamTokenResponse {
+    token: String,
+    expires: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+struct LoginResponse {
+    token: String,
+    expires: DateTime<Utc>,
+    user: CurrentUser,
+}
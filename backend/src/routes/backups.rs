@@ -0,0 +1,80 @@
+use axum::{
+    body::Bytes, extract::Query, http::StatusCode, response::IntoResponse, routing::post, Json,
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::auth::CurrentUser;
+use crate::backup;
+use crate::downloads::{self, DownloadLink};
+use crate::DB;
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/admin/backup", post(back_up))
+        .route("/admin/restore", post(restore))
+}
+
+pub async fn back_up(user: CurrentUser) -> impl IntoResponse {
+    let export = backup::export_file().await.map_err(|err| err.to_string());
+    let link = match export {
+        Ok(export) => downloads::store(export, &user.username)
+            .await
+            .map_err(|err| err.to_string()),
+        Err(err) => Err(err),
+    };
+
+    match link {
+        Ok(backup_link) => Json(BackupResponse { backup_link }).into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err
+            })),
+        )
+            .into_response(),
+    }
+}
+
+/// Restores the archive in the request body, or with `dry_run=true` only
+/// reports what restoring it would change.
+pub async fn restore(Query(params): Query<RestoreParams>, body: Bytes) -> impl IntoResponse {
+    let archive = match backup::parse(&body) {
+        Ok(archive) => archive,
+        Err(err) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({
+                "error": err.to_string()
+                })),
+            )
+                .into_response()
+        }
+    };
+
+    match backup::restore(&DB, archive, params.dry_run)
+        .await
+        .map_err(|err| err.to_string())
+    {
+        Ok(diff) => Json(diff).into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err
+            })),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct RestoreParams {
+    dry_run: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct BackupResponse {
+    backup_link: DownloadLink,
+}
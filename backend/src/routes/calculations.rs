@@ -1,67 +1,273 @@
-use axum::{extract::Multipart, response::IntoResponse, routing::post, Json, Router};
+use axum::{
+    body::Bytes,
+    extract::{Multipart, Path},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
 use chrono::NaiveDate;
 use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
 
-use crate::calculations::{self, LaborReportUpload, Summary, TippedDayCalculation};
+use crate::auth::CurrentUser;
+use crate::calculations::{
+    self, policy, BatchRecalculation, LaborReportUpload, NightAmendment, Recalculation,
+};
+use crate::downloads::{self, DownloadLink};
+use crate::jobs::{self, JobInput};
 
 pub fn routes() -> Router {
-    Router::new().route("/calculations", post(calculate))
+    Router::new()
+        .route("/calculations", post(calculate))
+        .route("/calculations/policies", get(policies))
+        .route("/calculations/recalculate", post(recalculate_range))
+        .route("/calculations/:date", get(night))
+        .route("/calculations/:date/recalculate", post(recalculate))
 }
 
-pub async fn calculate(mut data: Multipart) -> impl IntoResponse {
-    let mut labor_report_data = LaborReportUpload::default();
-    let mut summary = Summary::default();
-    let mut tips = Vec::new();
-
-    while let Some(field) = data.next_field().await.unwrap() {
-        let name = field.name().unwrap();
-        match name {
-            "date" => {
-                labor_report_data.date =
-                    NaiveDate::parse_from_str(&field.text().await.unwrap(), "%Y-%m-%d").unwrap()
-            }
-            "totalSales" => {
-                labor_report_data.total_sales = field.text().await.unwrap().parse::<f32>().unwrap();
-            }
-            "gotabTips" => {
-                labor_report_data.go_tab_tips = field.text().await.unwrap().parse::<f32>().unwrap();
-            }
-            "cashTips" => {
-                labor_report_data.cash_tips = field.text().await.unwrap().parse::<f32>().unwrap();
-            }
-            "laborReport" => {
-                (
-                    labor_report_data.data_csv_link,
-                    labor_report_data.template_csv_link,
-                    summary,
-                    tips,
-                ) = calculations::read_csv(
-                    labor_report_data.clone(),
-                    &field.bytes().await.unwrap(),
-                )
-                .await
-                .expect("Could not calculate");
-            }
+pub async fn calculate(user: CurrentUser, data: Multipart) -> impl IntoResponse {
+    let (night, labor_report) = match read_upload(data).await {
+        Ok(upload) => upload,
+        Err(response) => return response,
+    };
+
+    match jobs::enqueue(
+        JobInput::Calculation { night },
+        Some(&labor_report),
+        &user.username,
+    )
+    .await
+    .map_err(|err| err.to_string())
+    {
+        Ok(job) => (StatusCode::ACCEPTED, Json(job)).into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": format!("Could not queue the calculation: {}", err)
+            })),
+        )
+            .into_response(),
+    }
+}
+
+/// Reads the night's inputs and labor report from the upload form.
+async fn read_upload(mut data: Multipart) -> Result<(LaborReportUpload, Bytes), Response> {
+    let mut date = None;
+    let mut total_sales = None;
+    let mut go_tab_tips = None;
+    let mut cash_tips = None;
+    let mut confirmed_matches = HashMap::new();
+    let mut overrides = Vec::new();
+    let mut labor_report = None;
+
+    loop {
+        let field = match data.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(err) => return Err(bad_request(format!("Could not read the upload: {}", err))),
+        };
+        let name = field.name().unwrap_or_default().to_string();
+        if name == "laborReport" {
+            labor_report = Some(
+                field
+                    .bytes()
+                    .await
+                    .map_err(|err| bad_request(format!("Could not read laborReport: {}", err)))?,
+            );
+            continue;
+        }
+        let text = field
+            .text()
+            .await
+            .map_err(|err| bad_request(format!("Could not read {}: {}", name, err)))?;
+        match name.as_str() {
+            "date" => date = Some(text),
+            "totalSales" => total_sales = Some(text),
+            "gotabTips" => go_tab_tips = Some(text),
+            "cashTips" => cash_tips = Some(text),
+            "confirmedMatches" => match serde_json::from_str(&text) {
+                Ok(matches) => confirmed_matches = matches,
+                Err(err) => {
+                    return Err((
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        Json(json!({
+                        "error": format!("Invalid confirmedMatches: {}", err)
+                        })),
+                    )
+                        .into_response())
+                }
+            },
+            "overrides" => match serde_json::from_str(&text) {
+                Ok(parsed) => overrides = parsed,
+                Err(err) => {
+                    return Err((
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        Json(json!({
+                        "error": format!("Invalid overrides: {}", err)
+                        })),
+                    )
+                        .into_response())
+                }
+            },
             _ => continue,
         };
     }
 
-    let response = CalculationsResponse {
-        calculations_link: labor_report_data.data_csv_link,
-        template_link: labor_report_data.template_csv_link,
-        summary,
-        tips,
+    let Some(labor_report) = labor_report else {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({
+            "error": "Missing laborReport"
+            })),
+        )
+            .into_response());
+    };
+
+    let date = required("date", date).map_err(bad_request)?;
+    let night = LaborReportUpload {
+        date: NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+            .map_err(|_| bad_request(format!("Invalid date: {}", date)))?,
+        total_sales: amount("totalSales", total_sales).map_err(bad_request)?,
+        go_tab_tips: amount("gotabTips", go_tab_tips).map_err(bad_request)?,
+        cash_tips: amount("cashTips", cash_tips).map_err(bad_request)?,
+        confirmed_matches,
+        overrides,
+    };
+    Ok((night, labor_report))
+}
+
+fn required(name: &str, value: Option<String>) -> Result<String, String> {
+    value.ok_or_else(|| format!("Missing {}", name))
+}
+
+fn amount(name: &str, value: Option<String>) -> Result<f32, String> {
+    let value = required(name, value)?;
+    match value.trim().parse::<f32>() {
+        Ok(amount) if amount.is_finite() => Ok(amount),
+        _ => Err(format!("Invalid {}: {}", name, value)),
+    }
+}
+
+fn bad_request(error: String) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        Json(json!({
+        "error": error
+        })),
+    )
+        .into_response()
+}
+
+pub async fn night(Path(date): Path<NaiveDate>) -> impl IntoResponse {
+    match calculations::load_night(date)
+        .await
+        .map_err(|err| err.to_string())
+    {
+        Ok(Some(night)) => Json(night).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err
+            })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn recalculate(
+    user: CurrentUser,
+    Path(date): Path<NaiveDate>,
+    Json(amendment): Json<NightAmendment>,
+) -> impl IntoResponse {
+    let recalculation = match calculations::recalculate(date, amendment)
+        .await
+        .map_err(|err| err.to_string())
+    {
+        Ok(Some(recalculation)) => recalculation,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({
+                "error": err
+                })),
+            )
+                .into_response()
+        }
     };
 
-    dbg!(&response);
+    let mut exports = vec![recalculation.calculations.clone()];
+    exports.extend(recalculation.adjustment_template.clone());
+    let links = downloads::store_all(exports, &user.username)
+        .await
+        .map_err(|err| err.to_string());
+    let mut links = match links {
+        Ok(links) => links.into_iter(),
+        Err(err) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({
+                "error": format!("Could not save calculation files: {}", err)
+                })),
+            )
+                .into_response()
+        }
+    };
 
-    Json(response).into_response()
+    Json(RecalculationResponse {
+        calculations_link: links.next(),
+        adjustment_link: links.next(),
+        recalculation,
+    })
+    .into_response()
+}
+
+pub async fn policies() -> impl IntoResponse {
+    Json(json!({
+        "current": policy::current().version,
+        "policies": policy::POLICIES
+    }))
+}
+
+/// Queues calculating the stored nights in a date range again under a tip
+/// policy, committing the new tips when asked to.
+pub async fn recalculate_range(
+    user: CurrentUser,
+    Json(batch): Json<BatchRecalculation>,
+) -> impl IntoResponse {
+    if let Err(err) = batch.policy() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({
+            "error": err
+            })),
+        )
+            .into_response();
+    }
+
+    match jobs::enqueue(JobInput::BatchRecalculation { batch }, None, &user.username)
+        .await
+        .map_err(|err| err.to_string())
+    {
+        Ok(job) => (StatusCode::ACCEPTED, Json(job)).into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": format!("Could not queue the recalculation: {}", err)
+            })),
+        )
+            .into_response(),
+    }
 }
 
 #[derive(Debug, Serialize)]
-pub struct CalculationsResponse {
-    calculations_link: String,
-    template_link: String,
-    summary: Summary,
-    tips: Vec<TippedDayCalculation>,
+pub struct RecalculationResponse {
+    calculations_link: Option<DownloadLink>,
+    /// Payout file for the differences; absent when nobody is owed more.
+    adjustment_link: Option<DownloadLink>,
+    #[serde(flatten)]
+    recalculation: Recalculation,
 }
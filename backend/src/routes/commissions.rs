@@ -1,30 +1,19 @@
-use axum::{http::StatusCode, response::IntoResponse, routing::get, Json, Router};
-use chrono::{DateTime, NaiveDate, Utc};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::get, Json, Router};
+use chrono::{NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use surrealdb::sql::Thing;
 
-use crate::DB;
+use crate::model::commissions::Commission;
+use crate::repo::AppState;
 
-pub fn routes() -> Router {
+pub fn routes() -> Router<AppState> {
     Router::new().route("/commissions", get(wines_bottle_price).post(new_commission))
 }
 
-pub async fn wines_bottle_price() -> impl IntoResponse {
-    let commissions = DB
-        .query(
-            "
-            SELECT name.name AS name, wine.name AS wine, amount, wine.product_id AS product_id, date FROM commissions ORDER BY name ASC;
-            ",
-        )
-        .await;
-
-    match commissions {
-        Ok(mut commissions) => {
-            let commissions: Vec<CommissionSummary> =
-                commissions.take(0).expect("Could not get tips data");
-            Json(commissions).into_response()
-        }
+pub async fn wines_bottle_price(State(state): State<AppState>) -> impl IntoResponse {
+    match state.commissions.list().await {
+        Ok(commissions) => Json(commissions).into_response(),
         Err(err) => (
             StatusCode::OK,
             Json(json!({
@@ -35,53 +24,72 @@ pub async fn wines_bottle_price() -> impl IntoResponse {
     }
 }
 
-pub async fn new_commission(Json(data): Json<CommissionForCreate>) -> impl IntoResponse {
-    let staff: Vec<Commission> = DB
-        .create("commissions")
-        .content(Commission {
-            name: Thing {
-                tb: "staff".into(),
-                id: data.eid.parse::<i32>().expect("Could not parse eid").into(),
-            },
-            wine: Thing {
-                tb: "wines".into(),
-                id: data
-                    .product_id
-                    .parse::<i32>()
-                    .expect("Could not parse product_id")
-                    .into(),
-            },
-            amount: 1234,
-            date: NaiveDate::from_ymd_opt(2024, 6, 6).unwrap(),
-            created: Utc::now(),
-            modified: Utc::now(),
-        })
-        .await
-        .expect("Could not create new commission");
+pub async fn new_commission(
+    State(state): State<AppState>,
+    Json(data): Json<CommissionForCreate>,
+) -> impl IntoResponse {
+    let commission = match data.commission() {
+        Ok(commission) => commission,
+        Err(err) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({
+                "error": err
+                })),
+            )
+                .into_response()
+        }
+    };
 
-    Json(staff).into_response()
+    match state.commissions.create(commission).await {
+        Ok(created) => Json(created).into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err
+            })),
+        )
+            .into_response(),
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CommissionForCreate {
     eid: String,
     product_id: String,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct Commission {
-    name: Thing,
-    wine: Thing,
     amount: i32,
     date: NaiveDate,
-    created: DateTime<Utc>,
-    modified: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct CommissionSummary {
-    name: String,
-    wine: String,
-    amount: i32,
-    date: NaiveDate,
+impl CommissionForCreate {
+    fn commission(self) -> Result<Commission, String> {
+        let eid = self
+            .eid
+            .trim()
+            .parse::<i32>()
+            .map_err(|_| format!("Invalid eid: {}", self.eid))?;
+        let product_id = self
+            .product_id
+            .trim()
+            .parse::<i32>()
+            .map_err(|_| format!("Invalid product_id: {}", self.product_id))?;
+        if self.amount < 0 {
+            return Err(format!("Invalid amount: {}", self.amount));
+        }
+
+        Ok(Commission {
+            name: Thing {
+                tb: "staff".into(),
+                id: eid.into(),
+            },
+            wine: Thing {
+                tb: "wines".into(),
+                id: product_id.into(),
+            },
+            amount: self.amount,
+            date: self.date,
+            created: Utc::now(),
+            modified: Utc::now(),
+        })
+    }
 }
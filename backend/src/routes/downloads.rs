@@ -0,0 +1,58 @@
+use axum::{
+    extract::{Path, Query},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::auth::CurrentUser;
+use crate::downloads;
+
+pub fn routes() -> Router {
+    Router::new().route("/downloads/:id", get(download))
+}
+
+/// Only the user a link was made for can open it.
+pub async fn download(
+    user: CurrentUser,
+    Path(id): Path<String>,
+    Query(params): Query<DownloadParams>,
+) -> impl IntoResponse {
+    match downloads::redeem(&id, params.expires, &params.signature, &user.username).await {
+        Ok(Some(export)) => (
+            [
+                (header::CONTENT_TYPE, export.content_type.to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}\"", export.filename),
+                ),
+                (header::CACHE_CONTROL, "no-store".to_string()),
+            ],
+            export.bytes,
+        )
+            .into_response(),
+        Ok(None) => (
+            StatusCode::GONE,
+            Json(json!({
+            "error": "This download link is invalid, expired or already used"
+            })),
+        )
+            .into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err.to_string()
+            })),
+        )
+            .into_response(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadParams {
+    expires: i64,
+    signature: String,
+}
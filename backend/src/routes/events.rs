@@ -0,0 +1,40 @@
+use axum::{
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
+    routing::get,
+    Router,
+};
+use futures::stream;
+use serde_json::json;
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::changes;
+
+pub fn routes() -> Router {
+    Router::new().route("/events", get(events))
+}
+
+/// Streams changes to calculations, tips, staff, commissions and wines as
+/// server-sent events named after the topic. A `resync` event means some
+/// changes were missed and everything shown should be loaded again.
+pub async fn events() -> impl IntoResponse {
+    let events = stream::unfold(changes::subscribe(), |mut changes| async move {
+        let event = match changes.recv().await {
+            Ok(change) => Event::default()
+                .event(change.topic)
+                .json_data(&change)
+                .unwrap_or_default(),
+            Err(RecvError::Lagged(missed)) => Event::default()
+                .event("resync")
+                .json_data(json!({ "missed": missed }))
+                .unwrap_or_default(),
+            Err(RecvError::Closed) => return None,
+        };
+        Some((Ok::<_, Infallible>(event), changes))
+    });
+
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
@@ -0,0 +1,192 @@
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse,
+    },
+    routing::{get, post},
+    Json, Router,
+};
+use futures::stream;
+use serde_json::json;
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::auth::CurrentUser;
+use crate::jobs::{self, Job, JobStatus, RetryAmendment};
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/jobs", get(list))
+        .route("/jobs/:id", get(job))
+        .route("/jobs/:id/retry", post(retry))
+}
+
+/// Event streams, which [`crate::auth::authenticate_stream`] lets a browser
+/// open with a stream token.
+pub fn stream_routes() -> Router {
+    Router::new().route("/jobs/:id/events", get(events))
+}
+
+pub async fn list(user: CurrentUser) -> impl IntoResponse {
+    match jobs::list(&user.username)
+        .await
+        .map_err(|err| err.to_string())
+    {
+        Ok(jobs) => Json(jobs).into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err
+            })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn job(user: CurrentUser, Path(id): Path<String>) -> impl IntoResponse {
+    match jobs::get(&id, &user.username)
+        .await
+        .map_err(|err| err.to_string())
+    {
+        Ok(Some(job)) => Json(job).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err
+            })),
+        )
+            .into_response(),
+    }
+}
+
+/// Streams the job as server-sent `job` events, starting with where it is
+/// now and ending once it has succeeded or failed.
+pub async fn events(user: CurrentUser, Path(id): Path<String>) -> impl IntoResponse {
+    // Subscribe before loading the job so no change in between is missed
+    let updates = jobs::subscribe();
+    let job = match jobs::get(&id, &user.username)
+        .await
+        .map_err(|err| err.to_string())
+    {
+        Ok(Some(job)) => job,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({
+                "error": err
+                })),
+            )
+                .into_response()
+        }
+    };
+
+    let owner = user.username;
+    let events = stream::unfold(Some((Some(job), updates)), move |state| {
+        let id = id.clone();
+        let owner = owner.clone();
+        async move {
+            let (pending, mut updates) = state?;
+            let job = match pending {
+                Some(job) => job,
+                None => next_update(&id, &owner, &mut updates).await?,
+            };
+
+            let event = Event::default()
+                .event("job")
+                .json_data(&job)
+                .unwrap_or_default();
+            let next = match job.status.is_finished() {
+                true => None,
+                false => Some((None, updates)),
+            };
+            Some((Ok::<_, Infallible>(event), next))
+        }
+    });
+
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+async fn next_update(
+    id: &str,
+    owner: &str,
+    updates: &mut tokio::sync::broadcast::Receiver<Job>,
+) -> Option<Job> {
+    loop {
+        match updates.recv().await {
+            Ok(job) if job.id == id => return Some(job),
+            Ok(_) => continue,
+            // Missed some updates, so read where the job is now instead
+            Err(RecvError::Lagged(_)) => {
+                return jobs::get(id, owner).await.ok().flatten();
+            }
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// Queues a failed job to run again. A calculation can be sent
+/// `confirmedMatches` and `overrides` to add to its input, so one that
+/// failed on unmatched staff does not fail the same way again.
+pub async fn retry(
+    user: CurrentUser,
+    Path(id): Path<String>,
+    amendment: Option<Json<RetryAmendment>>,
+) -> impl IntoResponse {
+    let mut job = match jobs::get(&id, &user.username)
+        .await
+        .map_err(|err| err.to_string())
+    {
+        Ok(Some(job)) => job,
+        Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+        Err(err) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({
+                "error": err
+                })),
+            )
+                .into_response()
+        }
+    };
+
+    let amendment = amendment
+        .map(|Json(amendment)| amendment)
+        .unwrap_or_default();
+    if let Err(err) = job.input.amend(amendment) {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({
+            "error": err
+            })),
+        )
+            .into_response();
+    }
+
+    let retried = match job.status {
+        JobStatus::Failed => jobs::retry(&job).await.map_err(|err| err.to_string()),
+        _ => Ok(None),
+    };
+    match retried {
+        Ok(Some(job)) => (StatusCode::ACCEPTED, Json(job)).into_response(),
+        Ok(None) => (
+            StatusCode::CONFLICT,
+            Json(json!({
+            "error": "Only a failed job can be retried"
+            })),
+        )
+            .into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err
+            })),
+        )
+            .into_response(),
+    }
+}
@@ -0,0 +1,275 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use chrono::{Datelike, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+
+use super::staff::summary_stats;
+use crate::auth::CurrentUser;
+use crate::model::staff::{StaffSummaryStats, SummaryParams};
+use crate::repo::{AppState, TipsRepo};
+
+/// First day of a biweekly pay period; every other Monday from here on
+/// starts a new one.
+const BIWEEKLY_ANCHOR: (i32, u32, u32) = (2024, 1, 1);
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/me/tips", get(my_tips))
+        .route("/me/pay-periods", get(my_pay_periods))
+        .route("/me/summary", get(my_summary))
+}
+
+pub async fn my_tips(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(params): Query<MyTipsParams>,
+) -> impl IntoResponse {
+    let Some(eid) = user.eid else {
+        return no_staff_record();
+    };
+
+    match nightly_breakdown(state.tips.as_ref(), eid, params.start, params.end).await {
+        Ok(nights) => Json(nights).into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err
+            })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn my_pay_periods(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Query(params): Query<MyTipsParams>,
+) -> impl IntoResponse {
+    let Some(eid) = user.eid else {
+        return no_staff_record();
+    };
+
+    let today = Utc::now().date_naive();
+    let start = params.start.unwrap_or_else(|| start_of_year(today));
+    let end = params.end.unwrap_or(today);
+
+    let nights = match nightly_breakdown(state.tips.as_ref(), eid, Some(start), Some(end)).await {
+        Ok(nights) => nights,
+        Err(err) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({
+                "error": err
+                })),
+            )
+                .into_response()
+        }
+    };
+
+    let mut periods: BTreeMap<NaiveDate, PayPeriodTotals> = BTreeMap::new();
+    for night in nights {
+        let (start, end) = params.period.bounds(night.date);
+        let totals = periods.entry(start).or_insert_with(|| PayPeriodTotals {
+            start,
+            end,
+            ..Default::default()
+        });
+        totals.nights += 1;
+        totals.hours += night.hours;
+        totals.net_tips += night.net_tips;
+        totals.total_pay += night.total_pay;
+    }
+
+    let periods: Vec<PayPeriodTotals> = periods
+        .into_values()
+        .rev()
+        .map(|mut totals| {
+            if totals.hours > 0.0 {
+                totals.effective_hourly = totals.total_pay / totals.hours;
+                totals.tipped_hourly = totals.net_tips / totals.hours;
+            }
+            totals
+        })
+        .collect();
+
+    Json(periods).into_response()
+}
+
+pub async fn my_summary(State(state): State<AppState>, user: CurrentUser) -> impl IntoResponse {
+    let Some(eid) = user.eid else {
+        return no_staff_record();
+    };
+
+    let today = Utc::now().date_naive();
+    let params = SummaryParams {
+        start: Some(start_of_year(today)),
+        end: Some(today),
+        role: None,
+    };
+
+    match summary_stats(state.tips.as_ref(), eid, params).await {
+        Ok(summary) => Json(YearToDateSummary {
+            year: today.year(),
+            summary,
+        })
+        .into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err
+            })),
+        )
+            .into_response(),
+    }
+}
+
+async fn nightly_breakdown(
+    tips: &dyn TipsRepo,
+    eid: i32,
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+) -> Result<Vec<NightlyBreakdown>, surrealdb::Error> {
+    let rows = tips.nights(eid, start, end).await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| {
+            let (effective_hourly, tipped_hourly) = match row.duration > 0.0 {
+                true => (
+                    row.total_pay_for_night / row.duration,
+                    row.net_tips / row.duration,
+                ),
+                false => (0.0, 0.0),
+            };
+
+            NightlyBreakdown {
+                date: row.date,
+                role: row.role,
+                hours: row.duration,
+                pool_share: row.pool_share,
+                tip_out_paid: row.tip_out_paid,
+                tip_out_received: row.tip_out_received,
+                net_tips: row.net_tips,
+                total_pay: row.total_pay_for_night,
+                effective_hourly,
+                tipped_hourly,
+            }
+        })
+        .collect())
+}
+
+fn start_of_year(date: NaiveDate) -> NaiveDate {
+    NaiveDate::from_ymd_opt(date.year(), 1, 1).expect("Could not create NaiveDate")
+}
+
+fn end_of_month(date: NaiveDate) -> NaiveDate {
+    let (year, month) = match date.month() {
+        12 => (date.year() + 1, 1),
+        month => (date.year(), month + 1),
+    };
+    NaiveDate::from_ymd_opt(year, month, 1).expect("Could not create NaiveDate") - Duration::days(1)
+}
+
+fn no_staff_record() -> axum::response::Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({
+        "error": "Your account is not linked to a staff member"
+        })),
+    )
+        .into_response()
+}
+
+#[derive(Debug, Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum PayPeriod {
+    Weekly,
+    #[default]
+    Biweekly,
+    Semimonthly,
+    Monthly,
+}
+
+impl PayPeriod {
+    /// First and last day of the pay period containing `date`.
+    pub fn bounds(self, date: NaiveDate) -> (NaiveDate, NaiveDate) {
+        match self {
+            PayPeriod::Weekly => {
+                let start = date - Duration::days(date.weekday().num_days_from_monday() as i64);
+                (start, start + Duration::days(6))
+            }
+            PayPeriod::Biweekly => {
+                let (year, month, day) = BIWEEKLY_ANCHOR;
+                let anchor =
+                    NaiveDate::from_ymd_opt(year, month, day).expect("Could not create NaiveDate");
+                let offset = (date - anchor).num_days().div_euclid(14) * 14;
+                let start = anchor + Duration::days(offset);
+                (start, start + Duration::days(13))
+            }
+            PayPeriod::Semimonthly => match date.day() {
+                1..=15 => (
+                    date.with_day(1).expect("Could not create NaiveDate"),
+                    date.with_day(15).expect("Could not create NaiveDate"),
+                ),
+                _ => (
+                    date.with_day(16).expect("Could not create NaiveDate"),
+                    end_of_month(date),
+                ),
+            },
+            PayPeriod::Monthly => (
+                date.with_day(1).expect("Could not create NaiveDate"),
+                end_of_month(date),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct MyTipsParams {
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+    period: PayPeriod,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NightlyBreakdown {
+    date: NaiveDate,
+    role: String,
+    hours: f32,
+    pool_share: f32,
+    tip_out_paid: f32,
+    tip_out_received: f32,
+    net_tips: f32,
+    total_pay: f32,
+    effective_hourly: f32,
+    tipped_hourly: f32,
+}
+
+#[derive(Debug, Serialize, Default)]
+pub struct PayPeriodTotals {
+    start: NaiveDate,
+    end: NaiveDate,
+    nights: u32,
+    hours: f32,
+    net_tips: f32,
+    total_pay: f32,
+    effective_hourly: f32,
+    tipped_hourly: f32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct YearToDateSummary {
+    year: i32,
+    summary: StaffSummaryStats,
+}
+
+#[cfg(test)]
+mod tests;
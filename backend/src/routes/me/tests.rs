@@ -0,0 +1,85 @@
+use super::*;
+
+fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+}
+
+#[test]
+fn biweekly_periods_start_on_the_anchor() {
+    let period = PayPeriod::Biweekly;
+
+    assert_eq!(
+        period.bounds(date(2024, 1, 1)),
+        (date(2024, 1, 1), date(2024, 1, 14))
+    );
+    assert_eq!(
+        period.bounds(date(2024, 1, 14)),
+        (date(2024, 1, 1), date(2024, 1, 14))
+    );
+    assert_eq!(
+        period.bounds(date(2024, 1, 15)),
+        (date(2024, 1, 15), date(2024, 1, 28))
+    );
+    assert_eq!(
+        period.bounds(date(2025, 3, 5)),
+        (date(2025, 2, 24), date(2025, 3, 9))
+    );
+}
+
+#[test]
+fn biweekly_periods_before_the_anchor_keep_the_same_cadence() {
+    let period = PayPeriod::Biweekly;
+
+    assert_eq!(
+        period.bounds(date(2023, 12, 31)),
+        (date(2023, 12, 18), date(2023, 12, 31))
+    );
+    assert_eq!(
+        period.bounds(date(2023, 12, 18)),
+        (date(2023, 12, 18), date(2023, 12, 31))
+    );
+    assert_eq!(
+        period.bounds(date(2023, 12, 17)),
+        (date(2023, 12, 4), date(2023, 12, 17))
+    );
+}
+
+#[test]
+fn semimonthly_periods_split_after_the_fifteenth() {
+    let period = PayPeriod::Semimonthly;
+
+    assert_eq!(
+        period.bounds(date(2024, 2, 15)),
+        (date(2024, 2, 1), date(2024, 2, 15))
+    );
+    assert_eq!(
+        period.bounds(date(2024, 2, 16)),
+        (date(2024, 2, 16), date(2024, 2, 29))
+    );
+    assert_eq!(
+        period.bounds(date(2023, 2, 20)),
+        (date(2023, 2, 16), date(2023, 2, 28))
+    );
+    assert_eq!(
+        period.bounds(date(2024, 12, 31)),
+        (date(2024, 12, 16), date(2024, 12, 31))
+    );
+}
+
+#[test]
+fn months_end_on_their_last_day() {
+    assert_eq!(end_of_month(date(2024, 12, 1)), date(2024, 12, 31));
+    assert_eq!(end_of_month(date(2024, 4, 30)), date(2024, 4, 30));
+    assert_eq!(
+        PayPeriod::Monthly.bounds(date(2024, 12, 25)),
+        (date(2024, 12, 1), date(2024, 12, 31))
+    );
+}
+
+#[test]
+fn weekly_periods_run_monday_to_sunday() {
+    assert_eq!(
+        PayPeriod::Weekly.bounds(date(2024, 12, 29)),
+        (date(2024, 12, 23), date(2024, 12, 29))
+    );
+}
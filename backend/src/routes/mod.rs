@@ -1,16 +1,65 @@
-use axum::Router;
+use axum::{middleware, Router};
 
+use crate::repo::AppState;
+
+mod analytics;
+mod auth;
+mod backups;
 mod calculations;
 mod commissions;
-mod staff;
+mod downloads;
+mod events;
+mod jobs;
+mod me;
+mod pricing;
+pub(crate) mod staff;
+#[cfg(test)]
+mod tests;
 mod tips;
+mod users;
 mod wines;
 
-pub fn routes() -> Router {
-    Router::new()
+pub fn routes(state: AppState) -> Router {
+    let repo_routes = Router::new()
         .merge(staff::routes())
-        .merge(calculations::routes())
         .merge(tips::routes())
         .merge(wines::routes())
         .merge(commissions::routes())
+        .merge(pricing::routes())
+        .merge(analytics::manager_routes())
+        .with_state(state.clone());
+
+    let manager_routes = Router::new()
+        .merge(repo_routes)
+        .merge(calculations::routes())
+        .merge(jobs::routes())
+        .route_layer(middleware::from_fn(crate::auth::require_manager));
+
+    let owner_routes = Router::new()
+        .merge(users::routes())
+        .merge(backups::routes())
+        .merge(analytics::routes().with_state(state.clone()))
+        .route_layer(middleware::from_fn(crate::auth::require_owner));
+
+    let stream_routes = Router::new()
+        .merge(events::routes())
+        .merge(jobs::stream_routes())
+        .route_layer(middleware::from_fn(crate::auth::require_manager))
+        .route_layer(middleware::from_fn(crate::auth::authenticate_stream));
+
+    Router::new()
+        .merge(manager_routes)
+        .merge(owner_routes)
+        .merge(
+            Router::new()
+                .merge(staff::self_service_routes())
+                .merge(tips::self_service_routes())
+                .merge(me::routes())
+                .with_state(state),
+        )
+        .merge(auth::routes())
+        .merge(downloads::routes())
+        .route_layer(middleware::from_fn(crate::auth::authenticate))
+        .merge(auth::public_routes())
+        .merge(stream_routes)
 }
@@ -0,0 +1,73 @@
+use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::get, Json, Router};
+use serde_json::json;
+
+use crate::pricing::{self, PriceStatus, PricingPolicy, WinePricing};
+use crate::repo::AppState;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/pricing/policy", get(policy).put(update_policy))
+        .route("/pricing/suggestions", get(suggestions))
+        .route("/pricing/report", get(report))
+}
+
+pub async fn policy(State(state): State<AppState>) -> impl IntoResponse {
+    match pricing::load_policy(&*state.wines).await {
+        Ok(policy) => Json(policy).into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err.to_string()
+            })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn update_policy(
+    State(state): State<AppState>,
+    Json(data): Json<PricingPolicy>,
+) -> impl IntoResponse {
+    match pricing::save_policy(&*state.wines, data).await {
+        Ok(policy) => Json(policy).into_response(),
+        Err(err) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({
+            "error": err.to_string()
+            })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn suggestions(State(state): State<AppState>) -> impl IntoResponse {
+    match pricing::wine_prices(&*state.wines).await {
+        Ok(wines) => Json(wines).into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err.to_string()
+            })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn report(State(state): State<AppState>) -> impl IntoResponse {
+    match pricing::wine_prices(&*state.wines).await {
+        Ok(wines) => {
+            let flagged: Vec<WinePricing> = wines
+                .into_iter()
+                .filter(|wine| wine.status != PriceStatus::Ok)
+                .collect();
+            Json(flagged).into_response()
+        }
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err.to_string()
+            })),
+        )
+            .into_response(),
+    }
+}
@@ -1,46 +1,53 @@
 use std::error::Error;
 
 use axum::{
-    extract::{Multipart, Path},
+    extract::{Multipart, Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
     routing::{get, post},
     Json, Router,
 };
-use chrono::{DateTime, NaiveDate, Utc};
-use futures::future::join_all;
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-use crate::DB;
+use crate::auth::{self, CurrentUser};
+use crate::exports::{self, ExportFilter};
+use crate::model::staff::{
+    StaffMember, StaffMemberForCreate, StaffMemberForUpdate, StaffMemberPatch, StaffSummaryStats,
+    SummaryParams, TipSummary,
+};
+use crate::repo::{self, AppState, RepoResult, StaffRepo, TipsRepo};
 
-pub fn routes() -> Router {
+pub fn routes() -> Router<AppState> {
     Router::new()
         .route("/staff", get(staff).post(new_staff_member))
         .route(
             "/staff/:eid",
-            get(staff_detail).post(staff_detail_tip_summary),
+            get(staff_detail)
+                .post(staff_detail_tip_summary)
+                .put(update_staff_member)
+                .patch(patch_staff_member)
+                .delete(delete_staff_member),
         )
-        .route("/staff/:eid/summary", get(staff_summary_stats))
+        .route("/staff/:eid/deactivate", post(deactivate_staff_member))
+        .route("/staff/:eid/reactivate", post(reactivate_staff_member))
         .route("/import-staff", post(import_staff))
-        .route("/staff/csv", post(generate_csv))
+        .route("/staff/csv", get(generate_csv))
         .route("/staff/eid-name", get(eid_name))
 }
 
-pub async fn staff() -> impl IntoResponse {
-    let staff = DB
-        .query(
-            "
-            SELECT * from staff;
-            ",
-        )
-        .await;
+/// Routes staff accounts may use for their own `eid`.
+pub fn self_service_routes() -> Router<AppState> {
+    Router::new().route("/staff/:eid/summary", get(staff_summary_stats))
+}
 
-    match staff {
-        Ok(mut staff) => {
-            let staff: Vec<StaffMember> = staff.take(0).expect("Could not get staff data");
-            (StatusCode::OK, Json(staff)).into_response()
-        }
+pub async fn staff(
+    State(state): State<AppState>,
+    Query(params): Query<StaffListParams>,
+) -> impl IntoResponse {
+    match state.staff.list(params.include_inactive).await {
+        Ok(staff) => (StatusCode::OK, Json(staff)).into_response(),
         Err(err) => (
             StatusCode::OK,
             Json(json!({
@@ -51,26 +58,9 @@ pub async fn staff() -> impl IntoResponse {
     }
 }
 
-pub async fn eid_name() -> impl IntoResponse {
-    #[derive(Debug, Deserialize, Serialize)]
-    struct EidName {
-        name: String,
-        eid: i32,
-    }
-
-    let staff = DB
-        .query(
-            "
-            SELECT name, eid FROM staff;
-            ",
-        )
-        .await;
-
-    match staff {
-        Ok(mut staff) => {
-            let staff: Vec<EidName> = staff.take(0).expect("Could not get staff data");
-            Json(staff).into_response()
-        }
+pub async fn eid_name(State(state): State<AppState>) -> impl IntoResponse {
+    match state.staff.eid_names().await {
+        Ok(staff) => Json(staff).into_response(),
         Err(err) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({
@@ -81,259 +71,394 @@ pub async fn eid_name() -> impl IntoResponse {
     }
 }
 
-pub async fn staff_detail(Path(eid): Path<i32>) -> impl IntoResponse {
-    let member = DB
-        .query(
-            "
-            SELECT * FROM staff WHERE eid=$eid;
-            ",
+pub async fn staff_detail(
+    State(state): State<AppState>,
+    Path(eid): Path<i32>,
+) -> impl IntoResponse {
+    match state.staff.get(eid).await {
+        Ok(member) => Json(member).into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err
+            })),
         )
-        .bind(("eid", eid))
-        .await;
-
-    match member {
-        Ok(mut member) => {
-            let member: Option<StaffMember> = member.take(0).expect("Could not get staff data");
-            Json(member).into_response()
-        }
-        Err(err) => {
-            dbg!(&err);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                "error": err
-                })),
-            )
-                .into_response()
-        }
+            .into_response(),
     }
 }
 
-pub async fn staff_summary_stats(Path(eid): Path<i32>) -> impl IntoResponse {
-    #[derive(Serialize)]
-    struct StaffSummaryStats {
-        net_tips_sum: f32,
-        total_hours: f32,
-        total_pay: f32,
-        average_hourly: f32,
+pub async fn staff_summary_stats(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(eid): Path<i32>,
+    Query(params): Query<SummaryParams>,
+) -> impl IntoResponse {
+    if !user.can_view(eid) {
+        return auth::forbidden();
     }
 
-    let net_tips_sum: Option<f32> = DB
-        .query(
-            "
-            let $value = SELECT array::flatten(net_tips) FROM tips WHERE eid=$eid GROUP ALL;
-            RETURN math::sum(object::values($value[0])[0]);
-            ",
-        )
-        .bind(("eid", eid))
-        .await
-        .expect("Could not sum net tips")
-        .take(1)
-        .expect("Could not get net tips");
-
-    let net_tips_sum = net_tips_sum.expect("Could not get sum of net tips");
-
-    let total_hours: Option<f32> = DB
-        .query(
-            "
-            let $value = SELECT array::flatten(duration) FROM tips WHERE eid=$eid GROUP ALL;
-            RETURN math::sum(object::values($value[0])[0]);
-            ",
-        )
-        .bind(("eid", eid))
-        .await
-        .expect("Could not sum net tips")
-        .take(1)
-        .expect("Could not get net tips");
-
-    let total_hours = total_hours.expect("Could not get total hours");
-
-    let total_pay: Option<f32> = DB
-        .query(
-            "
-            let $value = SELECT array::flatten(total_pay_for_night) FROM tips WHERE eid=$eid GROUP ALL;
-            RETURN math::sum(object::values($value[0])[0]);
-            ",
+    match summary_stats(state.tips.as_ref(), eid, params).await {
+        Ok(summary) => Json(summary).into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err
+            })),
         )
-        .bind(("eid", eid))
-        .await
-        .expect("Could not sum net tips")
-        .take(1)
-        .expect("Could not get net tips");
-
-    let total_pay = total_pay.expect("Could not get total pay");
-
-    let average_hourly: f32 = net_tips_sum / total_hours;
-
-    Json(StaffSummaryStats {
-        net_tips_sum,
-        total_pay,
-        total_hours,
-        average_hourly,
-    })
+            .into_response(),
+    }
 }
 
-pub async fn staff_detail_tip_summary(Path(eid): Path<i32>) -> impl IntoResponse {
-    let member = DB
-        .query(
-            "
-            SELECT * FROM staff WHERE eid=$eid;
-            ",
-        )
-        .bind(("eid", eid))
-        .await;
-
-    let tips = DB
-        .query(
-            "
-            SELECT date, net_tips FROM tips WHERE employee=type::thing('staff', $eid) ORDER BY date DESC LIMIT 10;
-            ",
-        )
-        .bind(("eid", eid))
-        .await;
+/// Totals of an employee's stored tip rows, optionally limited to dates
+/// between `start` and `end` inclusive and to nights worked in one role.
+/// An employee with no matching rows gets all zeros.
+pub async fn summary_stats(
+    tips: &dyn TipsRepo,
+    eid: i32,
+    params: SummaryParams,
+) -> RepoResult<StaffSummaryStats> {
+    let mut totals = tips.totals(eid, params).await?;
+    if totals.total_hours > 0.0 {
+        totals.average_tipped_hourly = totals.net_tips_sum / totals.total_hours;
+        totals.average_total_hourly = totals.total_pay / totals.total_hours;
+    }
 
-    let tips: Vec<TipSummary> = tips
-        .expect("Could not unwrap tips response")
-        .take(0)
-        .expect("Could not take tips response");
+    Ok(totals)
+}
 
-    match member {
-        Ok(mut member) => {
-            let member: Option<StaffMember> = member.take(0).expect("Could not get staff data");
-            Json(MemberSummary {
-                staff_member: member.expect("Could not unwrap staff member"),
-                tips,
-            })
-            .into_response()
-        }
-        Err(err) => {
-            dbg!(&err);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(json!({
-                "error": err
-                })),
-            )
-                .into_response()
-        }
+pub async fn staff_detail_tip_summary(
+    State(state): State<AppState>,
+    Path(eid): Path<i32>,
+) -> impl IntoResponse {
+    let member = match state.staff.get(eid).await {
+        Ok(Some(member)) => member,
+        Ok(None) => return staff_member_not_found(eid),
+        Err(err) => return internal_error(err),
+    };
+
+    match state.tips.recent(eid, 10).await {
+        Ok(tips) => Json(MemberSummary {
+            staff_member: member,
+            tips,
+        })
+        .into_response(),
+        Err(err) => internal_error(err),
     }
 }
 
-pub async fn new_staff_member(Json(data): Json<StaffMemberForCreate>) -> impl IntoResponse {
-    let staff: Vec<StaffMember> = DB
-        .create("staff")
-        .content(StaffMember {
+pub async fn new_staff_member(
+    State(state): State<AppState>,
+    Json(data): Json<StaffMemberForCreate>,
+) -> impl IntoResponse {
+    let employment = data.employment.unwrap_or_default();
+    if let Err(err) = employment.validate() {
+        return unprocessable(err);
+    }
+    if let Err(response) =
+        check_payroll_ids(state.staff.as_ref(), data.eid, &data.payroll_ids).await
+    {
+        return response;
+    }
+
+    let staff = state
+        .staff
+        .create(StaffMember {
             name: data.name,
             card_id: data.card_id,
             eid: data.eid,
+            payroll_ids: data.payroll_ids,
+            employment,
+            active: true,
+            deactivated: None,
             created: Utc::now(),
             modified: Utc::now(),
         })
-        .await
-        .expect("Could not create new Staff Member");
+        .await;
 
-    Json(staff).into_response()
+    match staff {
+        Ok(staff) => (StatusCode::CREATED, Json(staff)).into_response(),
+        Err(err) if repo::is_duplicate(&err) => (
+            StatusCode::CONFLICT,
+            Json(json!({
+            "error": format!("A staff member with eid {} already exists", data.eid)
+            })),
+        )
+            .into_response(),
+        Err(err) => internal_error(err),
+    }
 }
 
-pub async fn import_staff(mut data: Multipart) -> impl IntoResponse {
-    let mut imported_data = Vec::new();
+pub async fn update_staff_member(
+    State(state): State<AppState>,
+    Path(eid): Path<i32>,
+    Json(data): Json<StaffMemberForUpdate>,
+) -> impl IntoResponse {
+    if let Err(err) = data.employment.validate() {
+        return unprocessable(err);
+    }
+    if let Err(response) = check_payroll_ids(state.staff.as_ref(), eid, &data.payroll_ids).await {
+        return response;
+    }
 
-    while let Some(field) = data.next_field().await.unwrap() {
-        let name = field.name().unwrap();
-        match name {
-            "importFile" => {
-                (imported_data) = read_import_csv(&field.bytes().await.unwrap())
-                    .await
-                    .expect("Could not import staff data");
-            }
-            _ => continue,
+    let member = state.staff.update(eid, data).await;
+    staff_member_response(eid, member)
+}
+
+pub async fn patch_staff_member(
+    State(state): State<AppState>,
+    Path(eid): Path<i32>,
+    Json(data): Json<StaffMemberPatch>,
+) -> impl IntoResponse {
+    if let Some(payroll_ids) = &data.payroll_ids {
+        if let Err(response) = check_payroll_ids(state.staff.as_ref(), eid, payroll_ids).await {
+            return response;
+        }
+    }
+    if let Some(employment) = &data.employment {
+        let existing = match state.staff.get(eid).await {
+            Ok(existing) => existing,
+            Err(err) => return internal_error(err),
         };
+
+        if let Some(existing) = existing {
+            if let Err(err) = employment.apply(existing.employment).validate() {
+                return unprocessable(err);
+            }
+        }
     }
 
-    Json(imported_data).into_response()
+    let member = state.staff.patch(eid, data).await;
+    staff_member_response(eid, member)
 }
 
-pub async fn read_import_csv(bytes: &[u8]) -> Result<Vec<StaffMemberForCreate>, Box<dyn Error>> {
-    let mut rdr = csv::Reader::from_reader(bytes);
+pub async fn deactivate_staff_member(
+    State(state): State<AppState>,
+    Path(eid): Path<i32>,
+) -> impl IntoResponse {
+    let member = state.staff.set_active(eid, false).await;
+    staff_member_response(eid, member)
+}
 
-    let mut staff: Vec<StaffMemberForCreate> = Vec::new();
+pub async fn reactivate_staff_member(
+    State(state): State<AppState>,
+    Path(eid): Path<i32>,
+) -> impl IntoResponse {
+    let member = state.staff.set_active(eid, true).await;
+    staff_member_response(eid, member)
+}
 
-    for result in rdr.deserialize() {
-        let record: StaffMemberForCreate = result?;
-        staff.push(record);
+pub async fn delete_staff_member(
+    State(state): State<AppState>,
+    Path(eid): Path<i32>,
+) -> impl IntoResponse {
+    let tip_count = match state.tips.count_for_staff(eid).await {
+        Ok(tip_count) => tip_count,
+        Err(err) => return internal_error(err),
+    };
+
+    if tip_count > 0 {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({
+            "error": "Staff member has tip history; deactivate them instead"
+            })),
+        )
+            .into_response();
     }
 
-    join_all(
-        staff
-            .clone()
-            .into_iter()
-            .map(|member| async move {
-                DB.update::<Option<StaffMember>>(("staff", member.eid))
-                    .content(StaffMember {
-                        name: member.name,
-                        card_id: member.card_id,
-                        eid: member.eid,
-                        created: Utc::now(),
-                        modified: Utc::now(),
-                    })
-                    .await
-                    .expect("Could not post tab to DB")
-                    .expect("Could not post tab to DB")
-            })
-            .collect::<Vec<_>>(),
-    )
-    .await;
+    staff_member_response(eid, state.staff.delete(eid).await)
+}
 
-    Ok(staff)
+fn staff_member_response(
+    eid: i32,
+    member: RepoResult<Option<StaffMember>>,
+) -> axum::response::Response {
+    match member {
+        Ok(Some(member)) => Json(member).into_response(),
+        Ok(None) => staff_member_not_found(eid),
+        Err(err) => internal_error(err),
+    }
 }
 
-async fn generate_csv(body: String) -> impl IntoResponse {
-    #[derive(Serialize, Deserialize)]
-    struct StaffCsvData {
-        name: String,
-        role: String,
-        date: String,
-        net_tips: f32,
-        total_pay_for_night: f32,
-        hourly_pay_for_night: f32,
+/// Rejects payroll IDs already assigned to another staff member, since the
+/// calculation matches labor report rows on them.
+async fn check_payroll_ids(
+    staff: &dyn StaffRepo,
+    eid: i32,
+    payroll_ids: &[String],
+) -> Result<(), axum::response::Response> {
+    if payroll_ids
+        .iter()
+        .any(|payroll_id| payroll_id.trim().is_empty())
+    {
+        return Err(unprocessable("payroll_ids cannot be blank".to_string()));
+    }
+    if payroll_ids.is_empty() {
+        return Ok(());
     }
 
-    let mut rdr = csv::Reader::from_reader(body.as_bytes());
+    let taken = staff
+        .payroll_id_owner(eid, payroll_ids.to_vec())
+        .await
+        .map_err(internal_error)?;
 
-    let file_path = format!("public/downloads/staff-data-export_{}.csv", Utc::now());
+    match taken {
+        None => Ok(()),
+        Some(other) => Err((
+            StatusCode::CONFLICT,
+            Json(json!({
+            "error": format!("Payroll ID already belongs to staff member {}", other)
+            })),
+        )
+            .into_response()),
+    }
+}
 
-    let mut wtr = csv::Writer::from_path(file_path.clone()).expect("Could not init writer");
+fn unprocessable(err: String) -> axum::response::Response {
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(json!({
+        "error": err
+        })),
+    )
+        .into_response()
+}
 
-    for result in rdr.deserialize() {
-        let record: StaffCsvData = result.expect("Could not deserialize to Struct");
-        wtr.serialize(record).expect("Could not serialize record");
-    }
+fn internal_error(err: surrealdb::Error) -> axum::response::Response {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({
+        "error": err
+        })),
+    )
+        .into_response()
+}
+
+fn staff_member_not_found(eid: i32) -> axum::response::Response {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({
+        "error": format!("No staff member with eid {}", eid)
+        })),
+    )
+        .into_response()
+}
 
-    wtr.flush().expect("Could not flush writer");
+pub async fn import_staff(State(state): State<AppState>, mut data: Multipart) -> impl IntoResponse {
+    let mut imported_data = Vec::new();
+
+    loop {
+        let field = match data.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(err) => return (err.status(), err.body_text()).into_response(),
+        };
+        if field.name() != Some("importFile") {
+            continue;
+        }
 
-    (StatusCode::OK, file_path)
+        let bytes = match field.bytes().await {
+            Ok(bytes) => bytes,
+            Err(err) => return (err.status(), err.body_text()).into_response(),
+        };
+        imported_data = match read_import_csv(state.staff.as_ref(), &bytes)
+            .await
+            .map_err(|err| err.to_string())
+        {
+            Ok(imported_data) => imported_data,
+            Err(err) => return unprocessable(err),
+        };
+    }
+
+    Json(imported_data).into_response()
 }
 
-#[derive(Deserialize, Serialize, Debug)]
-pub struct StaffMember {
+/// A row of the staff import CSV. `payroll_id` is optional and may hold
+/// several IDs separated by `;`.
+#[derive(Deserialize)]
+struct StaffImportRow {
     name: String,
     card_id: String,
     eid: i32,
-    created: DateTime<Utc>,
-    modified: DateTime<Utc>,
+    #[serde(default)]
+    payroll_id: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct StaffMemberForCreate {
-    name: String,
-    card_id: String,
-    eid: i32,
+pub async fn read_import_csv(
+    repo: &dyn StaffRepo,
+    bytes: &[u8],
+) -> Result<Vec<StaffMemberForCreate>, Box<dyn Error>> {
+    let mut rdr = csv::Reader::from_reader(bytes);
+
+    let mut staff: Vec<StaffMemberForCreate> = Vec::new();
+
+    for result in rdr.deserialize() {
+        let row: StaffImportRow = result?;
+        let payroll_ids: Vec<String> = row
+            .payroll_id
+            .split(';')
+            .map(str::trim)
+            .filter(|payroll_id| !payroll_id.is_empty())
+            .map(str::to_string)
+            .collect();
+
+        if let Some(other) = staff.iter().find(|member| {
+            member.eid != row.eid
+                && member
+                    .payroll_ids
+                    .iter()
+                    .any(|payroll_id| payroll_ids.contains(payroll_id))
+        }) {
+            return Err(format!(
+                "eid {} and eid {} have the same payroll ID",
+                other.eid, row.eid
+            )
+            .into());
+        }
+        if !payroll_ids.is_empty() {
+            if let Some(other) = repo.payroll_id_owner(row.eid, payroll_ids.clone()).await? {
+                return Err(format!(
+                    "A payroll ID for eid {} already belongs to staff member {}",
+                    row.eid, other
+                )
+                .into());
+            }
+        }
+
+        staff.push(StaffMemberForCreate {
+            name: row.name,
+            card_id: row.card_id,
+            eid: row.eid,
+            payroll_ids,
+            employment: None,
+        });
+    }
+
+    repo.import(staff.clone()).await?;
+
+    Ok(staff)
+}
+
+async fn generate_csv(
+    State(state): State<AppState>,
+    Query(filter): Query<ExportFilter>,
+) -> impl IntoResponse {
+    let filename = format!(
+        "staff-data-export_{}.csv",
+        Utc::now().format("%Y-%m-%dT%H-%M-%SZ")
+    );
+
+    exports::stream_csv(filename, move |offset, limit| {
+        let filter = filter.clone();
+        let tips = state.tips.clone();
+        async move { tips.staff_export_page(filter, offset, limit).await }
+    })
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-pub struct TipSummary {
-    date: NaiveDate,
-    net_tips: f32,
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct StaffListParams {
+    include_inactive: bool,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
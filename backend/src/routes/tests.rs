@@ -0,0 +1,233 @@
+use axum::{
+    body::Body,
+    http::{header, Method, Request, StatusCode},
+    Router,
+};
+use serde_json::{json, Value};
+use tower::ServiceExt;
+
+use super::{analytics, calculations, commissions, staff, tips};
+use crate::auth::{CurrentUser, UserRole};
+use crate::testing::{app_state, block_on};
+
+/// The staff, tips and commissions routes on a database of their own,
+/// without the session check in front of them.
+async fn app() -> Router {
+    let state = app_state().await;
+    Router::new()
+        .merge(staff::routes())
+        .merge(tips::routes())
+        .merge(commissions::routes())
+        .with_state(state)
+}
+
+async fn send(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    body: Option<Value>,
+) -> (StatusCode, String) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+        .expect("Could not build request");
+    let response = app
+        .clone()
+        .oneshot(request)
+        .await
+        .expect("Could not send request");
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("Could not read response");
+    (status, String::from_utf8_lossy(&bytes).into_owned())
+}
+
+#[test]
+fn creating_a_taken_eid_conflicts() {
+    block_on(async {
+        let app = app().await;
+        let fay = json!({ "name": "Fay Ito", "card_id": "9000301", "eid": 301 });
+
+        let (status, _) = send(&app, Method::POST, "/staff", Some(fay.clone())).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (status, _) = send(&app, Method::POST, "/staff", Some(fay)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    });
+}
+
+#[test]
+fn patch_clears_and_validates_employment() {
+    block_on(async {
+        let app = app().await;
+        let fay = json!({
+            "name": "Fay Ito", "card_id": "9000302", "eid": 302,
+            "employment": { "hourly_wage": 5.5, "hire_date": "2024-03-01" }
+        });
+        send(&app, Method::POST, "/staff", Some(fay)).await;
+
+        let clear = json!({ "employment": { "hourly_wage": null } });
+        let (status, body) = send(&app, Method::PATCH, "/staff/302", Some(clear)).await;
+        assert_eq!(status, StatusCode::OK);
+        let member: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(member["employment"]["hourly_wage"], Value::Null);
+        assert_eq!(member["employment"]["hire_date"], "2024-03-01");
+
+        let before_hire = json!({ "employment": { "termination_date": "2024-01-01" } });
+        let (status, _) = send(&app, Method::PATCH, "/staff/302", Some(before_hire)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    });
+}
+
+#[test]
+fn tips_page_past_the_end_is_empty() {
+    block_on(async {
+        let app = app().await;
+
+        let (status, body) = send(
+            &app,
+            Method::GET,
+            "/tips?page=4294967295&per_page=500",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let page: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(page["data"], json!([]));
+        assert_eq!(page["totals"]["rows"], 0);
+    });
+}
+
+#[test]
+fn empty_tips_export_has_only_a_header() {
+    block_on(async {
+        let app = app().await;
+
+        let (status, body) = send(&app, Method::GET, "/tips/csv", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.lines().count(), 1);
+        assert!(body.starts_with("date,name,eid,"));
+    });
+}
+
+#[test]
+fn new_commission_takes_amount_and_date_and_rejects_bad_ids() {
+    block_on(async {
+        let app = app().await;
+
+        let sale =
+            json!({ "eid": "101", "product_id": "2001", "amount": 15, "date": "2024-07-04" });
+        let (status, body) = send(&app, Method::POST, "/commissions", Some(sale)).await;
+        assert_eq!(status, StatusCode::OK);
+        let created: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(created[0]["amount"], 15);
+        assert_eq!(created[0]["date"], "2024-07-04");
+
+        let bad_eid =
+            json!({ "eid": "Fay", "product_id": "2001", "amount": 15, "date": "2024-07-04" });
+        let (status, body) = send(&app, Method::POST, "/commissions", Some(bad_eid)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert!(body.contains("Invalid eid: Fay"));
+    });
+}
+
+/// Posts a calculation upload form with `fields` and a labor report, as a
+/// signed-in manager.
+async fn upload(fields: &[(&str, &str)]) -> (StatusCode, Value) {
+    let boundary = "calculation-upload";
+    let mut body = String::new();
+    for (name, value) in fields
+        .iter()
+        .chain(&[("laborReport", "Employee,Payroll ID\n")])
+    {
+        body.push_str(&format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+            boundary, name, value
+        ));
+    }
+    body.push_str(&format!("--{}--\r\n", boundary));
+
+    let mut request = Request::builder()
+        .method(Method::POST)
+        .uri("/calculations")
+        .header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={}", boundary),
+        )
+        .body(Body::from(body))
+        .expect("Could not build request");
+    request.extensions_mut().insert(CurrentUser {
+        username: "manager".to_string(),
+        role: UserRole::Manager,
+        eid: None,
+    });
+
+    let response = calculations::routes()
+        .oneshot(request)
+        .await
+        .expect("Could not send request");
+    let status = response.status();
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("Could not read response");
+    (
+        status,
+        serde_json::from_slice(&bytes).unwrap_or(Value::Null),
+    )
+}
+
+#[test]
+fn calculation_upload_rejects_bad_and_missing_fields() {
+    block_on(async {
+        let night = [
+            ("date", "2024-06-01"),
+            ("totalSales", "4200"),
+            ("gotabTips", "610.5"),
+            ("cashTips", "80"),
+        ];
+
+        let mut non_numeric = night;
+        non_numeric[2] = ("gotabTips", "six hundred");
+        let (status, body) = upload(&non_numeric).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "Invalid gotabTips: six hundred");
+
+        let (status, body) = upload(&night[1..]).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "Missing date");
+
+        let mut bad_date = night;
+        bad_date[0] = ("date", "06/01/2024");
+        let (status, body) = upload(&bad_date).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], "Invalid date: 06/01/2024");
+    });
+}
+
+#[test]
+fn year_over_year_rejects_years_chrono_cannot_represent() {
+    block_on(async {
+        let app = analytics::routes().with_state(app_state().await);
+
+        for year in [i32::MIN, i32::MAX, 300_000] {
+            let uri = format!("/analytics/year-over-year?year={}", year);
+            let (status, body) = send(&app, Method::GET, &uri, None).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(
+                serde_json::from_str::<Value>(&body).unwrap()["error"],
+                format!("Invalid year: {}", year)
+            );
+        }
+
+        let (status, _) = send(
+            &app,
+            Method::GET,
+            "/analytics/year-over-year?year=2024",
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+    });
+}
@@ -1,67 +1,77 @@
 use axum::{
-    extract::{Multipart, Path},
+    extract::{rejection::QueryRejection, Path, Query, State},
     http::StatusCode,
     response::IntoResponse,
     routing::{get, post},
     Json, Router,
 };
-use chrono::{DateTime, NaiveDate, Utc};
+use chrono::{NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use surrealdb::sql::Thing;
 
-use crate::DB;
+use crate::auth::{self, CurrentUser};
+use crate::exports::{self, ExportFilter};
+use crate::jobs::{self, JobInput};
+use crate::model::tips::{TippedDay, TipsSort, TipsTotals};
+use crate::repo::AppState;
 
-pub fn routes() -> Router {
+pub fn routes() -> Router<AppState> {
     Router::new()
-        .route("/tips", post(tips))
-        .route("/tips/:eid", get(staff_member_tips))
+        .route("/tips", get(tips))
         .route("/tips/csv", get(generate_csv))
+        .route("/tips/export", post(queue_export))
 }
 
-pub async fn tips(mut data: Multipart) -> impl IntoResponse {
-    #[derive(Default, Debug)]
-    struct TipsQuery<String> {
-        start_date: Option<String>,
-        end_date: Option<String>,
-    }
-    dbg!(&data);
-
-    let mut tips_query = TipsQuery::default();
+/// Routes staff accounts may use for their own `eid`.
+pub fn self_service_routes() -> Router<AppState> {
+    Router::new().route("/tips/:eid", get(staff_member_tips))
+}
 
-    while let Some(field) = data.next_field().await.unwrap() {
-        let name = field.name().unwrap();
-        match name {
-            "startDate" => {
-                tips_query.start_date =
-                    Some(field.text().await.expect("Could not get start_date text"))
-            }
-            "endDate" => {
-                tips_query.end_date = Some(field.text().await.expect("Could not get end_date text"))
-            }
-            _ => continue,
-        };
+pub async fn tips(
+    State(state): State<AppState>,
+    params: Result<Query<TipsQuery>, QueryRejection>,
+) -> impl IntoResponse {
+    let Query(params) = match params {
+        Ok(params) => params,
+        Err(rejection) => return unprocessable(rejection.body_text()),
+    };
+
+    if let Err(err) = params.validate() {
+        return unprocessable(err);
     }
 
-    dbg!(&tips_query);
-
-    let tips = DB
-        .query(
-            "
-            SELECT * from tips WHERE date >= $start AND date <= $end ORDER BY name ASC;
-            ",
+    let filter = ExportFilter {
+        start: params.start,
+        end: params.end,
+        eid: params.eid,
+        role: params.role.clone(),
+    };
+
+    let tips = state
+        .tips
+        .page(
+            filter,
+            params.sort,
+            params.per_page,
+            // Widened so a large page number cannot overflow
+            u64::from(params.page - 1) * u64::from(params.per_page),
         )
-        .bind(("start", tips_query.start_date.unwrap_or("".to_string())))
-        .bind(("end", tips_query.end_date.unwrap_or("".to_string())))
         .await;
 
     match tips {
-        Ok(mut tips) => {
-            let tips: Vec<TippedDay> = tips.take(0).expect("Could not get tips data");
-            Json(tips).into_response()
-        }
+        Ok((data, totals)) => Json(TipsPage {
+            data,
+            pagination: Pagination {
+                page: params.page,
+                per_page: params.per_page,
+                total_items: totals.rows,
+                total_pages: totals.rows.div_ceil(params.per_page),
+            },
+            totals,
+        })
+        .into_response(),
         Err(err) => (
-            StatusCode::OK,
+            StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({
             "error": err
             })),
@@ -70,21 +80,27 @@ pub async fn tips(mut data: Multipart) -> impl IntoResponse {
     }
 }
 
-pub async fn staff_member_tips(Path(eid): Path<i32>) -> impl IntoResponse {
-    let tips = DB
-        .query(
-            "
-            SELECT * from tips WHERE eid=$eid ORDER BY name ASC;
-            ",
-        )
-        .bind(("eid", eid))
-        .await;
+fn unprocessable(err: String) -> axum::response::Response {
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        Json(json!({
+        "error": err
+        })),
+    )
+        .into_response()
+}
 
-    match tips {
-        Ok(mut tips) => {
-            let tips: Vec<TippedDay> = tips.take(0).expect("Could not get tips data");
-            Json(tips).into_response()
-        }
+pub async fn staff_member_tips(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    Path(eid): Path<i32>,
+) -> impl IntoResponse {
+    if !user.can_view(eid) {
+        return auth::forbidden();
+    }
+
+    match state.tips.for_staff(eid).await {
+        Ok(tips) => Json(tips).into_response(),
         Err(err) => (
             StatusCode::OK,
             Json(json!({
@@ -95,36 +111,96 @@ pub async fn staff_member_tips(Path(eid): Path<i32>) -> impl IntoResponse {
     }
 }
 
-async fn generate_csv(body: String) -> impl IntoResponse {
-    dbg!(&body);
-    let mut rdr = csv::Reader::from_reader(body.as_bytes());
+async fn generate_csv(
+    State(state): State<AppState>,
+    Query(filter): Query<ExportFilter>,
+) -> impl IntoResponse {
+    let filename = format!(
+        "tips-data-export_{}.csv",
+        Utc::now().format("%Y-%m-%dT%H-%M-%SZ")
+    );
+
+    exports::stream_csv(filename, move |offset, limit| {
+        let filter = filter.clone();
+        let tips = state.tips.clone();
+        async move { tips.export_page(filter, offset, limit).await }
+    })
+}
 
-    let file_path = format!("public/downloads/tips-data-export_{}.csv", Utc::now());
+/// Queues writing the same CSV as `/tips/csv` to a download, for ranges
+/// too large to stream in one request.
+async fn queue_export(user: CurrentUser, Query(filter): Query<ExportFilter>) -> impl IntoResponse {
+    match jobs::enqueue(JobInput::TipsExport { filter }, None, &user.username)
+        .await
+        .map_err(|err| err.to_string())
+    {
+        Ok(job) => (StatusCode::ACCEPTED, Json(job)).into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": format!("Could not queue the export: {}", err)
+            })),
+        )
+            .into_response(),
+    }
+}
 
-    let mut wtr = csv::Writer::from_path(file_path.clone()).expect("Could not init writer");
+const MAX_PER_PAGE: u32 = 500;
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct TipsQuery {
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+    eid: Option<i32>,
+    role: Option<String>,
+    page: u32,
+    per_page: u32,
+    sort: TipsSort,
+}
 
-    for result in rdr.deserialize() {
-        let record: TippedDay = result.expect("Could not deserialize to Struct");
-        wtr.serialize(record).expect("Could not serialize record");
+impl Default for TipsQuery {
+    fn default() -> Self {
+        Self {
+            start: None,
+            end: None,
+            eid: None,
+            role: None,
+            page: 1,
+            per_page: 50,
+            sort: TipsSort::default(),
+        }
     }
+}
 
-    wtr.flush().expect("Could not flush writer");
+impl TipsQuery {
+    fn validate(&self) -> Result<(), String> {
+        if let (Some(start), Some(end)) = (self.start, self.end) {
+            if start > end {
+                return Err("start must be on or before end".to_string());
+            }
+        }
+        if self.page == 0 {
+            return Err("page starts at 1".to_string());
+        }
+        if self.per_page == 0 || self.per_page > MAX_PER_PAGE {
+            return Err(format!("per_page must be between 1 and {}", MAX_PER_PAGE));
+        }
+        Ok(())
+    }
+}
 
-    (StatusCode::OK, file_path)
+#[derive(Debug, Serialize)]
+struct Pagination {
+    page: u32,
+    per_page: u32,
+    total_items: u32,
+    total_pages: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct TippedDay {
-    name: String,
-    employee: Thing,
-    role: String,
-    net_tips: f32,
-    total_pay_for_night: f32,
-    hourly_pay_for_night: f32,
-    tipped_hour_for_night: f32,
-    duration: f32,
-    eid: i32,
-    date: NaiveDate,
-    created: DateTime<Utc>,
-    modified: DateTime<Utc>,
+#[derive(Debug, Serialize)]
+struct TipsPage {
+    data: Vec<TippedDay>,
+    pagination: Pagination,
+    totals: TipsTotals,
 }
@@ -0,0 +1,201 @@
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{delete, get, put},
+    Json, Router,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::auth::{self, CurrentUser, User, UserRole};
+use crate::repo;
+use crate::DB;
+
+pub fn routes() -> Router {
+    Router::new()
+        .route("/users", get(users).post(new_user))
+        .route("/users/:username", delete(delete_user))
+        .route("/users/:username/password", put(reset_password))
+}
+
+pub async fn users() -> impl IntoResponse {
+    let users: Result<Vec<CurrentUser>, surrealdb::Error> = async {
+        DB.query(
+            "
+            SELECT username, role, eid FROM users ORDER BY username ASC;
+            ",
+        )
+        .await?
+        .take(0)
+    }
+    .await;
+
+    match users {
+        Ok(users) => Json(users).into_response(),
+        Err(err) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({
+            "error": err
+            })),
+        )
+            .into_response(),
+    }
+}
+
+pub async fn new_user(Json(data): Json<UserForCreate>) -> impl IntoResponse {
+    if data.username.trim().is_empty() || data.password.len() < 8 {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({
+            "error": "A username and a password of at least 8 characters are required"
+            })),
+        )
+            .into_response();
+    }
+
+    if data.role == UserRole::Staff && data.eid.is_none() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({
+            "error": "Staff accounts must be linked to an eid"
+            })),
+        )
+            .into_response();
+    }
+
+    let password_hash = match auth::hash_password(&data.password).map_err(|err| err.to_string()) {
+        Ok(password_hash) => password_hash,
+        Err(err) => return internal_error(err),
+    };
+
+    let user: Result<Option<User>, surrealdb::Error> = DB
+        .create(("users", data.username.trim()))
+        .content(User {
+            username: data.username.trim().to_string(),
+            password_hash,
+            role: data.role,
+            eid: data.eid,
+            created: Utc::now(),
+            modified: Utc::now(),
+        })
+        .await;
+
+    match user {
+        Ok(Some(user)) => (
+            StatusCode::CREATED,
+            Json(CurrentUser {
+                username: user.username,
+                role: user.role,
+                eid: user.eid,
+            }),
+        )
+            .into_response(),
+        Ok(None) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        Err(err) if repo::is_duplicate(&err) => (
+            StatusCode::CONFLICT,
+            Json(json!({
+            "error": format!("A user named {} already exists", data.username.trim())
+            })),
+        )
+            .into_response(),
+        Err(err) => internal_error(err.to_string()),
+    }
+}
+
+pub async fn delete_user(user: CurrentUser, Path(username): Path<String>) -> impl IntoResponse {
+    if user.username == username {
+        return (
+            StatusCode::CONFLICT,
+            Json(json!({
+            "error": "You cannot delete your own account"
+            })),
+        )
+            .into_response();
+    }
+
+    let deleted = async {
+        DB.query(
+            "
+            DELETE sessions WHERE user = type::thing('users', $username);
+            DELETE type::thing('users', $username);
+            ",
+        )
+        .bind(("username", username))
+        .await?
+        .check()
+    }
+    .await;
+
+    match deleted {
+        Ok(_) => StatusCode::NO_CONTENT.into_response(),
+        Err(err) => internal_error(err.to_string()),
+    }
+}
+
+pub async fn reset_password(
+    Path(username): Path<String>,
+    Json(data): Json<PasswordReset>,
+) -> impl IntoResponse {
+    if data.password.len() < 8 {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({
+            "error": "Passwords must be at least 8 characters"
+            })),
+        )
+            .into_response();
+    }
+
+    let password_hash = match auth::hash_password(&data.password).map_err(|err| err.to_string()) {
+        Ok(password_hash) => password_hash,
+        Err(err) => return internal_error(err),
+    };
+
+    let updated: Result<Vec<CurrentUser>, surrealdb::Error> = async {
+        DB.query(
+            "
+            UPDATE type::thing('users', $username) SET password_hash = $password_hash, modified = $now
+                WHERE username != NONE
+                RETURN username, role, eid;
+            DELETE sessions WHERE user = type::thing('users', $username);
+            ",
+        )
+        .bind(("username", username))
+        .bind(("password_hash", password_hash))
+        .bind(("now", Utc::now()))
+        .await?
+        .take(0)
+    }
+    .await;
+
+    match updated.map(|updated| updated.into_iter().next()) {
+        Ok(Some(user)) => Json(user).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(err) => internal_error(err.to_string()),
+    }
+}
+
+fn internal_error(err: String) -> axum::response::Response {
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        Json(json!({
+        "error": err
+        })),
+    )
+        .into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UserForCreate {
+    username: String,
+    password: String,
+    role: UserRole,
+    eid: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PasswordReset {
+    password: String,
+}
@@ -1,41 +1,91 @@
-use axum::{http::StatusCode, response::IntoResponse, routing::get, Json, Router};
-use serde::{Deserialize, Serialize};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Serialize;
 use serde_json::json;
 
-use crate::DB;
+use crate::auth::CurrentUser;
+use crate::downloads::{self, DownloadLink};
+use crate::repo::AppState;
+use crate::wine_list::{self, WineListTemplate};
 
-pub fn routes() -> Router {
-    Router::new().route("/wines/name-bottle-price", get(wines_bottle_price))
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/wines/name-bottle-price", get(wines_bottle_price))
+        .route("/wines/list", post(generate_wine_list))
 }
 
-pub async fn wines_bottle_price() -> impl IntoResponse {
-    let wines = DB
-        .query(
-            "
-            SELECT base_price, display_price, name, product_id from wines ORDER BY name ASC;
-            ",
+pub async fn wines_bottle_price(State(state): State<AppState>) -> impl IntoResponse {
+    match state.wines.bottle_prices().await {
+        Ok(wines) => Json(wines).into_response(),
+        Err(err) => (
+            StatusCode::OK,
+            Json(json!({
+            "error": err
+            })),
         )
-        .await;
+            .into_response(),
+    }
+}
 
-    match wines {
-        Ok(mut wines) => {
-            let wines: Vec<WinesBottlePrice> = wines.take(0).expect("Could not get tips data");
-            Json(wines).into_response()
+pub async fn generate_wine_list(
+    State(state): State<AppState>,
+    user: CurrentUser,
+    template: Option<Json<WineListTemplate>>,
+) -> impl IntoResponse {
+    let template = template.map(|Json(template)| template).unwrap_or_default();
+    if let Err(err) = template.validate() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({
+            "error": err
+            })),
+        )
+            .into_response();
+    }
+
+    let exports = wine_list::generate(&*state.wines, template)
+        .await
+        .map_err(|err| err.to_string());
+
+    let (html, pdf) = match exports {
+        Ok(exports) => exports,
+        Err(err) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(json!({
+                "error": err
+                })),
+            )
+                .into_response()
         }
+    };
+
+    let links = downloads::store_all(vec![html, pdf], &user.username).await;
+
+    match links.as_deref() {
+        Ok([html_link, pdf_link]) => Json(WineListResponse {
+            html_link: html_link.clone(),
+            pdf_link: pdf_link.clone(),
+        })
+        .into_response(),
+        Ok(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
         Err(err) => (
-            StatusCode::OK,
+            StatusCode::INTERNAL_SERVER_ERROR,
             Json(json!({
-            "error": err
+            "error": err.to_string()
             })),
         )
             .into_response(),
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct WinesBottlePrice {
-    base_price: i32,
-    display_price: Option<String>,
-    name: String,
-    product_id: i32,
+#[derive(Debug, Serialize)]
+struct WineListResponse {
+    html_link: DownloadLink,
+    pdf_link: DownloadLink,
 }
@@ -0,0 +1,87 @@
+//! The one database setup every test uses. Code still on the global `DB`
+//! shares a copy opened in memory once; code that takes its database, or
+//! the repositories, gets a [`database`] of the test's own.
+
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use std::future::Future;
+use surrealdb::{engine::any::Any, Surreal};
+use tokio::{runtime::Runtime, sync::OnceCell};
+
+use crate::repo::AppState;
+use crate::{migrations, DB};
+
+/// One runtime for every test, since the in-memory database is driven by
+/// tasks on the runtime it was opened in.
+static RUNTIME: Lazy<Runtime> = Lazy::new(|| Runtime::new().expect("Could not start runtime"));
+static DATABASE: OnceCell<()> = OnceCell::const_new();
+
+/// `(eid, name, payroll ID)` of the staff the fixtures are matched against.
+pub const STAFF: &[(i32, &str, &str)] = &[
+    (101, "Ana Rivera", "P101"),
+    (102, "Ben Chen", "P102"),
+    (103, "Cam Okafor", "P103"),
+    (104, "Dee Lund", "P104"),
+    (105, "Eli Haas", "P105"),
+];
+
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    RUNTIME.block_on(async {
+        DATABASE.get_or_init(open_database).await;
+        future.await
+    })
+}
+
+/// A migrated database holding the [`STAFF`], shared with no other test.
+pub async fn database() -> Surreal<Any> {
+    let db = surrealdb::engine::any::connect("mem://")
+        .await
+        .expect("Could not open database");
+    prepare(&db).await;
+    db
+}
+
+/// The repositories over a [`database`] of the test's own.
+pub async fn app_state() -> AppState {
+    AppState::surreal(database().await)
+}
+
+async fn open_database() {
+    DB.connect("mem://").await.expect("Could not open database");
+    prepare(&DB).await;
+}
+
+async fn prepare(db: &Surreal<Any>) {
+    db.use_ns("test")
+        .use_db("test")
+        .await
+        .expect("Could not select database");
+    migrations::run(db)
+        .await
+        .map_err(|err| err.to_string())
+        .expect("Could not migrate");
+
+    for (eid, name, payroll_id) in STAFF {
+        db.query(
+            "
+            CREATE type::thing('staff', $eid) CONTENT {
+                name: $name,
+                card_id: $card_id,
+                eid: $eid,
+                payroll_ids: [$payroll_id],
+                created: $now,
+                modified: $now
+            };
+            ",
+        )
+        .bind(("eid", eid))
+        .bind(("name", name))
+        .bind(("card_id", format!("9000{}", eid)))
+        .bind(("payroll_id", payroll_id))
+        .bind(("now", Utc::now()))
+        .await
+        .expect("Could not seed staff")
+        .check()
+        .expect("Could not seed staff");
+    }
+}
@@ -0,0 +1,173 @@
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+
+use crate::downloads::Export;
+use crate::repo::WinesRepo;
+
+mod html;
+mod pdf;
+
+pub async fn generate(
+    repo: &dyn WinesRepo,
+    template: WineListTemplate,
+) -> Result<(Export, Export), Box<dyn Error>> {
+    let wines = repo.list_entries().await?;
+
+    let date = Utc::now().date_naive();
+    let sections = group_sections(wines, &template);
+
+    let html_filename = format!("{}_wine_list.html", date);
+    let pdf_filename = format!("{}_wine_list.pdf", date);
+
+    let html = html::render(&template, &sections, date);
+    let pdf = pdf::render(&template, &sections, date)?;
+
+    Ok((
+        Export {
+            filename: html_filename,
+            content_type: "text/html",
+            bytes: html.into_bytes(),
+        },
+        Export {
+            filename: pdf_filename,
+            content_type: "application/pdf",
+            bytes: pdf,
+        },
+    ))
+}
+
+fn group_sections(wines: Vec<WineListEntry>, template: &WineListTemplate) -> Vec<WineListSection> {
+    let mut groups: BTreeMap<String, Vec<WineListEntry>> = BTreeMap::new();
+
+    for wine in wines {
+        let heading = match template.group_by {
+            WineListGrouping::Category => wine.category.clone(),
+            WineListGrouping::Region => wine.region.clone(),
+        }
+        .filter(|heading| !heading.trim().is_empty())
+        .unwrap_or_else(|| "Other".to_string());
+
+        groups.entry(heading).or_default().push(wine);
+    }
+
+    if template.sections.is_empty() {
+        return groups
+            .into_iter()
+            .map(|(heading, wines)| WineListSection { heading, wines })
+            .collect();
+    }
+
+    template
+        .sections
+        .iter()
+        .filter_map(|heading| {
+            groups.remove(heading).map(|wines| WineListSection {
+                heading: heading.clone(),
+                wines,
+            })
+        })
+        .collect()
+}
+
+fn format_date(date: NaiveDate) -> String {
+    date.format("%B %-d, %Y").to_string()
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct WineListTemplate {
+    pub title: String,
+    pub subtitle: Option<String>,
+    pub group_by: WineListGrouping,
+    /// Section headings to include, in print order. Empty prints every
+    /// section alphabetically.
+    pub sections: Vec<String>,
+    pub show_details: bool,
+    pub show_date: bool,
+    pub footer: Option<String>,
+    /// Replaces the default stylesheet of the HTML menu.
+    pub stylesheet: Option<String>,
+}
+
+impl Default for WineListTemplate {
+    fn default() -> Self {
+        Self {
+            title: "Wine List".to_string(),
+            subtitle: None,
+            group_by: WineListGrouping::Category,
+            sections: Vec::new(),
+            show_details: true,
+            show_date: true,
+            footer: None,
+            stylesheet: None,
+        }
+    }
+}
+
+impl WineListTemplate {
+    /// Rejects a stylesheet that could close the `<style>` element it is
+    /// printed in and inject markup into the menu.
+    pub fn validate(&self) -> Result<(), String> {
+        match &self.stylesheet {
+            Some(stylesheet) if stylesheet.to_ascii_lowercase().contains("</style") => {
+                Err("The stylesheet cannot contain </style".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum WineListGrouping {
+    Category,
+    Region,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct WineListEntry {
+    pub name: String,
+    pub product_id: i32,
+    pub base_price: i32,
+    pub display_price: Option<String>,
+    pub category: Option<String>,
+    pub region: Option<String>,
+    pub vintage: Option<i32>,
+}
+
+impl WineListEntry {
+    pub fn price(&self) -> String {
+        match &self.display_price {
+            Some(display_price) if !display_price.trim().is_empty() => {
+                display_price.trim().to_string()
+            }
+            _ => self.base_price.to_string(),
+        }
+    }
+
+    pub fn details(&self, group_by: WineListGrouping) -> Option<String> {
+        let location = match group_by {
+            WineListGrouping::Category => self.region.as_deref(),
+            WineListGrouping::Region => self.category.as_deref(),
+        }
+        .filter(|location| !location.trim().is_empty());
+
+        match (self.vintage, location) {
+            (Some(vintage), Some(location)) => Some(format!("{}, {}", vintage, location)),
+            (Some(vintage), None) => Some(vintage.to_string()),
+            (None, Some(location)) => Some(location.to_string()),
+            (None, None) => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WineListSection {
+    pub heading: String,
+    pub wines: Vec<WineListEntry>,
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,82 @@
+use chrono::NaiveDate;
+
+use super::{format_date, WineListSection, WineListTemplate};
+
+const DEFAULT_STYLESHEET: &str = "
+body { font-family: Georgia, serif; max-width: 42rem; margin: 2rem auto; color: #1c1917; }
+header { text-align: center; margin-bottom: 2rem; }
+h1 { font-size: 2rem; letter-spacing: 0.1em; text-transform: uppercase; margin: 0; }
+h2 { font-size: 1.1rem; letter-spacing: 0.08em; text-transform: uppercase; border-bottom: 1px solid #a8a29e; padding-bottom: 0.25rem; }
+ul { list-style: none; padding: 0; }
+li { display: flex; justify-content: space-between; margin: 0.5rem 0; }
+.details { display: block; font-size: 0.85rem; color: #57534e; font-style: italic; }
+.price { padding-left: 1rem; white-space: nowrap; }
+footer { text-align: center; font-size: 0.85rem; color: #57534e; margin-top: 2rem; }
+@media print { body { margin: 0 auto; } section { break-inside: avoid; } }
+";
+
+pub fn render(
+    template: &WineListTemplate,
+    sections: &[WineListSection],
+    date: NaiveDate,
+) -> String {
+    let mut html = String::new();
+
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n", escape(&template.title)));
+    html.push_str(&format!(
+        "<style>{}</style>\n</head>\n<body>\n",
+        escape_stylesheet(template.stylesheet.as_deref().unwrap_or(DEFAULT_STYLESHEET))
+    ));
+
+    html.push_str(&format!("<header>\n<h1>{}</h1>\n", escape(&template.title)));
+    if let Some(subtitle) = &template.subtitle {
+        html.push_str(&format!("<p>{}</p>\n", escape(subtitle)));
+    }
+    if template.show_date {
+        html.push_str(&format!("<p>{}</p>\n", format_date(date)));
+    }
+    html.push_str("</header>\n");
+
+    for section in sections {
+        html.push_str(&format!(
+            "<section>\n<h2>{}</h2>\n<ul>\n",
+            escape(&section.heading)
+        ));
+        for wine in &section.wines {
+            let details = match wine.details(template.group_by) {
+                Some(details) if template.show_details => {
+                    format!("<span class=\"details\">{}</span>", escape(&details))
+                }
+                _ => String::new(),
+            };
+            html.push_str(&format!(
+                "<li><span class=\"name\">{}{}</span><span class=\"price\">{}</span></li>\n",
+                escape(&wine.name),
+                details,
+                escape(&wine.price())
+            ));
+        }
+        html.push_str("</ul>\n</section>\n");
+    }
+
+    if let Some(footer) = &template.footer {
+        html.push_str(&format!("<footer>{}</footer>\n", escape(footer)));
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// `<\/` means the same as `</` in CSS but cannot end the `<style>`
+/// element early.
+fn escape_stylesheet(stylesheet: &str) -> String {
+    stylesheet.replace("</", "<\\/")
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
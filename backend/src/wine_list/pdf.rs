@@ -0,0 +1,131 @@
+use chrono::NaiveDate;
+use printpdf::{
+    BuiltinFont, IndirectFontRef, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference,
+};
+use std::error::Error;
+
+use super::{format_date, WineListSection, WineListTemplate};
+
+const PAGE_WIDTH: f32 = 210.0;
+const PAGE_HEIGHT: f32 = 297.0;
+const MARGIN: f32 = 20.0;
+const PRICE_COLUMN: f32 = PAGE_WIDTH - MARGIN - 20.0;
+
+pub fn render(
+    template: &WineListTemplate,
+    sections: &[WineListSection],
+    date: NaiveDate,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let (doc, page, layer) =
+        PdfDocument::new(&template.title, Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+
+    let mut writer = PageWriter {
+        layer: doc.get_page(page).get_layer(layer),
+        regular: doc.add_builtin_font(BuiltinFont::TimesRoman)?,
+        bold: doc.add_builtin_font(BuiltinFont::TimesBold)?,
+        italic: doc.add_builtin_font(BuiltinFont::TimesItalic)?,
+        doc: &doc,
+        cursor: PAGE_HEIGHT - MARGIN,
+    };
+
+    writer.centered(&template.title.to_uppercase(), 22.0, FontStyle::Bold);
+    if let Some(subtitle) = &template.subtitle {
+        writer.centered(subtitle, 12.0, FontStyle::Italic);
+    }
+    if template.show_date {
+        writer.centered(&format_date(date), 10.0, FontStyle::Regular);
+    }
+    writer.cursor -= 6.0;
+
+    for section in sections {
+        // Keep a heading on the same page as at least its first wine.
+        writer.reserve(20.0);
+        writer.line(&section.heading.to_uppercase(), 13.0, FontStyle::Bold);
+        writer.cursor -= 2.0;
+
+        for wine in &section.wines {
+            let details = wine
+                .details(template.group_by)
+                .filter(|_| template.show_details);
+
+            writer.reserve(if details.is_some() { 11.0 } else { 7.0 });
+            writer.layer.use_text(
+                wine.price(),
+                11.0,
+                Mm(PRICE_COLUMN),
+                Mm(writer.cursor),
+                &writer.regular,
+            );
+            writer.line(&wine.name, 11.0, FontStyle::Regular);
+            if let Some(details) = details {
+                writer.line(&details, 9.0, FontStyle::Italic);
+            }
+            writer.cursor -= 1.5;
+        }
+        writer.cursor -= 5.0;
+    }
+
+    if let Some(footer) = &template.footer {
+        writer.reserve(10.0);
+        writer.centered(footer, 9.0, FontStyle::Italic);
+    }
+
+    Ok(doc.save_to_bytes()?)
+}
+
+enum FontStyle {
+    Regular,
+    Bold,
+    Italic,
+}
+
+struct PageWriter<'a> {
+    doc: &'a PdfDocumentReference,
+    layer: PdfLayerReference,
+    regular: IndirectFontRef,
+    bold: IndirectFontRef,
+    italic: IndirectFontRef,
+    /// Baseline of the next line, in millimetres from the bottom of the page.
+    cursor: f32,
+}
+
+impl PageWriter<'_> {
+    fn font(&self, style: FontStyle) -> &IndirectFontRef {
+        match style {
+            FontStyle::Regular => &self.regular,
+            FontStyle::Bold => &self.bold,
+            FontStyle::Italic => &self.italic,
+        }
+    }
+
+    fn reserve(&mut self, height: f32) {
+        if self.cursor - height < MARGIN {
+            let (page, layer) = self
+                .doc
+                .add_page(Mm(PAGE_WIDTH), Mm(PAGE_HEIGHT), "Layer 1");
+            self.layer = self.doc.get_page(page).get_layer(layer);
+            self.cursor = PAGE_HEIGHT - MARGIN;
+        }
+    }
+
+    fn line(&mut self, text: &str, size: f32, style: FontStyle) {
+        self.layer
+            .use_text(text, size, Mm(MARGIN), Mm(self.cursor), self.font(style));
+        self.cursor -= line_height(size);
+    }
+
+    fn centered(&mut self, text: &str, size: f32, style: FontStyle) {
+        // Built-in fonts carry no metrics, so approximate the text width
+        // from the average glyph width of a serif face.
+        let width = text.chars().count() as f32 * size * 0.5 * 0.3528;
+        let x = ((PAGE_WIDTH - width) / 2.0).max(MARGIN);
+        self.layer
+            .use_text(text, size, Mm(x), Mm(self.cursor), self.font(style));
+        self.cursor -= line_height(size);
+    }
+}
+
+fn line_height(size: f32) -> f32 {
+    // Points to millimetres, with 40% leading.
+    size * 0.3528 * 1.4
+}
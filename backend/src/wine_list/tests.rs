@@ -0,0 +1,145 @@
+use super::*;
+use crate::repo::AppState;
+use crate::testing::{block_on, database};
+
+fn entry(name: &str, category: Option<&str>, region: Option<&str>) -> WineListEntry {
+    WineListEntry {
+        name: name.to_string(),
+        product_id: 0,
+        base_price: 48,
+        display_price: None,
+        category: category.map(str::to_string),
+        region: region.map(str::to_string),
+        vintage: Some(2021),
+    }
+}
+
+fn wines() -> Vec<WineListEntry> {
+    vec![
+        entry("Albariño", Some("White"), Some("Rías Baixas")),
+        entry("Barbera", Some("Red"), Some("Piedmont")),
+        entry("Cava", Some(" "), None),
+        entry("Dolcetto", Some("Red"), Some("Piedmont")),
+    ]
+}
+
+fn headings(sections: &[WineListSection]) -> Vec<(&str, Vec<&str>)> {
+    sections
+        .iter()
+        .map(|section| {
+            (
+                section.heading.as_str(),
+                section
+                    .wines
+                    .iter()
+                    .map(|wine| wine.name.as_str())
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn sections_are_alphabetical_with_blank_headings_under_other() {
+    let sections = group_sections(wines(), &WineListTemplate::default());
+
+    assert_eq!(
+        headings(&sections),
+        vec![
+            ("Other", vec!["Cava"]),
+            ("Red", vec!["Barbera", "Dolcetto"]),
+            ("White", vec!["Albariño"]),
+        ]
+    );
+}
+
+#[test]
+fn template_sections_pick_and_order_the_headings() {
+    let template = WineListTemplate {
+        group_by: WineListGrouping::Region,
+        sections: vec![
+            "Piedmont".to_string(),
+            "Mosel".to_string(),
+            "Other".to_string(),
+        ],
+        ..Default::default()
+    };
+
+    assert_eq!(
+        headings(&group_sections(wines(), &template)),
+        vec![
+            ("Piedmont", vec!["Barbera", "Dolcetto"]),
+            ("Other", vec!["Cava"])
+        ]
+    );
+}
+
+#[test]
+fn html_escapes_text_and_shows_details_when_asked() {
+    let mut wine = entry("Rosé <Cuvée> & \"Co\"", Some("Rosé"), Some("Provence"));
+    wine.display_price = Some(" 14 / 52 ".to_string());
+    let sections = vec![WineListSection {
+        heading: "Rosé".to_string(),
+        wines: vec![wine],
+    }];
+    let date = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+    let template = WineListTemplate {
+        footer: Some("Ask about <pairings>".to_string()),
+        ..Default::default()
+    };
+
+    let html = html::render(&template, &sections, date);
+    assert!(html.contains("Rosé &lt;Cuvée&gt; &amp; &quot;Co&quot;"));
+    assert!(html.contains("<span class=\"details\">2021, Provence</span>"));
+    assert!(html.contains("<span class=\"price\">14 / 52</span>"));
+    assert!(html.contains("<p>June 1, 2024</p>"));
+    assert!(html.contains("<footer>Ask about &lt;pairings&gt;</footer>"));
+
+    let plain = WineListTemplate {
+        show_details: false,
+        show_date: false,
+        ..Default::default()
+    };
+    let html = html::render(&plain, &sections, date);
+    assert!(!html.contains("class=\"details\""));
+    assert!(!html.contains("June 1, 2024"));
+}
+
+#[test]
+fn pdf_renders_a_document() {
+    let sections = group_sections(wines(), &WineListTemplate::default());
+    let date = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+
+    let pdf = pdf::render(&WineListTemplate::default(), &sections, date)
+        .map_err(|err| err.to_string())
+        .expect("Could not render the PDF");
+    assert!(pdf.starts_with(b"%PDF-"));
+}
+
+#[test]
+fn generate_lists_only_active_wines() {
+    block_on(async {
+        let db = database().await;
+        db.query(
+            "
+            CREATE wines:1 CONTENT { name: 'Barbera', product_id: 1, base_price: 16, category: 'Red', active: true };
+            CREATE wines:2 CONTENT { name: 'Cava', product_id: 2, base_price: 12, category: 'Sparkling' };
+            CREATE wines:3 CONTENT { name: 'Dolcetto', product_id: 3, base_price: 14, category: 'Red', active: false };
+            ",
+        )
+        .await
+        .unwrap()
+        .check()
+        .unwrap();
+        let state = AppState::surreal(db);
+
+        let (html, pdf) = generate(&*state.wines, WineListTemplate::default())
+            .await
+            .map_err(|err| err.to_string())
+            .unwrap();
+        let html = String::from_utf8(html.bytes).unwrap();
+        assert!(html.contains("Barbera") && html.contains("Cava"));
+        assert!(!html.contains("Dolcetto"));
+        assert_eq!(pdf.content_type, "application/pdf");
+    });
+}
@@ -6,9 +6,15 @@ interface StaffMember {
 	modified: string;
 }
 
+interface DownloadLink {
+	filename: string;
+	url: string;
+	expires: string;
+}
+
 interface CalculationsResponse {
-	calculations_link: string;
-	template_link: string;
+	calculations_link: DownloadLink;
+	template_link: DownloadLink;
 	summary: Summary;
 	tips: Tip[];
 }
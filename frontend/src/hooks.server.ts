@@ -0,0 +1,24 @@
+import { redirect, type Handle, type HandleFetch } from '@sveltejs/kit';
+import { SESSION_COOKIE } from '$lib/server/session';
+
+export const handle: Handle = async ({ event, resolve }) => {
+	event.locals.token = event.cookies.get(SESSION_COOKIE);
+
+	if (!event.locals.token && event.url.pathname.startsWith('/api/')) {
+		return new Response(null, { status: 401 });
+	}
+	if (!event.locals.token && event.url.pathname !== '/login') {
+		redirect(303, '/login');
+	}
+
+	return resolve(event);
+};
+
+// Every request to the backend goes out with the session's bearer token
+export const handleFetch: HandleFetch = async ({ event, request, fetch }) => {
+	if (event.locals.token && request.url.startsWith(import.meta.env.VITE_BACKEND_URL)) {
+		request.headers.set('Authorization', `Bearer ${event.locals.token}`);
+	}
+
+	return fetch(request);
+};
@@ -0,0 +1,19 @@
+/** Saves `blob` through the browser's usual download prompt. */
+export function saveFile(blob: Blob, filename: string) {
+	const url = URL.createObjectURL(blob);
+	const link = document.createElement('a');
+	link.href = url;
+	link.download = filename;
+	link.click();
+	URL.revokeObjectURL(url);
+}
+
+/** Downloads a CSV export the backend streams from `path`. */
+export async function downloadCsv(path: string) {
+	const res = await fetch(`/api${path}`);
+	if (!res.ok) return;
+
+	const disposition = res.headers.get('content-disposition') ?? '';
+	const filename = /filename="([^"]+)"/.exec(disposition)?.[1] ?? 'export.csv';
+	saveFile(await res.blob(), filename);
+}
@@ -0,0 +1,2 @@
+/** The cookie holding the backend session's bearer token. */
+export const SESSION_COOKIE = 'session';
@@ -3,6 +3,16 @@ import { zod } from 'sveltekit-superforms/adapters';
 import { formSchema } from './schema';
 import type { PageServerLoad, Actions } from './$types';
 
+const JOB_POLL_MS = 500;
+const JOB_TIMEOUT_MS = 120_000;
+
+interface Job {
+	id: string;
+	status: 'queued' | 'running' | 'succeeded' | 'failed';
+	result?: CalculationsResponse;
+	error?: string;
+}
+
 export const load: PageServerLoad = async () => {
 	return {
 		form: await superValidate(zod(formSchema))
@@ -10,7 +20,7 @@ export const load: PageServerLoad = async () => {
 };
 
 export const actions = {
-	default: async ({ request }) => {
+	default: async ({ request, fetch }) => {
 		const formData = await request.formData();
 		console.log(formData);
 		const superform = await superValidate(formData, zod(formSchema));
@@ -22,9 +32,27 @@ export const actions = {
 			body: formData
 		});
 
-		const calculationsResponse: CalculationsResponse = await response.json();
+		if (!response.ok) {
+			const { error } = await response.json();
+			return fail(response.status, withFiles({ form: superform, error }));
+		}
+
+		// The calculation runs as a background job; wait for its result
+		let job: Job = await response.json();
+		for (let waited = 0; !['succeeded', 'failed'].includes(job.status); waited += JOB_POLL_MS) {
+			if (waited > JOB_TIMEOUT_MS) {
+				const error = 'The calculation is taking too long';
+				return fail(504, withFiles({ form: superform, error }));
+			}
+			await new Promise((resolve) => setTimeout(resolve, JOB_POLL_MS));
+			job = await (await fetch(`${import.meta.env.VITE_BACKEND_URL}/jobs/${job.id}`)).json();
+		}
+
+		if (job.status === 'failed') {
+			return fail(422, withFiles({ form: superform, error: job.error }));
+		}
 
-		console.log(calculationsResponse);
+		const calculationsResponse = job.result as CalculationsResponse;
 
 		return withFiles({ calculationsResponse, form: superform });
 	}
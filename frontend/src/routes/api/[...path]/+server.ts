@@ -0,0 +1,25 @@
+import type { RequestHandler } from './$types';
+
+// Browser code reaches the backend through here, so the session token stays
+// in its httpOnly cookie and handleFetch adds it on the way out
+const forward: RequestHandler = async ({ params, url, request, fetch }) => {
+	const headers = new Headers();
+	const contentType = request.headers.get('content-type');
+	if (contentType) headers.set('content-type', contentType);
+
+	const response = await fetch(`${import.meta.env.VITE_BACKEND_URL}/${params.path}${url.search}`, {
+		method: request.method,
+		body: request.method === 'GET' ? undefined : await request.arrayBuffer(),
+		headers
+	});
+
+	const forwarded = new Headers();
+	for (const name of ['content-type', 'content-disposition']) {
+		const value = response.headers.get(name);
+		if (value) forwarded.set(name, value);
+	}
+	return new Response(response.body, { status: response.status, headers: forwarded });
+};
+
+export const GET = forward;
+export const POST = forward;
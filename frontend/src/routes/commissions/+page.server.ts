@@ -36,18 +36,13 @@ export const actions: Actions = {
 			});
 		}
 
-		console.log(
-			JSON.stringify({
-				eid: commissionsData.get('eid'),
-				product_id: commissionsData.get('product_id')
-			})
-		);
-
 		const response = await fetch(`${import.meta.env.VITE_BACKEND_URL}/commissions`, {
 			method: 'POST',
 			body: JSON.stringify({
 				eid: commissionsData.get('eid'),
-				product_id: commissionsData.get('product_id')
+				product_id: commissionsData.get('product_id'),
+				amount: commissionsForm.data.amount,
+				date: commissionsForm.data.date
 			}),
 			headers: { 'Content-type': 'application/json' }
 		});
@@ -2,7 +2,9 @@ import { z } from 'zod';
 
 export const commissionsSchema = z.object({
 	eid: z.number(),
-	product_id: z.number()
+	product_id: z.number(),
+	amount: z.number().int().min(0),
+	date: z.string().date()
 });
 
 export type FormSchema = typeof commissionsSchema;
@@ -0,0 +1,36 @@
+import { fail, redirect } from '@sveltejs/kit';
+import type { Actions } from './$types';
+import { SESSION_COOKIE } from '$lib/server/session';
+
+interface LoginResponse {
+	token: string;
+	expires: string;
+}
+
+export const actions: Actions = {
+	default: async ({ request, fetch, cookies }) => {
+		const data = await request.formData();
+		const username = data.get('username')?.toString() ?? '';
+
+		const response = await fetch(`${import.meta.env.VITE_BACKEND_URL}/auth/login`, {
+			method: 'POST',
+			body: JSON.stringify({ username, password: data.get('password')?.toString() ?? '' }),
+			headers: { 'Content-type': 'application/json' }
+		});
+
+		if (!response.ok) {
+			return fail(response.status, { username, error: 'Invalid username or password' });
+		}
+
+		const login: LoginResponse = await response.json();
+
+		cookies.set(SESSION_COOKIE, login.token, {
+			path: '/',
+			httpOnly: true,
+			sameSite: 'lax',
+			expires: new Date(login.expires)
+		});
+
+		redirect(303, '/');
+	}
+};
@@ -0,0 +1,12 @@
+import { redirect } from '@sveltejs/kit';
+import type { Actions } from './$types';
+import { SESSION_COOKIE } from '$lib/server/session';
+
+export const actions: Actions = {
+	default: async ({ fetch, cookies }) => {
+		await fetch(`${import.meta.env.VITE_BACKEND_URL}/auth/logout`, { method: 'POST' });
+		cookies.delete(SESSION_COOKIE, { path: '/' });
+
+		redirect(303, '/login');
+	}
+};
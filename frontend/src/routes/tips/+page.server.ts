@@ -1,36 +1,42 @@
 import { superValidate } from 'sveltekit-superforms';
 import { zod } from 'sveltekit-superforms/adapters';
 import { tipsSchema } from './schema';
-import type { PageServerLoad, Actions } from './$types.js';
-import { fail } from '@sveltejs/kit';
+import type { PageServerLoad } from './$types.js';
+import { error } from '@sveltejs/kit';
 
-export const load: PageServerLoad = async () => {
-	const tipsForm = await superValidate(zod(tipsSchema));
+/** Rows loaded per page of the table. */
+const PER_PAGE = 50;
 
-	return { tipsForm };
-};
+interface TipsPage {
+	data: TippedDay[];
+	pagination: { page: number; per_page: number; total_items: number; total_pages: number };
+}
 
-export const actions: Actions = {
-	default: async ({ request }) => {
-		const formData = await request.formData();
-		console.log(formData);
-		const form = await superValidate(formData, zod(tipsSchema));
-		if (!form.valid) {
-			return fail(400, {
-				form
-			});
-		}
+// The date range and page live in the URL, so only the page on screen is fetched
+export const load: PageServerLoad = async ({ url, fetch }) => {
+	const tipsForm = await superValidate(url, zod(tipsSchema));
+	if (!tipsForm.valid) {
+		return { tipsForm, tippedDays: [], pagination: null };
+	}
 
-		const response = await fetch(`${import.meta.env.VITE_BACKEND_URL}/tips`, {
-			method: 'POST',
-			body: formData
-		});
+	const page = Math.max(1, Math.floor(Number(url.searchParams.get('page'))) || 1);
+	const query = new URLSearchParams({
+		start: tipsForm.data.startDate,
+		end: tipsForm.data.endDate,
+		page: `${page}`,
+		per_page: `${PER_PAGE}`
+	});
+	const response = await fetch(`${import.meta.env.VITE_BACKEND_URL}/tips?${query}`);
+	if (!response.ok) {
+		const { error: message } = await response.json();
+		error(response.status, message);
+	}
 
-		const tippedDays: TippedDay[] = await response.json();
+	const tipsPage: TipsPage = await response.json();
 
-		return {
-			tippedDays,
-			form
-		};
-	}
+	return {
+		tipsForm,
+		tippedDays: tipsPage.data,
+		pagination: tipsPage.pagination
+	};
 };